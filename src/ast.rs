@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::fmt;
 
 use crate::{error::Error, token::Token};
 
@@ -13,11 +13,33 @@ pub enum Node {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Statement {
     Let(LetStatement),
+    Const(LetStatement),
     Return(ReturnStatement),
+    Throw(ThrowStatement),
+    Enum(EnumStatement),
+    Import(ImportStatement),
+    /// `export` followed by a `let`, `const` or `enum` statement at the top
+    /// level of a module.
+    Export(Box<Statement>),
     Expression(Expression),
     Empty,
 }
 
+impl Statement {
+    /// The expressions directly contained in this statement.
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Statement::Let(LetStatement { value, .. })
+            | Statement::Const(LetStatement { value, .. })
+            | Statement::Return(ReturnStatement { value })
+            | Statement::Throw(ThrowStatement { value })
+            | Statement::Expression(value) => vec![value],
+            Statement::Export(statement) => statement.children_mut(),
+            Statement::Enum(_) | Statement::Import(_) | Statement::Empty => vec![],
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Program {
@@ -29,6 +51,8 @@ pub struct Program {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LetStatement {
     pub name: Ident,
+    /// `let name: type = value;`
+    pub annotation: Option<Type>,
     pub value: Expression,
 }
 
@@ -38,6 +62,43 @@ pub struct ReturnStatement {
     pub value: Expression,
 }
 
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThrowStatement {
+    pub value: Expression,
+}
+
+/// `import "path" as alias;` or `import { name, ... } from "path";`
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportStatement {
+    pub path: String,
+    pub imported: Imported,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Imported {
+    /// The namespace of the module, bound to the alias.
+    Module(Ident),
+    /// The listed exports of the module, bound to their names.
+    Names(Vec<Ident>),
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumStatement {
+    pub name: Ident,
+    pub variants: Vec<EnumVariant>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumVariant {
+    pub name: Ident,
+    pub fields: Vec<Ident>,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Expression {
@@ -69,6 +130,254 @@ pub enum Expression {
         indexee: Box<Expression>,
         index: Box<Expression>,
     },
+    /// `sliced[start:end:step]`, where every bound is optional.
+    Sliced {
+        sliced: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+        step: Option<Box<Expression>>,
+    },
+    Match {
+        subject: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+    /// `[element for x in iterable if condition]`, or
+    /// `def { key: element for x in iterable }` if `key` is given.
+    Comprehension {
+        key: Option<Box<Expression>>,
+        element: Box<Expression>,
+        clauses: Vec<Clause>,
+    },
+    /// `try { ... } catch e { ... } finally { ... }`, where either `catch` or
+    /// `finally` can be left out.
+    Try {
+        body: Box<Expression>,
+        catch: Option<Catch>,
+        finally: Option<Box<Expression>>,
+    },
+    /// `value?`, unwraps an `ok` result or returns an `err` result from the
+    /// enclosing function.
+    Propagate(Box<Expression>),
+    /// `yield value`, suspends the running generator.
+    Yield(Box<Expression>),
+    /// `..value`, expands an iterable into a vector literal or the arguments
+    /// of an invocation.
+    Spread(Box<Expression>),
+    /// `spawn function`, calls `function` on a new green thread.
+    Spawn(Box<Expression>),
+    /// `await future`, blocks until `future` is settled and gives its value.
+    Await(Box<Expression>),
+    /// `name: value`, a named argument of an invocation.
+    Named {
+        name: Ident,
+        value: Box<Expression>,
+    },
+}
+
+impl Expression {
+    /// The expressions directly contained in this one, including those in
+    /// contained statements.
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::Program(Program { statements, .. }) | Expression::Block { statements } => {
+                statements
+                    .iter_mut()
+                    .flat_map(Statement::children_mut)
+                    .collect()
+            }
+            Expression::Ident(_) => vec![],
+            Expression::Literal(literal) => match literal {
+                Literal::Int(_) | Literal::String(_) | Literal::Bool(_) => vec![],
+                Literal::Function { clauses, .. } => clauses
+                    .iter_mut()
+                    .flat_map(|clause| {
+                        clause
+                            .parameters
+                            .iter_mut()
+                            .filter_map(|parameter| parameter.default.as_mut())
+                            .chain(clause.guard.as_mut())
+                            .chain([&mut *clause.body])
+                    })
+                    .collect(),
+                Literal::Macro { body, .. } => vec![body],
+                Literal::Collection { members, parent } => members
+                    .iter_mut()
+                    .map(|member| match member {
+                        Member::Named(_, value) | Member::Spread(value) => value,
+                    })
+                    .chain(parent.as_deref_mut())
+                    .collect(),
+                Literal::Vector { elements } => elements.iter_mut().collect(),
+            },
+            Expression::Infix { lhs, rhs, .. } => vec![lhs, rhs],
+            Expression::Prefix { operand, .. } => vec![operand],
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => [&mut **condition, &mut **consequence]
+                .into_iter()
+                .chain(alternative.as_deref_mut())
+                .collect(),
+            Expression::Invoked { invoked, args } => [&mut **invoked]
+                .into_iter()
+                .chain(args.iter_mut())
+                .collect(),
+            Expression::Indexed { indexee, index } => vec![indexee, index],
+            Expression::Sliced {
+                sliced,
+                start,
+                end,
+                step,
+            } => [&mut **sliced]
+                .into_iter()
+                .chain(start.as_deref_mut())
+                .chain(end.as_deref_mut())
+                .chain(step.as_deref_mut())
+                .collect(),
+            Expression::Match { subject, arms } => [&mut **subject]
+                .into_iter()
+                .chain(arms.iter_mut().map(|arm| &mut arm.body))
+                .collect(),
+            Expression::Comprehension {
+                key,
+                element,
+                clauses,
+            } => key
+                .as_deref_mut()
+                .into_iter()
+                .chain([&mut **element])
+                .chain(clauses.iter_mut().map(|clause| match clause {
+                    Clause::For { iterable, .. } => iterable,
+                    Clause::If(condition) => condition,
+                }))
+                .collect(),
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => [&mut **body]
+                .into_iter()
+                .chain(catch.as_mut().map(|catch| &mut *catch.body))
+                .chain(finally.as_deref_mut())
+                .collect(),
+            Expression::Propagate(value)
+            | Expression::Yield(value)
+            | Expression::Spread(value)
+            | Expression::Spawn(value)
+            | Expression::Await(value)
+            | Expression::Named { value, .. } => vec![value],
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expression,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// `_`, matches anything without binding it.
+    Wildcard,
+    /// `x`, matches anything and binds it to `x`.
+    Binding(Ident),
+    /// `1`, `"a"` or `true`, matches equal values.
+    Literal(Literal),
+    /// `Shape.Circle(r)`, `Circle(r)` or `Shape.Empty`, matches variants
+    /// with the given tag whose fields match `fields`.
+    Variant {
+        enumeration: Option<Ident>,
+        tag: Ident,
+        fields: Vec<Pattern>,
+    },
+    /// `[a, b]` or `[head, ..tail]`, matches vectors whose elements match
+    /// `elements`, with any number of elements left for `rest`.
+    Vector {
+        elements: Vec<Pattern>,
+        rest: Option<Ident>,
+    },
+}
+
+impl Pattern {
+    /// The names the pattern binds.
+    pub fn bindings(&self) -> Vec<&Ident> {
+        match self {
+            Pattern::Binding(ident) => vec![ident],
+            Pattern::Variant { fields, .. } => fields.iter().flat_map(Pattern::bindings).collect(),
+            Pattern::Vector { elements, rest } => elements
+                .iter()
+                .flat_map(Pattern::bindings)
+                .chain(rest)
+                .collect(),
+            Pattern::Wildcard | Pattern::Literal(_) => vec![],
+        }
+    }
+}
+
+/// `catch e { ... }`, binding the caught error value to `e` if given.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Catch {
+    pub binding: Option<Ident>,
+    pub body: Box<Expression>,
+}
+
+/// A clause of a comprehension, applied to everything produced by the
+/// clauses before it.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clause {
+    /// `for binding in iterable`, repeats the following clauses for every
+    /// element of `iterable`.
+    For {
+        binding: Ident,
+        iterable: Expression,
+    },
+    /// `if condition`, skips elements for which `condition` is falsy.
+    If(Expression),
+}
+
+/// A clause of a function, `(parameters) -> type if guard { body }`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionClause {
+    pub parameters: Vec<Parameter>,
+    pub rest: Option<Ident>,
+    /// `fn() -> type { ... }`
+    pub returns: Option<Type>,
+    /// Evaluated after binding the parameters, skipping the clause if falsy.
+    pub guard: Option<Expression>,
+    /// Whether the body contains `yield`.
+    pub generator: bool,
+    /// Whether the function was declared with `async fn`, so that calling it
+    /// gives a future.
+    pub r#async: bool,
+    pub body: Box<Expression>,
+}
+
+/// A function parameter, `a`, `b = 2`, `c: int` or a pattern like `0` or
+/// `[x, ..xs]`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter {
+    pub pattern: Pattern,
+    pub annotation: Option<Type>,
+    /// Evaluated in the function's frame when no argument is passed.
+    pub default: Option<Expression>,
+}
+
+impl Parameter {
+    /// The name the parameter can be passed by, if it isn't a pattern.
+    pub fn ident(&self) -> Option<&Ident> {
+        match &self.pattern {
+            Pattern::Binding(ident) => Some(ident),
+            _ => None,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -83,22 +392,94 @@ pub enum Literal {
     Int(i32),
     String(String),
     Bool(Bool),
+    /// `fn(parameters) { body }`, or `fn name(...) { ... } | name(...) { ... }`
+    /// with clauses tried in order, where `name` is bound to the function in
+    /// its clauses.
     Function {
+        name: Option<Ident>,
+        clauses: Vec<FunctionClause>,
+        capture: Vec<Ident>,
+    },
+    /// `macro(parameters) { body }`, called with the quoted AST of its
+    /// arguments during expansion.
+    Macro {
         parameters: Vec<Ident>,
         body: Box<Expression>,
-        capture: Vec<Ident>,
     },
     Collection {
-        members: HashMap<Ident, Expression>,
+        members: Vec<Member>,
+        parent: Option<Box<Expression>>,
     },
     Vector {
         elements: Vec<Expression>,
     },
 }
 
+/// A member of `def { ... }`. Members are added in order, so later ones
+/// replace earlier ones of the same name.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Member {
+    /// `name = value`
+    Named(Ident, Expression),
+    /// `..collection`, copying all members of `collection`.
+    Spread(Expression),
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Bool {
     True,
     False,
 }
+
+/// A type annotation. Values of type `any` are only checked once they reach an
+/// annotation.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Any,
+    Int,
+    Bool,
+    Str,
+    Unit,
+    /// `[element]`
+    Vector(Box<Type>),
+    /// `{ name: type, ... }`, a collection with at least these members.
+    Record(Vec<(Ident, Type)>),
+    /// `fn(parameters) -> returns`
+    Function {
+        parameters: Vec<Type>,
+        returns: Box<Type>,
+    },
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Str => write!(f, "str"),
+            Type::Unit => write!(f, "unit"),
+            Type::Vector(element) => write!(f, "[{element}]"),
+            Type::Record(members) => {
+                let members = members
+                    .iter()
+                    .map(|(name, r#type)| format!("{}: {type}", name.name))
+                    .collect::<Vec<_>>();
+                write!(f, "{{ {} }}", members.join(", "))
+            }
+            Type::Function {
+                parameters,
+                returns,
+            } => {
+                let parameters = parameters
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                write!(f, "fn({}) -> {returns}", parameters.join(", "))
+            }
+        }
+    }
+}
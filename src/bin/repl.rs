@@ -2,11 +2,41 @@ use std::io::{self, Write};
 
 use monkey::{
     ast::{Expression, Node},
+    check::Checker,
     eval::Eval,
 };
 
 fn main() {
     let mut runtime = Eval::new();
+    let mut checker = Checker::new();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    // `--lint <file>` reports likely type errors and the inferred types
+    // without running the program.
+    if let [flag, path] = &args[..] {
+        if flag == "--lint" {
+            match runtime.lint_file(path) {
+                Ok(inference) => {
+                    for warning in inference.warnings {
+                        println!("warning: {warning}");
+                    }
+                    for (path, ty) in inference.types {
+                        println!("{path}: {ty}");
+                    }
+                }
+                Err(e) => println!("{:?}", e),
+            }
+            return;
+        }
+    }
+
+    if let Some(path) = args.first() {
+        if let Err(e) = runtime.run_file(path) {
+            println!("{:?}", e);
+        }
+        return;
+    }
 
     loop {
         print!(">> ");
@@ -31,7 +61,25 @@ fn main() {
             }
         };
 
-        match runtime.eval(Node::Expression(Expression::Program(pro))) {
+        let pro = match runtime.expand(pro) {
+            Ok(pro) => pro,
+            Err(e) => {
+                println!("{:?}", e);
+                return;
+            }
+        };
+
+        let errors = checker.check_program(&pro);
+        if !errors.is_empty() {
+            println!("{:?}", errors);
+            continue;
+        }
+
+        // Threads spawned by the line run to completion before the next one.
+        match runtime
+            .eval(Node::Expression(Expression::Program(pro)))
+            .and_then(|_| runtime.run_threads())
+        {
             Ok(_) => {}
             Err(e) => {
                 println!("{:?}", e);
@@ -1,8 +1,14 @@
 use std::collections::HashMap;
 
 use crate::{
-    eval::{error::Error, ops::Flow},
-    object::{Builtin, Integer, ObjectType, Reference, Str, Unit},
+    eval::{
+        error::{self, Error},
+        ops::Flow,
+    },
+    object::{
+        Bool, Builtin, Channel, Collection, Integer, Iter, ObjectType, Reference, Str, Unit,
+        Variant, Vector,
+    },
 };
 
 /*
@@ -18,22 +24,18 @@ pub fn builtins() -> HashMap<String, Reference> {
     [
         (
             "len".to_string(),
-            Builtin::erased(|args| {
+            Builtin::erased(|eval, args| {
                 if args.len() != 1 {
                     return Err(crate::eval::error::Error::Eval(
                         "Incorrect number of arguments used for len()".into(),
                     ));
                 }
 
-                let int = args[0]
-                    .v_table()
-                    .get("len")
-                    .ok_or(crate::eval::error::Error::Eval(
+                let int = eval.call_slot(&args[0], "len", None)?.ok_or(
+                    crate::eval::error::Error::Eval(
                         "Object does not implement len operation.".into(),
-                    ))?(None)
-                .ok_or(crate::eval::error::Error::Eval(
-                    "Object does not implement len operation.".into(),
-                ))?;
+                    ),
+                )?;
 
                 if !matches!(int.r#type(), ObjectType::Integer) {
                     return Err(crate::eval::error::Error::Eval(
@@ -41,42 +43,372 @@ pub fn builtins() -> HashMap<String, Reference> {
                     ));
                 };
 
-                let len = unsafe { int.get_mut::<Integer>().val };
+                let len = unsafe { int.get::<Integer>().val };
 
                 return Ok(Flow::Continue(Integer::erased(len)));
             }),
         ),
         (
             "print".to_string(),
-            Builtin::erased(|args| {
+            Builtin::erased(|eval, args| {
                 if args.len() != 1 {
                     return Err(crate::eval::error::Error::Eval(
                         "Incorrect number of arguments used for print()".into(),
                     ));
                 }
 
-                let f = args[0].v_table().get("str").ok_or(Error::Eval(
+                let str = eval.call_slot(&args[0], "str", None)?.ok_or(Error::Eval(
                     "Object passed to print does not have a string represenetation.".into(),
                 ))?;
 
-                let str = f(None).unwrap_or(Str::erased("".into()));
-
                 if !matches!(str.r#type(), ObjectType::Str) {
                     return Err(crate::eval::error::Error::Eval(
                         "Object did not return valid string representation.".into(),
                     ));
                 }
 
-                let str = unsafe { str.get_mut::<Str>() };
+                let str = unsafe { str.get::<Str>() };
 
                 println!("{}", str);
 
                 return Ok(Flow::Continue(Unit::erased()));
             }),
         ),
+        (
+            "proto".to_string(),
+            Builtin::erased(|_, args| match args.as_slice() {
+                [collection] => {
+                    if !matches!(collection.r#type(), ObjectType::Collection) {
+                        return Err(Error::Eval("Only collections have a prototype.".into()));
+                    }
+
+                    let proto = unsafe { collection.get::<Collection>().proto() };
+
+                    Ok(Flow::Continue(proto.unwrap_or(Unit::erased())))
+                }
+                [collection, proto] => {
+                    Collection::set_proto(collection, proto.clone()).map_err(Error::Eval)?;
+
+                    Ok(Flow::Continue(collection.clone()))
+                }
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for proto()".into(),
+                )),
+            }),
+        ),
+        (
+            "freeze".to_string(),
+            Builtin::erased(|_, args| {
+                if args.len() != 1 {
+                    return Err(Error::Eval(
+                        "Incorrect number of arguments used for freeze()".into(),
+                    ));
+                }
+
+                args[0].freeze();
+
+                Ok(Flow::Continue(args[0].clone()))
+            }),
+        ),
+        (
+            "error".to_string(),
+            Builtin::erased(|_, args| match args.as_slice() {
+                [kind, message]
+                    if matches!(kind.r#type(), ObjectType::Str)
+                        && matches!(message.r#type(), ObjectType::Str) =>
+                {
+                    Ok(Flow::Continue(error::value(
+                        &kind.to_string(),
+                        &message.to_string(),
+                    )))
+                }
+                [_, _] => Err(Error::Type(
+                    "error() expects a kind and a message string.".into(),
+                )),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for error()".into(),
+                )),
+            }),
+        ),
+        (
+            "ok".to_string(),
+            Builtin::erased(|_, args| match args.as_slice() {
+                [value] => Ok(Flow::Continue(Variant::ok(value.clone()))),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for ok()".into(),
+                )),
+            }),
+        ),
+        (
+            "err".to_string(),
+            Builtin::erased(|_, args| match args.as_slice() {
+                [error] => Ok(Flow::Continue(Variant::err(error.clone()))),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for err()".into(),
+                )),
+            }),
+        ),
+        (
+            "is_ok".to_string(),
+            Builtin::erased(|_, args| match args.as_slice() {
+                [result] => match Variant::as_result(result) {
+                    Some(result) => Ok(Flow::Continue(Bool::erased(result.is_ok()))),
+                    None => Err(Error::Type("is_ok() expects a result.".into())),
+                },
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for is_ok()".into(),
+                )),
+            }),
+        ),
+        (
+            "unwrap_or".to_string(),
+            Builtin::erased(|_, args| match args.as_slice() {
+                [result, default] => match Variant::as_result(result) {
+                    Some(Ok(value)) => Ok(Flow::Continue(value)),
+                    Some(Err(_)) => Ok(Flow::Continue(default.clone())),
+                    None => Err(Error::Type("unwrap_or() expects a result.".into())),
+                },
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for unwrap_or()".into(),
+                )),
+            }),
+        ),
+        (
+            "map_err".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [result, f] => match Variant::as_result(result) {
+                    Some(Ok(_)) => Ok(Flow::Continue(result.clone())),
+                    Some(Err(error)) => Ok(Flow::Continue(Variant::err(
+                        eval.apply(f.clone(), vec![error], None)?.unwrap(),
+                    ))),
+                    None => Err(Error::Type("map_err() expects a result.".into())),
+                },
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for map_err()".into(),
+                )),
+            }),
+        ),
+        ("done".to_string(), Variant::done()),
+        (
+            "iter".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [iterable] => Ok(Flow::Continue(eval.iter(iterable)?)),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for iter()".into(),
+                )),
+            }),
+        ),
+        (
+            "next".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [iterator] => Ok(Flow::Continue(
+                    eval.next(iterator)?.unwrap_or(Variant::done()),
+                )),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for next()".into(),
+                )),
+            }),
+        ),
+        (
+            "map".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [iterable, f] => {
+                    let source = eval.iter(iterable)?;
+                    let f = f.clone();
+
+                    Ok(Flow::Continue(Iter::erased(move |eval| {
+                        match eval.next(&source)? {
+                            Some(value) => {
+                                Ok(Some(eval.apply(f.clone(), vec![value], None)?.unwrap()))
+                            }
+                            None => Ok(None),
+                        }
+                    })))
+                }
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for map()".into(),
+                )),
+            }),
+        ),
+        (
+            "filter".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [iterable, f] => {
+                    let source = eval.iter(iterable)?;
+                    let f = f.clone();
+
+                    Ok(Flow::Continue(Iter::erased(move |eval| {
+                        while let Some(value) = eval.next(&source)? {
+                            let keep = eval.apply(f.clone(), vec![value.clone()], None)?.unwrap();
+
+                            if eval.call_slot(&keep, "truthy", None)?.is_some() {
+                                return Ok(Some(value));
+                            }
+                        }
+
+                        Ok(None)
+                    })))
+                }
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for filter()".into(),
+                )),
+            }),
+        ),
+        (
+            "take".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [iterable, count] if matches!(count.r#type(), ObjectType::Integer) => {
+                    let source = eval.iter(iterable)?;
+                    let mut remaining = unsafe { count.get::<Integer>().val };
+
+                    Ok(Flow::Continue(Iter::erased(move |eval| {
+                        if remaining <= 0 {
+                            return Ok(None);
+                        }
+
+                        remaining -= 1;
+                        eval.next(&source)
+                    })))
+                }
+                [_, _] => Err(Error::Type("take() expects an integer count.".into())),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for take()".into(),
+                )),
+            }),
+        ),
+        (
+            "zip".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [lhs, rhs] => {
+                    let lhs = eval.iter(lhs)?;
+                    let rhs = eval.iter(rhs)?;
+
+                    Ok(Flow::Continue(Iter::erased(move |eval| {
+                        let Some(lhs) = eval.next(&lhs)? else {
+                            return Ok(None);
+                        };
+
+                        Ok(eval.next(&rhs)?.map(|rhs| Vector::erased(vec![lhs, rhs])))
+                    })))
+                }
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for zip()".into(),
+                )),
+            }),
+        ),
+        (
+            "enumerate".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [iterable] => {
+                    let source = eval.iter(iterable)?;
+                    let mut index = 0;
+
+                    Ok(Flow::Continue(Iter::erased(move |eval| {
+                        let Some(value) = eval.next(&source)? else {
+                            return Ok(None);
+                        };
+
+                        index += 1;
+                        Ok(Some(Vector::erased(vec![
+                            Integer::erased(index - 1),
+                            value,
+                        ])))
+                    })))
+                }
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for enumerate()".into(),
+                )),
+            }),
+        ),
+        (
+            "par_map".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [f, iterable] => {
+                    // The workers read the function and values alongside each
+                    // other, so they have to be immutable.
+                    let share = |value: &Reference| {
+                        value.share().ok_or_else(|| {
+                            Error::Type(format!(
+                                "par_map() can only share immutable values between threads, but {value} is or refers to a mutable one. Use freeze() first."
+                            ))
+                        })
+                    };
+
+                    let function = share(f)?;
+                    let source = eval.iter(iterable)?;
+                    let mut values = vec![];
+                    while let Some(value) = eval.next(&source)? {
+                        values.push(share(&value)?);
+                    }
+
+                    Ok(Flow::Continue(eval.par_map(function, values)?))
+                }
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for par_map()".into(),
+                )),
+            }),
+        ),
+        (
+            "channel".to_string(),
+            Builtin::erased(|_, args| match args.as_slice() {
+                [] => Ok(Flow::Continue(Channel::erased(0))),
+                [capacity] if matches!(capacity.r#type(), ObjectType::Integer) => {
+                    let capacity = unsafe { capacity.get::<Integer>().val };
+
+                    match usize::try_from(capacity) {
+                        Ok(capacity) => Ok(Flow::Continue(Channel::erased(capacity))),
+                        Err(_) => Err(Error::Eval(
+                            "channel() expects a capacity of at least 0.".into(),
+                        )),
+                    }
+                }
+                [_] => Err(Error::Type("channel() expects an integer capacity.".into())),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for channel()".into(),
+                )),
+            }),
+        ),
+        (
+            "close".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [channel] => {
+                    eval.close(channel)?;
+                    Ok(Flow::Continue(Unit::erased()))
+                }
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for close()".into(),
+                )),
+            }),
+        ),
+        (
+            "sleep".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [ms] if matches!(ms.r#type(), ObjectType::Integer) => {
+                    let ms = unsafe { ms.get::<Integer>().val };
+                    Ok(Flow::Continue(eval.sleep(ms.max(0) as u64)))
+                }
+                [_] => Err(Error::Type("sleep() expects an integer duration.".into())),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for sleep()".into(),
+                )),
+            }),
+        ),
+        (
+            "timeout".to_string(),
+            Builtin::erased(|eval, args| match args.as_slice() {
+                [future, ms] if matches!(ms.r#type(), ObjectType::Integer) => {
+                    let ms = unsafe { ms.get::<Integer>().val };
+                    Ok(Flow::Continue(eval.timeout(future, ms.max(0) as u64)?))
+                }
+                [_, _] => Err(Error::Type("timeout() expects an integer duration.".into())),
+                _ => Err(Error::Eval(
+                    "Incorrect number of arguments used for timeout()".into(),
+                )),
+            }),
+        ),
         (
             "yeet".to_string(),
-            Builtin::erased(|args| {
+            Builtin::erased(|_, args| {
                 if args.len() != 0 {
                     return Err(crate::eval::error::Error::Eval(
                         "Incorrect number of arguments used for len()".into(),
@@ -88,7 +420,7 @@ pub fn builtins() -> HashMap<String, Reference> {
         ),
         (
             "exit".to_string(),
-            Builtin::erased(|args| {
+            Builtin::erased(|_, args| {
                 if args.len() != 0 {
                     return Err(crate::eval::error::Error::Eval(
                         "Incorrect number of arguments used for len()".into(),
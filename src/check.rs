@@ -0,0 +1,733 @@
+//! A pass over a program's annotations, reporting values that can't have the
+//! type they are annotated with before anything is evaluated.
+//!
+//! Bindings without annotation take the type of their value. Whatever the
+//! checker can't tell the type of is `any`, which is compatible with every
+//! type and left to be checked once the value reaches an annotation at
+//! runtime.
+
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        Catch, Clause, Expression, FunctionClause, Ident, ImportStatement, Imported, LetStatement,
+        Literal, Member, Pattern, Program, ReturnStatement, Statement, ThrowStatement, Type,
+    },
+    error::Error,
+    token::{Operator, Token},
+};
+
+/// Whether a value of type `actual` may be used where `expected` is.
+fn consistent(actual: &Type, expected: &Type) -> bool {
+    match (actual, expected) {
+        (Type::Any, _) | (_, Type::Any) => true,
+        (Type::Vector(actual), Type::Vector(expected)) => consistent(actual, expected),
+        (Type::Record(actual), Type::Record(expected)) => {
+            expected.iter().all(|(name, expected)| {
+                actual
+                    .iter()
+                    .find(|(member, _)| member == name)
+                    .is_some_and(|(_, actual)| consistent(actual, expected))
+            })
+        }
+        (
+            Type::Function {
+                parameters: actual_parameters,
+                returns: actual_returns,
+            },
+            Type::Function {
+                parameters: expected_parameters,
+                returns: expected_returns,
+            },
+        ) => {
+            // Functions may have more parameters with defaults, so only the
+            // ones both have are compared.
+            expected_parameters
+                .iter()
+                .zip(actual_parameters)
+                .all(|(expected, actual)| consistent(expected, actual))
+                && consistent(actual_returns, expected_returns)
+        }
+        (actual, expected) => actual == expected,
+    }
+}
+
+/// The type of both `a` and `b`, `any` if they differ.
+fn join(a: Type, b: Type) -> Type {
+    if a == b {
+        a
+    } else {
+        Type::Any
+    }
+}
+
+pub struct Checker {
+    /// The types of the names bound in each enclosing scope and whether they
+    /// are annotated, in which case every value assigned has to match.
+    scopes: Vec<HashMap<String, (Type, bool)>>,
+    /// The type annotated for the results of the function being checked.
+    returns: Option<Type>,
+    errors: Vec<Error>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            returns: None,
+            errors: vec![],
+        }
+    }
+
+    /// Checks `program`, keeping its top-level bindings for the programs
+    /// checked after it.
+    pub fn check_program(&mut self, program: &Program) -> Vec<Error> {
+        self.check_statements(&program.statements);
+        self.errors.drain(..).collect()
+    }
+
+    fn mismatch(&mut self, message: String) {
+        self.errors.push(Error::Type(message));
+    }
+
+    fn bind(&mut self, name: &Ident, r#type: Type, annotated: bool) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.name.clone(), (r#type, annotated));
+    }
+
+    /// The type of the value bound to `name`.
+    ///
+    /// Vectors and collections bound without annotation may be changed through
+    /// other bindings of them, so only their kind is known.
+    fn lookup(&self, name: &Ident) -> Type {
+        let Some((r#type, annotated)) = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.name))
+        else {
+            return Type::Any;
+        };
+
+        match r#type {
+            _ if *annotated => r#type.clone(),
+            Type::Vector(_) => Type::Vector(Box::new(Type::Any)),
+            Type::Record(members) => Type::Record(
+                members
+                    .iter()
+                    .map(|(member, _)| (member.clone(), Type::Any))
+                    .collect(),
+            ),
+            r#type => r#type.clone(),
+        }
+    }
+
+    /// Checks `statements` in a scope of their own.
+    fn check_scoped(&mut self, statements: &[Statement]) -> Type {
+        self.scopes.push(HashMap::new());
+        let r#type = self.check_statements(statements);
+        self.scopes.pop();
+        r#type
+    }
+
+    /// Checks `statements`, giving the type of the last one.
+    fn check_statements(&mut self, statements: &[Statement]) -> Type {
+        statements
+            .iter()
+            .fold(Type::Unit, |_, statement| self.check_statement(statement))
+    }
+
+    fn check_statement(&mut self, statement: &Statement) -> Type {
+        match statement {
+            Statement::Let(LetStatement {
+                name,
+                annotation,
+                value,
+            })
+            | Statement::Const(LetStatement {
+                name,
+                annotation,
+                value,
+            }) => {
+                let r#type = self.infer(value);
+
+                match annotation {
+                    Some(annotation) => {
+                        if !consistent(&r#type, annotation) {
+                            self.mismatch(format!(
+                                "`{}` is declared as `{annotation}` but bound to `{type}`.",
+                                name.name
+                            ));
+                        }
+                        self.bind(name, annotation.clone(), true);
+                    }
+                    None => self.bind(name, r#type.clone(), false),
+                }
+
+                r#type
+            }
+            Statement::Return(ReturnStatement { value }) => {
+                let r#type = self.infer(value);
+                self.check_returned(&r#type);
+                Type::Any
+            }
+            Statement::Throw(ThrowStatement { value }) => {
+                self.infer(value);
+                Type::Any
+            }
+            Statement::Enum(statement) => {
+                self.bind(&statement.name, Type::Any, false);
+                Type::Any
+            }
+            Statement::Import(ImportStatement { imported, .. }) => {
+                match imported {
+                    Imported::Module(alias) => self.bind(alias, Type::Any, false),
+                    Imported::Names(names) => {
+                        for name in names {
+                            self.bind(name, Type::Any, false);
+                        }
+                    }
+                }
+                Type::Unit
+            }
+            Statement::Export(statement) => self.check_statement(statement),
+            Statement::Expression(expression) => self.infer(expression),
+            Statement::Empty => Type::Unit,
+        }
+    }
+
+    fn check_returned(&mut self, r#type: &Type) {
+        if let Some(returns) = &self.returns {
+            if !consistent(r#type, returns) {
+                let message = format!("Function should return `{returns}` but returns `{type}`.");
+                self.mismatch(message);
+            }
+        }
+    }
+
+    /// Checks `expression`, giving its type.
+    fn infer(&mut self, expression: &Expression) -> Type {
+        match expression {
+            Expression::Program(Program { statements, .. }) => self.check_statements(statements),
+            Expression::Block { statements } => self.check_scoped(statements),
+            Expression::Ident(ident) => self.lookup(ident),
+            Expression::Literal(literal) => self.infer_literal(literal),
+            Expression::Infix { operator, lhs, rhs } => self.infer_infix(operator, lhs, rhs),
+            Expression::Prefix { operator, operand } => {
+                let operand = self.infer(operand);
+                match (operator, operand) {
+                    (Token::Operator(Operator::Bang), _) => Type::Bool,
+                    (Token::Operator(Operator::Minus), Type::Int) => Type::Int,
+                    _ => Type::Any,
+                }
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.infer(condition);
+                let consequence = self.infer(consequence);
+
+                match alternative {
+                    Some(alternative) => {
+                        let alternative = self.infer(alternative);
+                        join(consequence, alternative)
+                    }
+                    None => Type::Any,
+                }
+            }
+            Expression::Invoked { invoked, args } => self.infer_invoke(invoked, args),
+            Expression::Indexed { indexee, index } => {
+                let indexee = self.infer(indexee);
+                let index = self.infer(index);
+
+                match (indexee, index) {
+                    (Type::Vector(element), Type::Int | Type::Any) => *element,
+                    (Type::Str, Type::Int | Type::Any) => Type::Str,
+                    _ => Type::Any,
+                }
+            }
+            Expression::Sliced {
+                sliced,
+                start,
+                end,
+                step,
+            } => {
+                for bound in [start, end, step].into_iter().flatten() {
+                    self.infer(bound);
+                }
+
+                match self.infer(sliced) {
+                    r#type @ (Type::Vector(_) | Type::Str) => r#type,
+                    _ => Type::Any,
+                }
+            }
+            Expression::Match { subject, arms } => {
+                self.infer(subject);
+
+                arms.iter()
+                    .map(|arm| {
+                        self.scopes.push(HashMap::new());
+                        self.bind_pattern(&arm.pattern);
+                        let r#type = self.infer(&arm.body);
+                        self.scopes.pop();
+                        r#type
+                    })
+                    .reduce(join)
+                    .unwrap_or(Type::Any)
+            }
+            Expression::Comprehension {
+                key,
+                element,
+                clauses,
+            } => {
+                self.scopes.push(HashMap::new());
+
+                for clause in clauses {
+                    match clause {
+                        Clause::For { binding, iterable } => {
+                            let element = match self.infer(iterable) {
+                                Type::Vector(element) => *element,
+                                _ => Type::Any,
+                            };
+                            self.bind(binding, element, false);
+                        }
+                        Clause::If(condition) => {
+                            self.infer(condition);
+                        }
+                    }
+                }
+
+                if let Some(key) = key {
+                    self.infer(key);
+                }
+                let element = self.infer(element);
+
+                self.scopes.pop();
+
+                match key {
+                    Some(_) => Type::Any,
+                    None => Type::Vector(Box::new(element)),
+                }
+            }
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let mut r#type = self.infer(body);
+
+                if let Some(Catch { binding, body }) = catch {
+                    self.scopes.push(HashMap::new());
+                    if let Some(binding) = binding {
+                        self.bind(binding, Type::Any, false);
+                    }
+                    r#type = join(r#type, self.infer(body));
+                    self.scopes.pop();
+                }
+
+                if let Some(finally) = finally {
+                    self.infer(finally);
+                }
+
+                r#type
+            }
+            Expression::Propagate(value) | Expression::Yield(value) | Expression::Spread(value) => {
+                self.infer(value);
+                Type::Any
+            }
+            Expression::Spawn(function) => {
+                self.infer(function);
+                Type::Unit
+            }
+            Expression::Await(future) => {
+                self.infer(future);
+                Type::Any
+            }
+            Expression::Named { value, .. } => self.infer(value),
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Vector { elements, rest } => {
+                for element in elements {
+                    self.bind_pattern(element);
+                }
+                if let Some(rest) = rest {
+                    self.bind(rest, Type::Vector(Box::new(Type::Any)), false);
+                }
+            }
+            pattern => {
+                for name in pattern.bindings() {
+                    self.bind(name, Type::Any, false);
+                }
+            }
+        }
+    }
+
+    fn infer_literal(&mut self, literal: &Literal) -> Type {
+        match literal {
+            Literal::Int(_) => Type::Int,
+            Literal::String(_) => Type::Str,
+            Literal::Bool(_) => Type::Bool,
+            Literal::Vector { elements } => {
+                let element = elements
+                    .iter()
+                    .map(|element| match element {
+                        Expression::Spread(_) => {
+                            self.infer(element);
+                            Type::Any
+                        }
+                        _ => self.infer(element),
+                    })
+                    .reduce(join)
+                    .unwrap_or(Type::Any);
+
+                Type::Vector(Box::new(element))
+            }
+            Literal::Collection { members, parent } => {
+                let mut named = vec![];
+                let mut spread = false;
+                for member in members {
+                    match member {
+                        Member::Named(name, value) => named.push((name.clone(), self.infer(value))),
+                        Member::Spread(value) => {
+                            self.infer(value);
+                            spread = true;
+                        }
+                    }
+                }
+                named.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+                if let Some(parent) = parent {
+                    self.infer(parent);
+                }
+
+                // Spread and parent collections add members of their own.
+                if !spread && parent.is_none() {
+                    Type::Record(named)
+                } else {
+                    Type::Any
+                }
+            }
+            Literal::Function {
+                name,
+                clauses,
+                capture,
+            } => {
+                let types = clauses
+                    .iter()
+                    .map(|clause| self.check_clause(name.as_ref(), clause, capture))
+                    .collect::<Vec<_>>();
+
+                // Functions with several clauses accept what any of them does.
+                match &types[..] {
+                    [r#type] => r#type.clone(),
+                    _ => Type::Any,
+                }
+            }
+            Literal::Macro { .. } => Type::Any,
+        }
+    }
+
+    /// Checks a clause of a function, giving the function's type if it only
+    /// has this clause.
+    fn check_clause(
+        &mut self,
+        name: Option<&Ident>,
+        clause: &FunctionClause,
+        capture: &[Ident],
+    ) -> Type {
+        let FunctionClause {
+            parameters,
+            rest,
+            returns,
+            guard,
+            generator,
+            r#async,
+            body,
+        } = clause;
+
+        // Function bodies only see their parameters, captures and name.
+        let mut scope: HashMap<String, (Type, bool)> = capture
+            .iter()
+            .map(|ident| (ident.name.clone(), (self.lookup(ident), false)))
+            .chain(name.map(|name| (name.name.clone(), (Type::Any, false))))
+            .collect();
+
+        for parameter in parameters {
+            let r#type = parameter.annotation.clone().unwrap_or(Type::Any);
+            match &parameter.pattern {
+                Pattern::Binding(ident) => {
+                    scope.insert(ident.name.clone(), (r#type, parameter.annotation.is_some()));
+                }
+                pattern => {
+                    for ident in pattern.bindings() {
+                        scope.insert(ident.name.clone(), (Type::Any, false));
+                    }
+                }
+            }
+        }
+        if let Some(rest) = rest {
+            scope.insert(
+                rest.name.clone(),
+                (Type::Vector(Box::new(Type::Any)), false),
+            );
+        }
+
+        // Generators return an iterator rather than their results.
+        let returns = returns.clone().filter(|_| !generator);
+
+        let scopes = std::mem::replace(&mut self.scopes, vec![scope]);
+        let outer = std::mem::replace(&mut self.returns, returns.clone());
+
+        for parameter in parameters {
+            let (Some(annotation), Some(default)) = (&parameter.annotation, &parameter.default)
+            else {
+                continue;
+            };
+
+            let r#type = self.infer(default);
+            if !consistent(&r#type, annotation) {
+                self.mismatch(format!(
+                    "Parameter `{}` is declared as `{annotation}` but defaults to `{type}`.",
+                    parameter.ident().map_or("_", |ident| &ident.name)
+                ));
+            }
+        }
+
+        if let Some(guard) = guard {
+            self.infer(guard);
+        }
+
+        let r#type = self.infer(body);
+        self.check_returned(&r#type);
+
+        self.scopes = scopes;
+        self.returns = outer;
+
+        // Async functions return a future of their results.
+        let returns = returns.filter(|_| !r#async);
+
+        Type::Function {
+            parameters: parameters
+                .iter()
+                .map(|parameter| parameter.annotation.clone().unwrap_or(Type::Any))
+                .collect(),
+            returns: Box::new(returns.unwrap_or(Type::Any)),
+        }
+    }
+
+    fn infer_infix(&mut self, operator: &Token, lhs: &Expression, rhs: &Expression) -> Type {
+        let Token::Operator(op) = operator else {
+            return Type::Any;
+        };
+
+        match op {
+            Operator::Assign => {
+                let r#type = self.infer(rhs);
+                self.assign(lhs, r#type.clone());
+                return r#type;
+            }
+            Operator::PlusEqual | Operator::MinusEqual => {
+                let lhs_type = self.infer(lhs);
+                let rhs_type = self.infer(rhs);
+                let r#type = self.infer_operation(operator, lhs_type, rhs_type);
+                self.assign(lhs, r#type.clone());
+                return r#type;
+            }
+            Operator::Dot => {
+                let collection = self.infer(lhs);
+
+                return match (collection, rhs) {
+                    (Type::Record(members), Expression::Ident(name)) => members
+                        .into_iter()
+                        .find(|(member, _)| member == name)
+                        .map_or(Type::Any, |(_, r#type)| r#type),
+                    _ => Type::Any,
+                };
+            }
+            Operator::LeftArrow => {
+                self.infer(lhs);
+                self.infer(rhs);
+                return Type::Unit;
+            }
+            _ => {}
+        }
+
+        let lhs = self.infer(lhs);
+        let rhs = self.infer(rhs);
+        self.infer_operation(operator, lhs, rhs)
+    }
+
+    /// The type of applying `operator` to operands of the given types, which
+    /// is only known for values that can't overload operators.
+    ///
+    /// Operands the operator doesn't support aren't reported, as the error
+    /// they raise may well be caught.
+    fn infer_operation(&self, operator: &Token, lhs: Type, rhs: Type) -> Type {
+        let Token::Operator(op) = operator else {
+            return Type::Any;
+        };
+
+        let r#type = match (op, &lhs, &rhs) {
+            (Operator::Equal | Operator::NotEqual, _, _) => return Type::Bool,
+            (_, Type::Any | Type::Record(_) | Type::Function { .. }, _) | (_, _, Type::Any) => {
+                return Type::Any
+            }
+            (
+                Operator::Plus
+                | Operator::PlusEqual
+                | Operator::Minus
+                | Operator::MinusEqual
+                | Operator::Multiply
+                | Operator::Divide,
+                Type::Int,
+                Type::Int,
+            ) => Some(Type::Int),
+            (Operator::Plus | Operator::PlusEqual, Type::Str, Type::Str) => Some(Type::Str),
+            (Operator::Plus | Operator::PlusEqual, Type::Vector(a), Type::Vector(b)) => {
+                Some(Type::Vector(Box::new(join(*a.clone(), *b.clone()))))
+            }
+            (
+                Operator::Less
+                | Operator::LessOrEqual
+                | Operator::Greater
+                | Operator::GreaterOrEqual,
+                Type::Int,
+                Type::Int,
+            )
+            | (
+                Operator::Less
+                | Operator::LessOrEqual
+                | Operator::Greater
+                | Operator::GreaterOrEqual,
+                Type::Str,
+                Type::Str,
+            )
+            | (
+                Operator::Less
+                | Operator::LessOrEqual
+                | Operator::Greater
+                | Operator::GreaterOrEqual,
+                Type::Vector(_),
+                Type::Vector(_),
+            ) => Some(Type::Bool),
+            _ => None,
+        };
+
+        r#type.unwrap_or(Type::Any)
+    }
+
+    /// Records that a value of type `r#type` is assigned to `target`.
+    fn assign(&mut self, target: &Expression, r#type: Type) {
+        let name = match target {
+            Expression::Ident(name) => name,
+            Expression::Infix {
+                operator: Token::Operator(Operator::Dot),
+                lhs,
+                ..
+            }
+            | Expression::Indexed { indexee: lhs, .. } => {
+                // Assigning to a member or element may change the shape of
+                // the collection, unless its type is declared.
+                self.infer(target);
+                if let Expression::Ident(name) = &**lhs {
+                    self.widen(name);
+                }
+                return;
+            }
+            _ => {
+                self.infer(target);
+                return;
+            }
+        };
+
+        let Some((declared, annotated)) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name.name))
+        else {
+            return;
+        };
+
+        if *annotated {
+            if !consistent(&r#type, declared) {
+                let message = format!(
+                    "`{}` is declared as `{declared}` but assigned `{type}`.",
+                    name.name
+                );
+                self.mismatch(message);
+            }
+        } else if *declared != r#type {
+            *declared = Type::Any;
+        }
+    }
+
+    /// Forgets the type of the unannotated binding `name`.
+    fn widen(&mut self, name: &Ident) {
+        if let Some((declared, false)) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name.name))
+        {
+            *declared = Type::Any;
+        }
+    }
+
+    fn infer_invoke(&mut self, invoked: &Expression, args: &[Expression]) -> Type {
+        let function = self.infer(invoked);
+
+        let mut positional = vec![];
+        for arg in args {
+            let r#type = self.infer(arg);
+            match arg {
+                Expression::Named { .. } => {}
+                // Spread arguments make the positions of the ones after them
+                // unknown.
+                Expression::Spread(_) => positional.push(None),
+                _ => positional.push(Some(r#type)),
+            }
+        }
+
+        let Type::Function {
+            parameters,
+            returns,
+        } = function
+        else {
+            return Type::Any;
+        };
+
+        let name = match invoked {
+            Expression::Ident(ident) => format!("`{}`", ident.name),
+            _ => "function".to_string(),
+        };
+
+        for (i, (parameter, arg)) in parameters.iter().zip(positional).enumerate() {
+            let Some(arg) = arg else {
+                break;
+            };
+
+            if !consistent(&arg, parameter) {
+                self.mismatch(format!(
+                    "Argument {} of {name} should be `{parameter}` but is `{arg}`.",
+                    i + 1
+                ));
+            }
+        }
+
+        *returns
+    }
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
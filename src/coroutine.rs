@@ -0,0 +1,176 @@
+//! Coroutines that run on their own thread and hand control back and forth
+//! with the thread resuming them, so that only one of both runs at a time.
+//!
+//! This lets a generator suspend in the middle of evaluating its body without
+//! the tree-walking `Eval` having to unwind and later rebuild its state.
+//!
+//! It comes at a cost, as every coroutine is an OS thread:
+//!
+//! - Each generator and green thread that was started and hasn't finished or
+//!   been dropped holds on to a thread and its native stack of
+//!   [`COROUTINE_STACK_SIZE`], plus one of [`stack::NATIVE_STACK_SIZE`] for each
+//!   time its evaluation had to continue on a new one. Stacks are mostly
+//!   reserved rather than used memory, but the number of threads is limited
+//!   by the system, and starting a coroutine fails once it is reached.
+//! - Each value a generator yields takes two handoffs between threads, which
+//!   is a few microseconds rather than the cost of a function call.
+//! - A generator that delegates to another one, e.g. by recursing, adds a
+//!   thread per level, and every value passes through all of them, so deep
+//!   recursive generators get slow quadratically.
+
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::JoinHandle,
+};
+
+use crate::{
+    eval::error::Error,
+    object::Reference,
+    stack::{self, COROUTINE_STACK_SIZE},
+};
+
+/// Moves a value to another thread.
+///
+/// References aren't `Send`, as objects are not synchronized. Handing them
+/// over is sound as long as the sending thread doesn't touch them until they
+/// are handed back or it finished, e.g. as it blocks until control is handed
+/// back, which is what `Coroutine` and `Yielder` ensure.
+#[derive(Debug)]
+pub(crate) struct Handoff<T>(pub T);
+
+unsafe impl<T> Send for Handoff<T> {}
+
+#[derive(Debug)]
+enum Step {
+    Yielded(Reference),
+    Returned,
+    Failed(Error),
+}
+
+type Body = Box<dyn FnOnce(Yielder) -> Result<(), Error>>;
+
+/// The resuming side of a coroutine.
+pub struct Coroutine {
+    body: Option<Handoff<Body>>,
+    resume: Option<Sender<()>>,
+    steps: Option<Receiver<Handoff<Step>>>,
+    thread: Option<JoinHandle<()>>,
+    done: bool,
+}
+
+/// The suspending side of a coroutine, passed to its body.
+#[derive(Debug)]
+pub struct Yielder {
+    resume: Receiver<()>,
+    steps: Sender<Handoff<Step>>,
+}
+
+impl Coroutine {
+    /// Creates a coroutine running `body` once it is first resumed.
+    ///
+    /// The body runs on another thread, so it may only use what isn't `Send`
+    /// while the coroutine is suspended on this one, see [`Handoff`].
+    pub(crate) fn new(body: impl FnOnce(Yielder) -> Result<(), Error> + 'static) -> Self {
+        Self {
+            body: Some(Handoff(Box::new(body))),
+            resume: None,
+            steps: None,
+            thread: None,
+            done: false,
+        }
+    }
+
+    /// Runs the coroutine until it yields the next value or finishes, in which
+    /// case `None` is returned.
+    pub fn resume(&mut self) -> Result<Option<Reference>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+
+        if let Some(body) = self.body.take() {
+            self.start(body)?;
+        }
+
+        let step = match (&self.resume, &self.steps) {
+            (Some(resume), Some(steps)) if resume.send(()).is_ok() => steps.recv().ok(),
+            _ => None,
+        };
+
+        match step {
+            Some(Handoff(Step::Yielded(value))) => Ok(Some(value)),
+            Some(Handoff(Step::Returned)) => {
+                self.done = true;
+                Ok(None)
+            }
+            Some(Handoff(Step::Failed(e))) => {
+                self.done = true;
+                Err(e)
+            }
+            None => {
+                self.done = true;
+                Err(Error::Eval("Coroutine stopped unexpectedly.".into()))
+            }
+        }
+    }
+
+    fn start(&mut self, body: Handoff<Body>) -> Result<(), Error> {
+        let (resume, resumed) = channel();
+        let (stepped, steps) = channel();
+
+        let thread = stack::spawn(COROUTINE_STACK_SIZE, move || {
+            let body = body;
+
+            // The coroutine is dropped without being resumed again.
+            if resumed.recv().is_err() {
+                return;
+            }
+
+            let yielder = Yielder {
+                resume: resumed,
+                steps: stepped.clone(),
+            };
+
+            let step = match (body.0)(yielder) {
+                Ok(()) => Step::Returned,
+                Err(e) => Step::Failed(e),
+            };
+
+            let _ = stepped.send(Handoff(step));
+        })
+        .map_err(|e| Error::Eval(format!("Could not start coroutine: {e}")))?;
+
+        self.resume = Some(resume);
+        self.steps = Some(steps);
+        self.thread = Some(thread);
+
+        Ok(())
+    }
+}
+
+impl Drop for Coroutine {
+    fn drop(&mut self) {
+        // Closing the channel makes a suspended body fail out of `suspend`.
+        // Waiting for it to unwind keeps it from running alongside us.
+        self.resume.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Yielder {
+    /// Hands `value` to the resuming thread and blocks until resumed again.
+    ///
+    /// Fails if the coroutine was dropped in the meantime, which should
+    /// unwind the body.
+    pub fn suspend(&self, value: Reference) -> Result<(), Error> {
+        self.steps
+            .send(Handoff(Step::Yielded(value)))
+            .ok()
+            .and_then(|_| self.resume.recv().ok())
+            .ok_or(Error::Abort(
+                "Generator was dropped while suspended.".into(),
+            ))
+    }
+}
@@ -10,4 +10,12 @@ pub enum Error {
     IfError(String),
     FunctionError(String),
     Collection(String),
+    Enum(String),
+    Match(String),
+    Const(String),
+    Comprehension(String),
+    Try(String),
+    Import(String),
+    /// A malformed type annotation, or a value not matching one.
+    Type(String),
 }
@@ -1,24 +1,380 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc,
+    },
+    thread,
+};
 
 use crate::{
-    ast::{self, Expression, Ident, LetStatement, Literal, Node, ReturnStatement, Statement},
+    ast::{
+        self, Catch, Clause, EnumStatement, Expression, FunctionClause, Ident, ImportStatement,
+        Imported, LetStatement, Literal, MatchArm, Member, Node, Pattern, Program, ReturnStatement,
+        Statement, ThrowStatement, Type,
+    },
+    check::Checker,
+    coroutine::{Coroutine, Handoff, Yielder},
+    infer::{Inference, Inferrer},
+    lexer::Lexer,
     object::{
-        self, Builtin, Collection, Function, Integer, ObjectType, Reference, Str, Unit, Vector,
+        self, Builtin, Channel, Collection, Function, Future, Integer, Iter, ObjectType, Quote,
+        Reference, Shareable, Slice, Str, Unit, Variant, Vector,
     },
-    stack::Stack,
+    parser::Parser,
+    scheduler::{Completer, Scheduler, Timer},
+    stack::{self, Frame, Stack, NATIVE_STACK_SIZE},
     token::{Operator, Token},
 };
 
 use error::{Error, Result};
 
+/// Whether `value` has type `r#type`, checking every element of vectors and
+/// every listed member of records.
+fn conforms(value: &Reference, r#type: &Type) -> bool {
+    match r#type {
+        Type::Any => true,
+        Type::Int => matches!(value.r#type(), ObjectType::Integer),
+        Type::Bool => matches!(value.r#type(), ObjectType::Bool),
+        Type::Str => matches!(value.r#type(), ObjectType::Str),
+        Type::Unit => matches!(value.r#type(), ObjectType::Unit),
+        Type::Vector(element) => {
+            matches!(value.r#type(), ObjectType::Vector)
+                && unsafe { value.get::<Vector>() }
+                    .elements
+                    .iter()
+                    .all(|value| conforms(value, element))
+        }
+        Type::Record(members) => {
+            matches!(value.r#type(), ObjectType::Collection)
+                && members.iter().all(|(name, r#type)| {
+                    Collection::lookup(value, name)
+                        .is_some_and(|(member, _)| conforms(&member, r#type))
+                })
+        }
+        // Parameters and results are checked by the function itself.
+        Type::Function { .. } => {
+            matches!(value.r#type(), ObjectType::Function | ObjectType::Builtin)
+        }
+    }
+}
+
+/// How errors refer to `function`.
+fn function_name(function: &Function) -> String {
+    function
+        .name
+        .as_ref()
+        .map_or("Anonymous function".to_string(), |name| {
+            format!("Function `{name}`")
+        })
+}
+
+/// How traces list calls of `function`.
+fn call_name(function: &Function) -> Arc<str> {
+    function.name.clone().unwrap_or(Arc::from("<anonymous>"))
+}
+
+/// The integer a slice `bound` evaluated to.
+fn slice_bound(bound: &Reference) -> std::result::Result<i32, Error> {
+    if !matches!(bound.r#type(), ObjectType::Integer) {
+        return Err(Error::Type(format!(
+            "Slice bounds must be integers, got {bound}."
+        )));
+    }
+
+    Ok(unsafe { bound.get::<Integer>().val })
+}
+
+/// Whether `expression` invokes the identifier `name`, e.g. `unquote(x)`.
+fn is_call(expression: &Expression, name: &str) -> bool {
+    matches!(
+        expression,
+        Expression::Invoked { invoked, .. }
+            if matches!(&**invoked, Expression::Ident(ident) if ident.name == name)
+    )
+}
+
+/// The names bound by `expression` itself, like the parameters of a function
+/// or the `let` statements of a block.
+fn binders_mut(expression: &mut Expression) -> Vec<&mut Ident> {
+    fn pattern_binders_mut(pattern: &mut Pattern) -> Vec<&mut Ident> {
+        match pattern {
+            Pattern::Binding(ident) => vec![ident],
+            Pattern::Variant { fields, .. } => {
+                fields.iter_mut().flat_map(pattern_binders_mut).collect()
+            }
+            Pattern::Vector { elements, rest } => elements
+                .iter_mut()
+                .flat_map(pattern_binders_mut)
+                .chain(rest.as_mut())
+                .collect(),
+            Pattern::Wildcard | Pattern::Literal(_) => vec![],
+        }
+    }
+
+    match expression {
+        Expression::Program(Program { statements, .. }) | Expression::Block { statements } => {
+            statements
+                .iter_mut()
+                .filter_map(|statement| match statement {
+                    Statement::Let(LetStatement { name, .. })
+                    | Statement::Const(LetStatement { name, .. }) => Some(name),
+                    _ => None,
+                })
+                .collect()
+        }
+        Expression::Literal(Literal::Function { name, clauses, .. }) => name
+            .as_mut()
+            .into_iter()
+            .chain(clauses.iter_mut().flat_map(|clause| {
+                clause
+                    .parameters
+                    .iter_mut()
+                    .flat_map(|parameter| pattern_binders_mut(&mut parameter.pattern))
+                    .chain(clause.rest.as_mut())
+            }))
+            .collect(),
+        Expression::Match { arms, .. } => arms
+            .iter_mut()
+            .flat_map(|arm| pattern_binders_mut(&mut arm.pattern))
+            .collect(),
+        Expression::Comprehension { clauses, .. } => clauses
+            .iter_mut()
+            .filter_map(|clause| match clause {
+                Clause::For { binding, .. } => Some(binding),
+                Clause::If(_) => None,
+            })
+            .collect(),
+        Expression::Try {
+            catch:
+                Some(Catch {
+                    binding: Some(binding),
+                    ..
+                }),
+            ..
+        } => vec![binding],
+        _ => vec![],
+    }
+}
+
+/// Renames the names bound in a quoted expression to fresh ones, recording
+/// them in `renamed`, but leaves unquoted expressions alone.
+fn rename_bindings(expression: &mut Expression, renamed: &mut HashMap<String, String>) {
+    static FRESH: AtomicUsize = AtomicUsize::new(0);
+
+    if is_call(expression, "unquote") {
+        return;
+    }
+
+    for binder in binders_mut(expression) {
+        // `#` can't appear in identifiers, so fresh names can't be captured.
+        let fresh = renamed.entry(binder.name.clone()).or_insert_with(|| {
+            format!(
+                "{}#{}",
+                binder.name,
+                FRESH.fetch_add(1, atomic::Ordering::Relaxed)
+            )
+        });
+        binder.name = fresh.clone();
+    }
+
+    for child in expression.children_mut() {
+        rename_bindings(child, renamed);
+    }
+}
+
+/// Numbers the names made up by `rename_bindings` in the order they are bound
+/// in, recording them in `numbered`, which makes quotes that only differ in
+/// the made up names equal.
+fn number_bindings(expression: &mut Expression, numbered: &mut HashMap<String, String>) {
+    for binder in binders_mut(expression) {
+        let Some((name, _)) = binder.name.split_once('#') else {
+            continue;
+        };
+
+        let len = numbered.len();
+        let fresh = numbered
+            .entry(binder.name.clone())
+            .or_insert_with(|| format!("{name}#{len}"));
+        binder.name = fresh.clone();
+    }
+
+    for child in expression.children_mut() {
+        number_bindings(child, numbered);
+    }
+}
+
+/// `expression` with its made up names numbered, see `number_bindings`.
+pub(crate) fn numbered(expression: &Expression) -> Expression {
+    let mut expression = expression.clone();
+    let mut numbered = HashMap::new();
+    number_bindings(&mut expression, &mut numbered);
+    rename_uses(&mut expression, &numbered);
+
+    expression
+}
+
+/// Renames the uses of names renamed by `rename_bindings`.
+fn rename_uses(expression: &mut Expression, renamed: &HashMap<String, String>) {
+    match expression {
+        _ if is_call(expression, "unquote") => {}
+        Expression::Ident(ident) => {
+            if let Some(name) = renamed.get(&ident.name) {
+                ident.name = name.clone();
+            }
+        }
+        // Member names aren't bindings.
+        Expression::Infix {
+            operator: Token::Operator(Operator::Dot),
+            lhs,
+            ..
+        } => rename_uses(lhs, renamed),
+        expression => {
+            if let Expression::Literal(Literal::Function { capture, .. }) = expression {
+                for ident in capture {
+                    if let Some(name) = renamed.get(&ident.name) {
+                        ident.name = name.clone();
+                    }
+                }
+            }
+
+            for child in expression.children_mut() {
+                rename_uses(child, renamed);
+            }
+        }
+    }
+}
+
+/// Marks the names a quoted expression uses without binding them, which
+/// `rename_bindings` left alone, as `#name`.
+///
+/// These refer to the binding of `name` where the macro is defined instead
+/// of to a binding of the code it is expanded into, see [`Stack::get`].
+/// Assigned names, `self` and `super` are left alone.
+fn mark_free(expression: &mut Expression) {
+    fn mark(ident: &mut Ident) {
+        if !ident.name.contains('#') && !matches!(ident.name.as_str(), "self" | "super") {
+            ident.name.insert(0, '#');
+        }
+    }
+
+    match expression {
+        _ if is_call(expression, "unquote") => {}
+        Expression::Ident(ident) => mark(ident),
+        // Nested quotes are still quotes.
+        Expression::Invoked { invoked, args } if matches!(&**invoked, Expression::Ident(ident) if ident.name == "quote") => {
+            args.iter_mut().for_each(mark_free)
+        }
+        Expression::Infix {
+            operator: Token::Operator(Operator::Dot),
+            lhs,
+            ..
+        } => mark_free(lhs),
+        Expression::Infix {
+            operator: Token::Operator(Operator::Assign | Operator::PlusEqual | Operator::MinusEqual),
+            lhs,
+            rhs,
+        } if matches!(**lhs, Expression::Ident(_)) => mark_free(rhs),
+        expression => {
+            if let Expression::Literal(Literal::Function { capture, .. }) = expression {
+                capture.iter_mut().for_each(mark);
+            }
+
+            for child in expression.children_mut() {
+                mark_free(child);
+            }
+        }
+    }
+}
+
 pub mod error {
+    use std::collections::HashMap;
+
     use super::ops::Flow;
+    use crate::{
+        ast::Ident,
+        object::{Collection, ObjectType, Reference, Str, Vector},
+    };
 
     pub type Result<T> = std::result::Result<Flow<T>, Error>;
 
     #[derive(Debug, Clone)]
     pub enum Error {
         Eval(String),
+        /// An operation applied to values of the wrong type.
+        Type(String),
+        /// An index out of range.
+        Index(String),
+        /// A name that isn't bound.
+        Name(String),
+        /// A call nested deeper than the evaluator's maximum depth.
+        Recursion(String),
+        /// Every green thread blocked on a channel or future.
+        Deadlock(String),
+        /// A future that wasn't settled in time, see `timeout`.
+        Timeout(String),
+        /// An error value raised with `throw`, see [`value`].
+        Thrown(Reference),
+        /// Unwinds the evaluation without being catchable, e.g. when a
+        /// suspended generator is dropped.
+        Abort(String),
+    }
+
+    impl Error {
+        /// Converts the error into the value bound by `catch`, or gives it
+        /// back if it can't be caught.
+        pub fn into_value(self) -> std::result::Result<Reference, Error> {
+            let (kind, message) = match self {
+                Error::Thrown(value) => return Ok(value),
+                Error::Abort(_) => return Err(self),
+                Error::Eval(message) => ("Error", message),
+                Error::Type(message) => ("TypeError", message),
+                Error::Index(message) => ("IndexError", message),
+                Error::Name(message) => ("NameError", message),
+                Error::Recursion(message) => ("RecursionError", message),
+                Error::Deadlock(message) => ("DeadlockError", message),
+                Error::Timeout(message) => ("TimeoutError", message),
+            };
+
+            Ok(value(kind, &message))
+        }
+    }
+
+    /// Creates an error value, a collection with a `kind`, a `message` and
+    /// the `trace` of the functions it was raised in, outermost first.
+    pub fn value(kind: &str, message: &str) -> Reference {
+        Collection::erased(HashMap::from([
+            (
+                Ident {
+                    name: "kind".into(),
+                },
+                Str::erased(kind.into()),
+            ),
+            (
+                Ident {
+                    name: "message".into(),
+                },
+                Str::erased(message.into()),
+            ),
+            (
+                Ident {
+                    name: "trace".into(),
+                },
+                Vector::erased(vec![]),
+            ),
+        ]))
+    }
+
+    /// Whether `obj` is an error value, i.e. a collection with a `kind` and a
+    /// `message`.
+    pub fn is_value(obj: &Reference) -> bool {
+        ["kind", "message"].into_iter().all(|name| {
+            let name = Ident { name: name.into() };
+            matches!(obj.r#type(), ObjectType::Collection)
+                && Collection::lookup(obj, &name).is_some()
+        })
     }
 }
 
@@ -93,25 +449,401 @@ pub mod ops {
 
 use ops::Flow;
 
+/// The special collection members that override vtable slots.
+const OVERLOADS: [(&str, &str); 19] = [
+    ("add_lhs", "__add__"),
+    ("sub_lhs", "__sub__"),
+    ("mul_lhs", "__mul__"),
+    ("div_lhs", "__div__"),
+    ("eq_lhs", "__eq__"),
+    ("neq_lhs", "__ne__"),
+    ("le_lhs", "__lt__"),
+    ("leq_lhs", "__le__"),
+    ("ge_lhs", "__gt__"),
+    ("geq_lhs", "__ge__"),
+    ("ins_lhs", "__and__"),
+    ("uni_lhs", "__or__"),
+    ("inv", "__not__"),
+    ("neg", "__neg__"),
+    ("idx", "__index__"),
+    ("len", "__len__"),
+    ("str", "__str__"),
+    ("truthy", "__truthy__"),
+    ("next", "__next__"),
+];
+
+/// A call in tail position, deferred to the `apply_named` of the function
+/// making it.
+#[derive(Debug)]
+struct TailCall {
+    function: Reference,
+    args: Vec<Reference>,
+    named: Vec<(Ident, Reference)>,
+    receiver: Option<Receiver>,
+    /// The return annotation of the function making the call, together with
+    /// that function, if it has one.
+    returns: Option<(Type, Reference)>,
+}
+
+/// The collection a method is invoked on.
+#[derive(Debug, Clone)]
+pub struct Receiver {
+    /// Bound to `self` inside the method.
+    pub object: Reference,
+    /// Bound to `super` inside the method, if the defining collection has a
+    /// prototype.
+    pub parent: Option<Reference>,
+}
+
+/// Modules loaded by `import`, handed from an evaluator to the evaluators of
+/// the modules it imports.
+#[derive(Debug, Default)]
+struct Modules {
+    /// The namespaces of evaluated modules by their canonical path.
+    loaded: HashMap<PathBuf, Reference>,
+    /// The modules being evaluated, importers first.
+    loading: Vec<PathBuf>,
+}
+
 #[derive(Debug)]
 pub struct Eval {
     stack: Stack,
+    /// The file being evaluated, which imports are resolved relative to.
+    path: Option<PathBuf>,
+    /// The names exported by the module being evaluated.
+    exports: Vec<String>,
+    /// Whether the node evaluated next is in tail position of the running
+    /// function.
+    tail: bool,
+    /// The number of `try` expressions the running function is in.
+    tries: usize,
+    tail_call: Option<TailCall>,
+    /// The parameters and bodies of the macros defined by expanded programs.
+    macros: HashMap<String, (Vec<Ident>, Expression)>,
+    modules: Modules,
+    /// Suspends the generator whose body this evaluator runs, if any.
+    yielder: Option<Yielder>,
+    /// The green threads of the program, shared with the evaluators running
+    /// them.
+    scheduler: Scheduler,
+    /// Suspends the green thread whose function this evaluator runs, if any,
+    /// while it is blocked.
+    thread: Option<Yielder>,
+    /// The names of the functions being called, outermost first.
+    calls: Vec<Arc<str>>,
+    /// The calls active where the error currently unwinding was raised,
+    /// recorded when it leaves the first function.
+    trace: Option<Vec<Arc<str>>>,
 }
 
 impl Eval {
     pub fn new() -> Self {
         Self {
             stack: Stack::new(),
+            path: None,
+            exports: vec![],
+            tail: false,
+            tries: 0,
+            tail_call: None,
+            macros: HashMap::new(),
+            modules: Modules::default(),
+            yielder: None,
+            scheduler: Scheduler::default(),
+            thread: None,
+            calls: vec![],
+            trace: None,
         }
     }
 
+    /// Lets timers fire in order without really waiting for them, as the clock
+    /// jumps to the next timer once every thread waits.
+    pub fn with_virtual_clock(self) -> Self {
+        self.scheduler.use_virtual_clock();
+        self
+    }
+
+    /// Limits the number of nested calls, beyond which calling fails with a
+    /// recursion error.
+    ///
+    /// Deep calls continue on new native stacks, so the limit doesn't depend
+    /// on the native stack of the thread evaluating.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.stack.set_max_depth(max_depth);
+        self
+    }
+
+    /// An evaluator for a coroutine or module of this one, which shares its
+    /// builtins, green threads and clock.
+    fn child(&self) -> Eval {
+        let mut eval = Eval::new().with_max_depth(self.stack.max_depth());
+        eval.stack.share_builtins(&self.stack);
+        eval.scheduler = self.scheduler.clone();
+        eval
+    }
+
+    /// Defines a builtin visible to every function, e.g. an async operation
+    /// of the host which returns a future from [`Eval::pending`].
+    pub fn define(&mut self, name: &str, value: Reference) {
+        self.stack.define(name.to_string(), value);
+    }
+
+    /// The milliseconds passed on the evaluator's clock.
+    pub fn now(&self) -> u64 {
+        self.scheduler.now()
+    }
+
+    /// Creates a future for an operation of the host, which the returned
+    /// completer settles from any thread once the operation completes.
+    pub fn pending(&mut self) -> (Reference, Completer) {
+        self.scheduler.pending()
+    }
+
     pub fn clear(&mut self) {
+        let max_depth = self.stack.max_depth();
         self.stack = Stack::new();
+        self.stack.set_max_depth(max_depth);
+        self.scheduler.clear();
+    }
+
+    /// Evaluates the program in the file at `path`, resolving its imports
+    /// relative to it.
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<Reference> {
+        let (path, program) = self.load_file(path)?;
+
+        let errors = Checker::new().check_program(&program);
+        if !errors.is_empty() {
+            return Err(Error::Type(format!(
+                "Could not type check `{}`: {:?}",
+                path.display(),
+                errors
+            )));
+        }
+
+        let outer = self.path.replace(path.clone());
+        self.modules.loading.push(path);
+
+        let ret = self.eval(Node::Expression(Expression::Program(program)));
+
+        self.modules.loading.pop();
+        self.path = outer;
+
+        let ret = ret?;
+        self.run_threads()?;
+
+        Ok(ret)
+    }
+
+    /// Infers the types of the program in `path` without running it.
+    pub fn lint_file(&mut self, path: impl AsRef<Path>) -> std::result::Result<Inference, Error> {
+        let (_, program) = self.load_file(path)?;
+
+        Ok(Inferrer::new().infer_program(&program))
+    }
+
+    /// Parses and expands the program in `path`, giving it with its canonical
+    /// path.
+    fn load_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> std::result::Result<(PathBuf, Program), Error> {
+        let path = path.as_ref();
+        let path = fs::canonicalize(path)
+            .map_err(|e| Error::Eval(format!("Could not find `{}`: {e}", path.display())))?;
+        let input = fs::read_to_string(&path)
+            .map_err(|e| Error::Eval(format!("Could not read `{}`: {e}", path.display())))?;
+
+        let program = Parser::new(Lexer::new(&input))
+            .and_then(|mut parser| parser.parse_program())
+            .map_err(|e| Error::Eval(format!("Could not parse `{}`: {e:?}", path.display())))?;
+
+        if !program.errors.is_empty() {
+            return Err(Error::Eval(format!(
+                "Could not parse `{}`: {:?}",
+                path.display(),
+                program.errors
+            )));
+        }
+
+        let program = self.expand(program)?;
+
+        Ok((path, program))
+    }
+
+    /// Removes the top-level macro definitions from `program` and replaces
+    /// calls of every macro defined so far with the expression they quote.
+    pub fn expand(&mut self, mut program: Program) -> std::result::Result<Program, Error> {
+        let mut statements = vec![];
+
+        for statement in program.statements {
+            match statement {
+                Statement::Let(LetStatement {
+                    name,
+                    value: Expression::Literal(Literal::Macro { parameters, body }),
+                    ..
+                }) => {
+                    self.macros.insert(name.name, (parameters, *body));
+                }
+                mut statement => {
+                    for child in statement.children_mut() {
+                        self.expand_expression(child)?;
+                    }
+
+                    statements.push(statement);
+                }
+            }
+        }
+
+        program.statements = statements;
+
+        Ok(program)
+    }
+
+    fn expand_expression(&mut self, expression: &mut Expression) -> std::result::Result<(), Error> {
+        if let Expression::Invoked { invoked, args } = expression {
+            let called = match &**invoked {
+                // Quotes mark the macros they call like other names they use.
+                Expression::Ident(Ident { name }) => {
+                    let name = name.strip_prefix('#').unwrap_or(name);
+                    self.macros
+                        .get(name)
+                        .cloned()
+                        .map(|definition| (name.to_string(), definition))
+                }
+                _ => None,
+            };
+
+            if let Some((name, (parameters, body))) = called {
+                let args = std::mem::take(args);
+                *expression = self.expand_call(&name, parameters, body, args)?;
+
+                // Macros may expand to calls of other macros.
+                return self.expand_expression(expression);
+            }
+        }
+
+        expression
+            .children_mut()
+            .into_iter()
+            .try_for_each(|child| self.expand_expression(child))
+    }
+
+    /// Calls a macro with the quoted `args` in a frame of its own and returns
+    /// the expression it quotes.
+    fn expand_call(
+        &mut self,
+        name: &str,
+        parameters: Vec<Ident>,
+        body: Expression,
+        args: Vec<Expression>,
+    ) -> std::result::Result<Expression, Error> {
+        if args.len() != parameters.len() {
+            return Err(Error::Eval(format!(
+                "Macro `{name}` expects {} arguments but received {}.",
+                parameters.len(),
+                args.len()
+            )));
+        }
+
+        self.stack.push_frame().map_err(Error::Recursion)?;
+        for (parameter, arg) in parameters.into_iter().zip(args) {
+            self.stack.add(parameter.name, Quote::erased(arg));
+        }
+
+        let ret = self.eval(Node::Expression(body));
+        self.stack.pop_frame();
+        let value = ret?.unwrap();
+
+        if !matches!(value.r#type(), ObjectType::Quote) {
+            return Err(Error::Type(format!(
+                "Macro `{name}` must return a quoted expression but returned {value}."
+            )));
+        }
+
+        Ok(unsafe { value.get::<Quote>().expression.clone() })
+    }
+
+    /// Evaluates `quote(expression)`, splicing the AST of the values of
+    /// `unquote(...)` calls into the quoted expression.
+    ///
+    /// Names bound inside the quoted expression are renamed, so that they
+    /// neither capture nor shadow the names of the code it is expanded into,
+    /// and the names it uses freely are marked to not be captured by them.
+    fn eval_quote(&mut self, mut args: Vec<Expression>) -> Result<Reference> {
+        if args.len() != 1 {
+            return Err(Error::Eval(
+                "quote() expects exactly one expression.".into(),
+            ));
+        }
+
+        let mut expression = args.remove(0);
+
+        let mut renamed = HashMap::new();
+        rename_bindings(&mut expression, &mut renamed);
+        rename_uses(&mut expression, &renamed);
+        mark_free(&mut expression);
+
+        self.eval_unquotes(&mut expression)?;
+
+        Ok(Flow::Continue(Quote::erased(expression)))
+    }
+
+    fn eval_unquotes(&mut self, expression: &mut Expression) -> std::result::Result<(), Error> {
+        if !is_call(expression, "unquote") {
+            return expression
+                .children_mut()
+                .into_iter()
+                .try_for_each(|child| self.eval_unquotes(child));
+        }
+
+        let Expression::Invoked { args, .. } = expression else {
+            unreachable!()
+        };
+
+        let [unquoted] = args.as_slice() else {
+            return Err(Error::Eval(
+                "unquote() expects exactly one expression.".into(),
+            ));
+        };
+
+        let value = self.eval(Node::Expression(unquoted.clone()))?.unwrap();
+
+        *expression = match value.r#type() {
+            ObjectType::Quote => unsafe { value.get::<Quote>().expression.clone() },
+            ObjectType::Integer => {
+                Expression::Literal(Literal::Int(unsafe { value.get::<Integer>().val }))
+            }
+            ObjectType::Bool if self.call_slot(&value, "truthy", None)?.is_some() => {
+                Expression::Literal(Literal::Bool(ast::Bool::True))
+            }
+            ObjectType::Bool => Expression::Literal(Literal::Bool(ast::Bool::False)),
+            ObjectType::Str => Expression::Literal(Literal::String(value.to_string())),
+            _ => {
+                return Err(Error::Type(format!(
+                    "Cannot unquote {value}, only quotes, integers, booleans and strings can be."
+                )))
+            }
+        };
+
+        Ok(())
     }
 
     pub fn eval(&mut self, node: Node) -> Result<Reference> {
+        // Deep evaluations continue on a new native stack instead of
+        // overflowing the one of the current thread.
+        if stack::exhausted() {
+            return stack::grow(|| self.eval(node)).map_err(|e| {
+                Error::Eval(format!("Could not continue on a new native stack: {e}"))
+            })?;
+        }
+
+        // Only the nodes passing it on below keep the tail position.
+        let tail = std::mem::take(&mut self.tail);
+
         let ret = match node {
-            Node::Statement(Statement::Expression(e)) => self.eval(Node::Expression(e))?,
+            Node::Statement(Statement::Expression(e)) => {
+                self.tail = tail;
+                self.eval(Node::Expression(e))?
+            }
             Node::Expression(Expression::Literal(Literal::Int(val))) => {
                 Flow::Continue(Integer::erased(val))
             }
@@ -123,11 +855,10 @@ impl Eval {
                 Flow::Continue(Str::erased(str))
             }
             Node::Expression(Expression::Ident(Ident { name })) => {
-                let val = self
-                    .stack
-                    .get(&name)
-                    .map(|a| a.clone())
-                    .ok_or(Error::Eval(format!("Variable {} not found in scope", name)))?;
+                let val =
+                    self.stack.get(&name).map(|a| a.clone()).ok_or_else(|| {
+                        Error::Name(format!("Variable {} not found in scope", name))
+                    })?;
 
                 Flow::Continue(val)
             }
@@ -141,604 +872,3907 @@ impl Eval {
                 condition,
                 consequence,
                 alternative,
-            }) => self.eval_if(*condition, *consequence, alternative.map(|b| *b))?,
+            }) => self.eval_if(*condition, *consequence, alternative.map(|b| *b), tail)?,
             Node::Expression(Expression::Block { statements }) => {
                 self.stack.push();
-                let ret = self.eval_statements(statements)?;
+                let ret = self.eval_statements(statements, tail);
                 self.stack.pop();
-                ret
+                ret?
+            }
+            Node::Expression(Expression::Program(pro)) => {
+                self.eval_statements(pro.statements, false)?
             }
-            Node::Expression(Expression::Program(pro)) => self.eval_statements(pro.statements)?,
             Node::Statement(Statement::Return(ReturnStatement { value })) => {
+                self.tail = true;
                 let ret = self.eval(Node::Expression(value))?;
                 Flow::Break(ret.unwrap())
             }
-            Node::Statement(Statement::Let(LetStatement { name, value })) => self.eval_assign(
-                Token::Operator(Operator::Assign),
-                Expression::Ident(name),
+            Node::Statement(Statement::Throw(ThrowStatement { value })) => {
+                self.eval_throw(value)?
+            }
+            Node::Expression(Expression::Try {
+                body,
+                catch,
+                finally,
+            }) => self.eval_try(*body, catch, finally.map(|finally| *finally))?,
+            Node::Statement(Statement::Let(LetStatement {
+                name,
+                annotation,
                 value,
-            )?,
+            })) => self.eval_declare(name, annotation, value, false)?,
+            Node::Statement(Statement::Const(LetStatement {
+                name,
+                annotation,
+                value,
+            })) => self.eval_declare(name, annotation, value, true)?,
+            Node::Expression(Expression::Invoked { invoked, args })
+                if matches!(&*invoked, Expression::Ident(Ident { name }) if name == "quote")
+                    && self.stack.get("quote".to_string()).is_none() =>
+            {
+                self.eval_quote(args)?
+            }
             Node::Expression(Expression::Invoked { invoked, args }) => {
-                self.eval_invoke(*invoked, args)?
+                self.eval_invoke(*invoked, args, tail)?
+            }
+            Node::Expression(Expression::Literal(Literal::Macro { .. })) => {
+                return Err(Error::Eval(
+                    "Macros can only be defined by top-level `let` statements.".into(),
+                ))
             }
             Node::Expression(Expression::Literal(Literal::Function {
-                parameters,
-                body,
+                name,
+                clauses,
                 capture,
-            })) => self.eval_function(parameters, *body, capture)?,
-            Node::Expression(Expression::Literal(Literal::Collection { members })) => {
-                Flow::Continue(Collection::erased(members.into_iter().try_fold(
-                    HashMap::new(),
-                    |mut members, (ident, exp)| {
-                        self.eval(Node::Expression(exp)).map(|member| {
-                            members.insert(ident, member.clone());
-                            members
-                        })
-                    },
-                )?))
-            }
-            Node::Expression(Expression::Literal(Literal::Vector { elements })) => Flow::Continue(
-                Vector::erased(elements.into_iter().try_fold(vec![], |mut elements, exp| {
-                    self.eval(Node::Expression(exp)).map(|reference| {
-                        elements.push(reference.unwrap());
-                        elements
-                    })
-                })?),
-            ),
+            })) => self.eval_function(None, name, clauses, capture)?,
+            Node::Expression(Expression::Yield(value)) => self.eval_yield(*value)?,
+            Node::Expression(Expression::Spawn(function)) => self.eval_spawn(*function)?,
+            Node::Expression(Expression::Await(future)) => self.eval_await(*future)?,
+            Node::Expression(Expression::Propagate(value)) => self.eval_propagate(*value)?,
+            Node::Expression(Expression::Literal(Literal::Collection { members, parent })) => {
+                self.eval_collection(members, parent.map(|p| *p))?
+            }
+            Node::Expression(Expression::Literal(Literal::Vector { elements })) => {
+                match self.eval_elements(elements)? {
+                    Flow::Continue(elements) => Flow::Continue(Vector::erased(elements)),
+                    Flow::Break(mut value) => Flow::Break(value.remove(0)),
+                }
+            }
+            Node::Expression(Expression::Comprehension {
+                key,
+                element,
+                clauses,
+            }) => self.eval_comprehension(key.map(|key| *key), *element, clauses)?,
             Node::Expression(Expression::Indexed { indexee, index }) => {
                 self.eval_index(*indexee, *index)?
             }
+            Node::Expression(Expression::Sliced {
+                sliced,
+                start,
+                end,
+                step,
+            }) => self.eval_slice(*sliced, start, end, step)?,
+            Node::Statement(Statement::Enum(statement)) => self.eval_enum(statement)?,
+            Node::Statement(Statement::Import(statement)) => self.eval_import(statement)?,
+            Node::Statement(Statement::Export(statement)) => self.eval_export(*statement)?,
+            Node::Expression(Expression::Match { subject, arms }) => {
+                self.eval_match(*subject, arms, tail)?
+            }
             _ => todo!(),
         };
 
         Ok(ret)
     }
-    fn eval_function(
+    /// Invokes the vtable `slot` of `obj` with `arg`.
+    ///
+    /// Collections can override any slot listed in `OVERLOADS` with a special
+    /// member, e.g. `__add__` for `add_lhs`, which is called as a method of
+    /// `obj`. Returns `None` if `obj` does not support `slot` for `arg`.
+    pub fn call_slot(
         &mut self,
-        parameters: Vec<Ident>,
-        body: Expression,
-        capture: Vec<Ident>,
-    ) -> Result<Reference> {
-        let capture = capture
-            .into_iter()
-            .try_fold(HashMap::new(), |mut map, ident| {
-                match self.stack.get(&ident.name) {
-                    Some(value) => {
-                        map.insert(ident, value.clone());
-                        Ok(map)
-                    }
-                    None => Err(Error::Eval(format!(
-                        "Attempting to capture unknown variable {} in function decleration.",
-                        ident.name
-                    ))),
-                }
-            })?;
+        obj: &Reference,
+        slot: &str,
+        arg: Option<Reference>,
+    ) -> std::result::Result<Option<Reference>, Error> {
+        if matches!(obj.r#type(), ObjectType::Collection) {
+            let overload =
+                OVERLOADS
+                    .iter()
+                    .find(|(name, _)| *name == slot)
+                    .and_then(|(_, member)| {
+                        Collection::lookup(
+                            obj,
+                            &Ident {
+                                name: member.to_string(),
+                            },
+                        )
+                    });
+
+            if let Some((method, owner)) = overload {
+                let ret = self.call_overload(obj, method, owner, arg)?;
+
+                return Ok(match slot {
+                    // The truthy slot signals falsiness by returning nothing.
+                    "truthy" => ret.v_table().get("truthy").and_then(|truthy| truthy(None)),
+                    _ => Some(ret),
+                });
+            }
 
-        Ok(Flow::Continue(Function::erased(parameters, body, capture)))
-    }
+            // `!=` falls back to negating an overloaded `==`.
+            if slot == "neq_lhs" {
+                let eq = Collection::lookup(
+                    obj,
+                    &Ident {
+                        name: "__eq__".to_string(),
+                    },
+                );
 
-    fn eval_index(&mut self, indexee: Expression, index: Expression) -> Result<Reference> {
-        let index = self.eval(Node::Expression(index))?.unwrap();
-        let indexee = self.eval(Node::Expression(indexee))?.unwrap();
-
-        let obj = indexee
-            .v_table()
-            .get("idx")
-            .ok_or(Error::Eval("Object does not support indexing".into()))?(Some(
-            index,
-        ))
-        .ok_or(Error::Eval(
-            "Indexing not supported with this object.".into(),
-        ))?;
+                if let Some((method, owner)) = eq {
+                    let ret = self.call_overload(obj, method, owner, arg)?;
+                    return Ok(ret.v_table().get("inv").and_then(|inv| inv(None)));
+                }
+            }
+        }
 
-        Ok(Flow::Continue(obj))
+        // Containers compare their contents here, so that overloaded `==` of
+        // nested collections is used as well.
+        if let ("eq_lhs" | "neq_lhs", ObjectType::Vector | ObjectType::Collection, Some(rhs)) =
+            (slot, obj.r#type(), &arg)
+        {
+            let equal = self.equals(obj, rhs)?;
+            return Ok(Some(object::Bool::erased(equal == (slot == "eq_lhs"))));
+        }
+
+        Ok(obj.v_table().get(slot).and_then(|f| f(arg)))
     }
 
-    fn eval_invoke(&mut self, invoked: Expression, args: Vec<Expression>) -> Result<Reference> {
-        let function = self.eval(Node::Expression(invoked))?.unwrap();
+    /// Whether `lhs == rhs`, including overloaded `==` of collections.
+    fn equals(&mut self, lhs: &Reference, rhs: &Reference) -> std::result::Result<bool, Error> {
+        match (lhs.r#type(), rhs.r#type()) {
+            (ObjectType::Vector, ObjectType::Vector) => {
+                let lhs = unsafe { lhs.get::<Vector>() }.elements.clone();
+                let rhs = unsafe { rhs.get::<Vector>() }.elements.clone();
+
+                if lhs.len() != rhs.len() {
+                    return Ok(false);
+                }
 
-        let args: Vec<Reference> =
-            args.into_iter().try_fold(vec![], |mut args, arg| {
-                match self.eval(Node::Expression(arg)) {
-                    Err(e) => Err(e),
-                    Ok(arg) => {
-                        args.push(arg.unwrap());
-                        Ok(args)
+                for (lhs, rhs) in lhs.iter().zip(rhs.iter()) {
+                    if !self.equals(lhs, rhs)? {
+                        return Ok(false);
                     }
                 }
-            })?;
 
-        if matches!(function.r#type(), ObjectType::Builtin) {
-            let builtin = unsafe { function.get_mut::<Builtin>() };
-            return builtin.call(args);
-        }
+                Ok(true)
+            }
+            (ObjectType::Collection, _) => {
+                let eq = Collection::lookup(
+                    lhs,
+                    &Ident {
+                        name: "__eq__".to_string(),
+                    },
+                );
 
-        if !matches!(function.r#type(), ObjectType::Function) {
-            return Err(Error::Eval(format!(
-                "Inovking non-function types is not supported",
-            )));
-        }
+                if let Some((method, owner)) = eq {
+                    let ret = self.call_overload(lhs, method, owner, Some(rhs.clone()))?;
+                    return self.truthy(&ret);
+                }
 
-        let function = unsafe { function.get_mut::<Function>() };
+                if !matches!(rhs.r#type(), ObjectType::Collection) {
+                    return Ok(false);
+                }
 
-        if function.parameters.len() != args.len() {
-            return Err(Error::Eval(format!(
-                "Incorrect number of arguments passed for invocation",
-            )));
-        }
+                let (lhs, rhs) =
+                    unsafe { (lhs.get::<Collection>(), rhs.get::<Collection>()) };
 
-        self.stack.push_frame();
+                let protos = match (lhs.proto(), rhs.proto()) {
+                    (Some(lhs), Some(rhs)) => self.equals(&lhs, &rhs)?,
+                    (lhs, rhs) => lhs.is_none() && rhs.is_none(),
+                };
 
-        for (ident, arg) in function.parameters.iter().zip(args.into_iter()) {
-            self.stack.add(ident.name.clone(), arg);
-        }
+                let (lhs, rhs) = (lhs.members.clone(), rhs.members.clone());
 
-        for (ident, captured) in &function.capture {
-            self.stack.add(ident.name.clone(), captured.clone());
-        }
+                if !protos || lhs.len() != rhs.len() {
+                    return Ok(false);
+                }
 
-        let ret = self.eval(Node::Expression(function.body.clone()));
+                for (ident, lhs) in lhs.iter() {
+                    match rhs.get(ident) {
+                        Some(rhs) if self.equals(lhs, rhs)? => continue,
+                        _ => return Ok(false),
+                    }
+                }
 
-        self.stack.pop_frame();
-
-        ret
+                Ok(true)
+            }
+            _ => Ok(object::equals(lhs, rhs)),
+        }
     }
 
-    fn eval_statements(&mut self, statements: Vec<Statement>) -> Result<Reference> {
-        let mut ret = Flow::Continue(Unit::erased());
-        for st in statements {
-            ret = match self.eval(Node::Statement(st))? {
-                f @ Flow::Continue(_) => f,
-                f @ Flow::Break(_) => {
-                    return Ok(f);
-                }
-            };
+    /// Whether `cond` counts as true, failing for objects that cannot be used
+    /// as conditions.
+    fn truthy(&mut self, cond: &Reference) -> std::result::Result<bool, Error> {
+        if cond.v_table().get("truthy").is_none() {
+            return Err(Error::Eval(format!(
+                "Condition type of {} is not fit for conditions.",
+                cond
+            )));
         }
 
-        Ok(ret)
+        Ok(self.call_slot(cond, "truthy", None)?.is_some())
     }
 
-    fn eval_prefix(&mut self, operator: Token, operand: Expression) -> Result<Reference> {
-        let mut operand = self.eval(Node::Expression(operand))?;
-        if operand.is_break() {
-            return Ok(operand);
+    fn call_overload(
+        &mut self,
+        obj: &Reference,
+        method: Reference,
+        owner: Reference,
+        arg: Option<Reference>,
+    ) -> std::result::Result<Reference, Error> {
+        let parent = unsafe { owner.get::<Collection>().proto() };
+        let receiver = Receiver {
+            object: obj.clone(),
+            parent,
         };
 
-        let err = Error::Eval(format!(
-            "Unsupported operator {:?} for operand type {}",
-            operator, operand
-        ));
+        Ok(self
+            .apply(method, arg.into_iter().collect(), Some(receiver))?
+            .unwrap())
+    }
 
-        match operator {
-            Token::Operator(Operator::Bang) => {
-                operand = Flow::Continue(
-                    (operand.v_table().get("inv").ok_or(err.clone())?)(None).ok_or(err.clone())?,
-                );
+    /// Binds the enum's name to a collection holding a constructor for every
+    /// variant with fields and the value of every variant without.
+    fn eval_enum(&mut self, statement: EnumStatement) -> Result<Reference> {
+        let EnumStatement { name, variants } = statement;
+        let enumeration: Arc<str> = Arc::from(name.name.as_str());
+
+        let members = variants
+            .into_iter()
+            .map(|variant| {
+                let tag: Arc<str> = Arc::from(variant.name.name.as_str());
+
+                if variant.fields.is_empty() {
+                    return (
+                        variant.name,
+                        Variant::erased(enumeration.clone(), tag, vec![]),
+                    );
+                }
+
+                let enumeration = enumeration.clone();
+                let arity = variant.fields.len();
+                let constructor = Builtin::erased(move |_, args| {
+                    if args.len() != arity {
+                        return Err(Error::Eval(format!(
+                            "{enumeration}.{tag} expects {arity} fields but got {}",
+                            args.len()
+                        )));
+                    }
+
+                    Ok(Flow::Continue(Variant::erased(
+                        enumeration.clone(),
+                        tag.clone(),
+                        args,
+                    )))
+                });
+
+                (variant.name, constructor)
+            })
+            .collect();
+
+        let namespace = Collection::erased(members);
+        self.stack
+            .declare(name.name, namespace.clone(), false)
+            .map_err(Error::Eval)?;
+
+        Ok(Flow::Continue(namespace))
+    }
+
+    fn eval_import(&mut self, statement: ImportStatement) -> Result<Reference> {
+        let ImportStatement { path, imported } = statement;
+
+        let namespace = self.import(&path)?;
+
+        match imported {
+            Imported::Module(alias) => self
+                .stack
+                .declare(alias.name, namespace.clone(), false)
+                .map_err(Error::Eval)?,
+            Imported::Names(names) => {
+                for name in names {
+                    let value = unsafe { namespace.get::<Collection>() }
+                        .members
+                        .get(&name)
+                        .cloned()
+                        .ok_or_else(|| {
+                            Error::Name(format!("Module `{path}` does not export `{}`.", name.name))
+                        })?;
+
+                    self.stack
+                        .declare(name.name, value, false)
+                        .map_err(Error::Eval)?;
+                }
             }
-            Token::Operator(Operator::Minus) => {
-                operand = Flow::Continue(
-                    (operand.v_table().get("neg").ok_or(err.clone())?)(None).ok_or(err.clone())?,
-                );
+        }
+
+        Ok(Flow::Continue(namespace))
+    }
+
+    /// Evaluates a `let`, `const` or `enum` statement and adds the name it
+    /// declares to the exports of the module.
+    fn eval_export(&mut self, statement: Statement) -> Result<Reference> {
+        let name = match &statement {
+            Statement::Let(LetStatement { name, .. })
+            | Statement::Const(LetStatement { name, .. })
+            | Statement::Enum(EnumStatement { name, .. }) => name.name.clone(),
+            _ => {
+                return Err(Error::Eval(
+                    "Only `let`, `const` and `enum` statements can be exported.".into(),
+                ))
             }
-            _ => unsafe { core::hint::unreachable_unchecked() },
+        };
+
+        let ret = self.eval(Node::Statement(statement))?;
+        self.exports.push(name);
+
+        Ok(ret)
+    }
+
+    /// Returns the namespace of the exports of the module at `path`, evaluating
+    /// it in its own global scope unless it was imported before.
+    fn import(&mut self, path: &str) -> std::result::Result<Reference, Error> {
+        let base = self
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let path = fs::canonicalize(base.join(path))
+            .map_err(|e| Error::Eval(format!("Could not find module `{path}`: {e}")))?;
+
+        if let Some(namespace) = self.modules.loaded.get(&path) {
+            return Ok(namespace.clone());
         }
 
-        Ok(operand)
+        if let Some(start) = self.modules.loading.iter().position(|p| *p == path) {
+            let cycle = self.modules.loading[start..]
+                .iter()
+                .chain([&path])
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+
+            return Err(Error::Eval(format!("Import cycle: {}", cycle.join(" -> "))));
+        }
+
+        // The module is evaluated on the same native stack, below our calls.
+        let depth = self.stack.max_depth().saturating_sub(self.stack.depth());
+        let mut module = self.child().with_max_depth(depth);
+        module.modules = std::mem::take(&mut self.modules);
+
+        let ret = module.run_file(&path);
+        self.modules = std::mem::take(&mut module.modules);
+        ret?;
+
+        let members = module
+            .stack
+            .globals()
+            .into_iter()
+            .filter(|(name, _)| module.exports.contains(name))
+            .map(|(name, value)| (Ident { name }, value))
+            .collect();
+
+        // Every importer shares the namespace, so its members must not be
+        // reassigned under them. Unlike freezing, sealing leaves the exported
+        // values mutable, so the namespace isn't shareable with other threads.
+        let namespace = Collection::erased(members);
+        unsafe { namespace.get::<Collection>() }.sealed.set(true);
+        self.modules.loaded.insert(path, namespace.clone());
+
+        Ok(namespace)
     }
 
-    fn eval_access(
+    fn eval_match(
         &mut self,
-        _operator: Token,
-        lhs: Expression,
-        rhs: Expression,
+        subject: Expression,
+        arms: Vec<MatchArm>,
+        tail: bool,
     ) -> Result<Reference> {
-        let collection = match lhs {
-            c @ Expression::Literal(Literal::Collection { .. }) => {
-                self.eval(Node::Expression(c))?.unwrap()
+        let subject = self.eval(Node::Expression(subject))?;
+        if subject.is_break() {
+            return Ok(subject);
+        }
+        let subject = subject.unwrap();
+
+        for MatchArm { pattern, body } in arms {
+            self.stack.push();
+
+            let ret = match self.bind_pattern(&pattern, &subject) {
+                Ok(true) => {
+                    self.tail = tail;
+                    Some(self.eval(Node::Expression(body)))
+                }
+                Ok(false) => None,
+                Err(e) => Some(Err(e)),
+            };
+
+            self.stack.pop();
+
+            if let Some(ret) = ret {
+                return ret;
             }
-            Expression::Ident(Ident { name }) => {
-                let c = self.stack.get(&name).ok_or(Error::Eval(format!(
-                    "Cannot find {} in the current scope.",
-                    name
-                )))?;
+        }
 
-                c
+        Err(Error::Eval(format!("No match arm matches {}", subject)))
+    }
+
+    /// Matches `value` against `pattern`, adding the bindings it introduces to
+    /// the current scope.
+    fn bind_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Reference,
+    ) -> std::result::Result<bool, Error> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+            Pattern::Binding(ident) => {
+                self.stack.add(ident.name.clone(), value.clone());
+                Ok(true)
             }
-            _ => {
-                return Err(Error::Eval(format!(
-                    "Accessing non-collection types is not supported."
-                )))
+            Pattern::Literal(literal) => {
+                let literal = match literal {
+                    Literal::Int(val) => Integer::erased(*val),
+                    Literal::String(str) => Str::erased(str.clone()),
+                    Literal::Bool(bool) => object::Bool::erased(matches!(bool, ast::Bool::True)),
+                    _ => unreachable!("the parser only produces plain literal patterns"),
+                };
+
+                Ok(object::equals(&literal, value))
             }
-        };
+            Pattern::Variant {
+                enumeration,
+                tag,
+                fields,
+            } => {
+                if !matches!(value.r#type(), ObjectType::Variant) {
+                    return Ok(false);
+                }
 
-        if !matches!(collection.r#type(), ObjectType::Collection) {
-            return Err(Error::Eval(format!(
-                "Accessing non-collection types is not supported",
-            )));
-        }
+                let variant = unsafe { value.get::<Variant>() };
 
-        let members = unsafe { collection.get_mut::<Collection>().members.clone() };
+                if *variant.tag != tag.name
+                    || enumeration
+                        .as_ref()
+                        .is_some_and(|enumeration| *variant.enumeration != enumeration.name)
+                {
+                    return Ok(false);
+                }
 
-        let ident = match rhs {
-            Expression::Ident(i) => i,
-            _ => {
-                let rhs = self.eval(Node::Expression(rhs))?;
-                return Err(Error::Eval(format!(
-                    "Exprected identifier as accessor {} instead",
-                    rhs
-                )));
+                if variant.fields.len() != fields.len() {
+                    return Err(Error::Eval(format!(
+                        "Pattern for {}.{} expects {} fields but the variant has {}",
+                        variant.enumeration,
+                        variant.tag,
+                        fields.len(),
+                        variant.fields.len()
+                    )));
+                }
+
+                for (pattern, field) in fields.iter().zip(variant.fields.clone().iter()) {
+                    if !self.bind_pattern(pattern, field)? {
+                        return Ok(false);
+                    }
+                }
+
+                Ok(true)
+            }
+            Pattern::Vector { elements, rest } => {
+                if !matches!(value.r#type(), ObjectType::Vector) {
+                    return Ok(false);
+                }
+
+                let values = unsafe { value.get::<Vector>().elements.clone() };
+
+                let fits = match rest {
+                    Some(_) => values.len() >= elements.len(),
+                    None => values.len() == elements.len(),
+                };
+                if !fits {
+                    return Ok(false);
+                }
+
+                for (pattern, value) in elements.iter().zip(values.iter()) {
+                    if !self.bind_pattern(pattern, value)? {
+                        return Ok(false);
+                    }
+                }
+
+                if let Some(rest) = rest {
+                    let rest_values = values[elements.len()..].to_vec();
+                    self.stack
+                        .add(rest.name.clone(), Vector::erased(rest_values));
+                }
+
+                Ok(true)
             }
+        }
+    }
+
+    fn eval_collection(
+        &mut self,
+        members: Vec<Member>,
+        parent: Option<Expression>,
+    ) -> Result<Reference> {
+        let parent = match parent.map(|parent| self.eval(Node::Expression(parent))) {
+            Some(parent) => match parent? {
+                Flow::Continue(parent) => Some(parent),
+                f @ Flow::Break(_) => return Ok(f),
+            },
+            None => None,
         };
 
-        members
-            .get(&ident)
-            .map(|mem| Flow::Continue(mem.clone()))
-            .ok_or(Error::Eval(format!(
-                "Collection does not contain the member {}.",
-                ident.name
-            )))
+        let mut values = HashMap::new();
+        for member in members {
+            match member {
+                Member::Named(ident, value) => {
+                    let value = self.eval_bound(&ident.name, value)?;
+                    if value.is_break() {
+                        return Ok(value);
+                    }
+                    values.insert(ident, value.unwrap());
+                }
+                Member::Spread(base) => {
+                    let base = self.eval(Node::Expression(base))?;
+                    if base.is_break() {
+                        return Ok(base);
+                    }
+                    let base = base.unwrap();
+
+                    if !matches!(base.r#type(), ObjectType::Collection) {
+                        return Err(Error::Eval(format!(
+                            "Only collections can be spread into a definition, got {base}."
+                        )));
+                    }
+
+                    let base = unsafe { base.get::<Collection>().members.clone() };
+                    values.extend(base.iter().map(|(k, v)| (k.clone(), v.clone())));
+                }
+            }
+        }
+
+        let collection = Collection::erased(values);
+
+        if let Some(parent) = parent {
+            Collection::set_proto(&collection, parent).map_err(Error::Eval)?;
+        }
+
+        Ok(Flow::Continue(collection))
+    }
+
+    /// Evaluates `value` to be bound to `name`, which names function literals
+    /// for error messages.
+    fn eval_bound(&mut self, name: &str, value: Expression) -> Result<Reference> {
+        match value {
+            Expression::Literal(Literal::Function {
+                name: binding,
+                clauses,
+                capture,
+            }) => self.eval_function(Some(name), binding, clauses, capture),
+            value => self.eval(Node::Expression(value)),
+        }
     }
 
-    fn eval_access_assign(
+    fn eval_function(
         &mut self,
-        _operator: Token,
-        collection: Expression,
-        accessor: Expression,
-        rhs: Expression,
+        declared: Option<&str>,
+        name: Option<Ident>,
+        clauses: Vec<FunctionClause>,
+        capture: Vec<Ident>,
     ) -> Result<Reference> {
-        let collection = self.eval(Node::Expression(collection))?.unwrap();
-        let ident = match accessor {
-            Expression::Ident(i) => i,
-            _ => {
-                return Err(Error::Eval(
-                    "Collection can only be accessed via an ident.".into(),
-                ))
+        let capture = capture
+            .into_iter()
+            .try_fold(HashMap::new(), |mut map, ident| {
+                match self.stack.get(&ident.name) {
+                    Some(value) => {
+                        let constant = self.stack.is_constant(&ident.name);
+                        map.insert(ident, (value.clone(), constant));
+                        Ok(map)
+                    }
+                    None => Err(Error::Eval(format!(
+                        "Attempting to capture unknown variable {} in function decleration.",
+                        ident.name
+                    ))),
+                }
+            })?;
+
+        Ok(Flow::Continue(Function::erased(
+            declared, name, clauses, capture,
+        )))
+    }
+
+    fn eval_index(&mut self, indexee: Expression, index: Expression) -> Result<Reference> {
+        let index = self.eval(Node::Expression(index))?;
+        if index.is_break() {
+            return Ok(index);
+        }
+        let index = index.unwrap();
+
+        let indexee = self.eval(Node::Expression(indexee))?;
+        if indexee.is_break() {
+            return Ok(indexee);
+        }
+        let indexee = indexee.unwrap();
+
+        if let Some(obj) = self.call_slot(&indexee, "idx", Some(index.clone()))? {
+            return Ok(Flow::Continue(obj));
+        }
+
+        if matches!(index.r#type(), ObjectType::Integer) {
+            if let Some(len) = self.call_slot(&indexee, "len", None)? {
+                return Err(Error::Index(format!(
+                    "Index {index} out of range for length {len}."
+                )));
             }
-        };
+        }
 
-        if !matches!(collection.r#type(), ObjectType::Collection) {
-            return Err(Error::Eval("Only collections can be accessed.".into()));
+        Err(Error::Type(
+            "Indexing not supported with this object.".into(),
+        ))
+    }
+
+    fn eval_slice(
+        &mut self,
+        sliced: Expression,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+        step: Option<Box<Expression>>,
+    ) -> Result<Reference> {
+        let sliced = self.eval(Node::Expression(sliced))?;
+        if sliced.is_break() {
+            return Ok(sliced);
         }
+        let sliced = sliced.unwrap();
 
-        let rhs = self.eval(Node::Expression(rhs))?;
-        if rhs.is_break() {
-            return Ok(Flow::Break(rhs.unwrap()));
-        };
+        let mut bounds = [None; 3];
+        for (bound, expression) in bounds.iter_mut().zip([start, end, step]) {
+            let Some(expression) = expression else {
+                continue;
+            };
 
-        let collection = unsafe { collection.get_mut::<Collection>() };
+            let value = self.eval(Node::Expression(*expression))?;
+            if value.is_break() {
+                return Ok(value);
+            }
 
-        let mut map = (*collection.members).clone();
+            *bound = Some(slice_bound(&value.unwrap())?);
+        }
+        let [start, end, step] = bounds;
 
-        map.insert(ident, rhs.clone());
+        if step == Some(0) {
+            return Err(Error::Index("Slice step cannot be zero.".into()));
+        }
 
-        collection.members = Arc::new(map);
+        let obj = self
+            .call_slot(&sliced, "idx", Some(Slice::erased(start, end, step)))?
+            .ok_or(Error::Type(
+                "Slicing not supported with this object.".into(),
+            ))?;
 
-        Ok(rhs)
+        Ok(Flow::Continue(obj))
     }
 
-    fn eval_assign(
+    /// Evaluates an invocation, or defers it to `apply_named` if it is in
+    /// `tail` position.
+    fn eval_invoke(
         &mut self,
-        operator: Token,
-        lhs: Expression,
-        rhs: Expression,
+        invoked: Expression,
+        args: Vec<Expression>,
+        tail: bool,
     ) -> Result<Reference> {
-        let ident = match lhs {
-            Expression::Ident(Ident { name }) => name,
+        let (function, receiver) = match invoked {
             Expression::Infix {
                 operator: Token::Operator(Operator::Dot),
-                lhs: collection,
-                rhs: accessor,
-                ..
-            } => return self.eval_access_assign(operator, *collection, *accessor, rhs),
-            _ => {
-                let lhs = self.eval(Node::Expression(lhs))?;
-                return Err(Error::Eval(format!(
-                    "Exprected identifier in assignment got {} instead",
-                    lhs
-                )));
+                lhs,
+                rhs,
+            } => {
+                // `super.method(args)` looks `method` up on the parent of the
+                // collection defining the running method, but keeps `self`.
+                let is_super =
+                    matches!(&*lhs, Expression::Ident(Ident { name }) if name == "super");
+
+                let this = if is_super {
+                    Some(self.stack.get("self".to_string()).ok_or(Error::Eval(
+                        "`super` can only be used inside of methods.".into(),
+                    ))?)
+                } else {
+                    None
+                };
+
+                let receiver = self.eval(Node::Expression(*lhs))?;
+                if receiver.is_break() {
+                    return Ok(receiver);
+                }
+                let receiver = receiver.unwrap();
+                let object = this.unwrap_or(receiver.clone());
+
+                match (&*rhs, matches!(receiver.r#type(), ObjectType::Collection)) {
+                    (Expression::Ident(ident), true) => {
+                        let (function, owner) = Collection::lookup(&receiver, ident)
+                            .ok_or_else(|| self.missing_member(&receiver, ident))?;
+                        let parent = unsafe { owner.get::<Collection>().proto() };
+
+                        (function, Some(Receiver { object, parent }))
+                    }
+                    _ => (self.eval_member(&receiver, *rhs)?.unwrap(), None),
+                }
+            }
+            invoked => {
+                let function = self.eval(Node::Expression(invoked))?;
+                if function.is_break() {
+                    return Ok(function);
+                }
+
+                (function.unwrap(), None)
             }
         };
-        let rhs = self.eval(Node::Expression(rhs))?;
-        if rhs.is_break() {
-            return Ok(Flow::Break(rhs.unwrap()));
+
+        let mut positional = vec![];
+        let mut named = vec![];
+        for arg in args {
+            match arg {
+                Expression::Named { name, value } => {
+                    let value = self.eval(Node::Expression(*value))?;
+                    if value.is_break() {
+                        return Ok(value);
+                    }
+
+                    named.push((name, value.unwrap()))
+                }
+                arg => positional.push(arg),
+            }
+        }
+
+        let positional = match self.eval_elements(positional)? {
+            Flow::Continue(positional) => positional,
+            Flow::Break(mut value) => return Ok(Flow::Break(value.remove(0))),
         };
 
-        let err = Error::Eval(format!(
-            "Unsupported operator {:?} for operand types {} and {}",
-            operator, ident, rhs
-        ));
+        // Calls in tail position unwind like `return` and are made by the
+        // `apply_named` of the running function instead, so that they don't
+        // grow the Rust stack. Inside of `try` they still have to be caught.
+        if tail && self.tries == 0 && !self.calls.is_empty() {
+            self.tail_call = Some(TailCall {
+                function,
+                args: positional,
+                named,
+                receiver,
+                returns: None,
+            });
+
+            return Ok(Flow::Break(Unit::erased()));
+        }
 
-        let rhs = match operator {
-            Token::Operator(op @ Operator::MinusEqual | op @ Operator::PlusEqual) => {
-                let op = match op {
-                    Operator::PlusEqual => "add_lhs",
-                    Operator::MinusEqual => "sub_lhs",
-                    _ => unsafe { core::hint::unreachable_unchecked() },
+        self.apply_named(function, positional, named, receiver)
+    }
+
+    /// Evaluates the elements of a vector literal or the positional arguments
+    /// of an invocation, expanding spread elements in place.
+    ///
+    /// If an element breaks, this breaks with only the value it broke with.
+    fn eval_elements(&mut self, elements: Vec<Expression>) -> Result<Vec<Reference>> {
+        let mut evaluated = vec![];
+
+        for element in elements {
+            let (value, spread) = match element {
+                Expression::Spread(iterable) => (self.eval(Node::Expression(*iterable))?, true),
+                element => (self.eval(Node::Expression(element))?, false),
+            };
+
+            match value {
+                Flow::Break(value) => return Ok(Flow::Break(vec![value])),
+                Flow::Continue(iterable) if spread => evaluated.extend(self.iterate(&iterable)?),
+                Flow::Continue(value) => evaluated.push(value),
+            }
+        }
+
+        Ok(Flow::Continue(evaluated))
+    }
+
+    /// Evaluates a comprehension in its own scope, producing a vector of its
+    /// elements, or a collection if it has a `key`.
+    fn eval_comprehension(
+        &mut self,
+        key: Option<Expression>,
+        element: Expression,
+        clauses: Vec<Clause>,
+    ) -> Result<Reference> {
+        let mut produced = vec![];
+
+        self.stack.push();
+        let ret = self.eval_clauses(&clauses, key.as_ref(), &element, &mut produced);
+        self.stack.pop();
+
+        if let Some(value) = ret? {
+            return Ok(Flow::Break(value));
+        }
+
+        if key.is_none() {
+            let elements = produced.into_iter().map(|(_, element)| element);
+            return Ok(Flow::Continue(Vector::erased(elements.collect())));
+        }
+
+        let members = produced
+            .into_iter()
+            .map(|(key, element)| {
+                let key = key.unwrap();
+
+                if !matches!(key.r#type(), ObjectType::Str) {
+                    return Err(Error::Eval(format!(
+                        "Comprehension keys must be strings, got {key}."
+                    )));
+                }
+
+                let name = unsafe { key.get::<Str>().str.to_string() };
+
+                Ok((Ident { name }, element))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+
+        Ok(Flow::Continue(Collection::erased(members)))
+    }
+
+    /// Applies the first of `clauses` and recurses into the rest, pushing the
+    /// key and element to `produced` once all clauses passed.
+    ///
+    /// Gives the value of a `?` that returned early from the enclosing
+    /// function, if any.
+    fn eval_clauses(
+        &mut self,
+        clauses: &[Clause],
+        key: Option<&Expression>,
+        element: &Expression,
+        produced: &mut Vec<(Option<Reference>, Reference)>,
+    ) -> std::result::Result<Option<Reference>, Error> {
+        let Some((clause, clauses)) = clauses.split_first() else {
+            let key = match key {
+                Some(key) => match self.eval(Node::Expression(key.clone()))? {
+                    Flow::Continue(key) => Some(key),
+                    Flow::Break(value) => return Ok(Some(value)),
+                },
+                None => None,
+            };
+            let element = match self.eval(Node::Expression(element.clone()))? {
+                Flow::Continue(element) => element,
+                Flow::Break(value) => return Ok(Some(value)),
+            };
+
+            produced.push((key, element));
+            return Ok(None);
+        };
+
+        match clause {
+            Clause::For { binding, iterable } => {
+                let iterable = match self.eval(Node::Expression(iterable.clone()))? {
+                    Flow::Continue(iterable) => iterable,
+                    Flow::Break(value) => return Ok(Some(value)),
                 };
 
-                let lhs = self.stack.get(&ident).ok_or(Error::Eval(format!(
-                    "Identifier {} not found in scope",
-                    ident
-                )))?;
+                let iter = self.iter(&iterable)?;
+
+                while let Some(value) = self.next(&iter)? {
+                    self.stack
+                        .declare(binding.name.clone(), value, false)
+                        .map_err(Error::Eval)?;
+
+                    if let Some(value) = self.eval_clauses(clauses, key, element, produced)? {
+                        return Ok(Some(value));
+                    }
+                }
+            }
+            Clause::If(condition) => {
+                let condition = match self.eval(Node::Expression(condition.clone()))? {
+                    Flow::Continue(condition) => condition,
+                    Flow::Break(value) => return Ok(Some(value)),
+                };
+
+                if self.truthy(&condition)? {
+                    return self.eval_clauses(clauses, key, element, produced);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The elements produced by iterating over `obj`, e.g. when spreading it.
+    ///
+    /// Vectors produce their elements and strings their characters. Iterators
+    /// are run until they are exhausted.
+    pub fn iterate(&mut self, obj: &Reference) -> std::result::Result<Vec<Reference>, Error> {
+        match obj.r#type() {
+            ObjectType::Vector => Ok(unsafe { obj.get::<Vector>() }.elements.to_vec()),
+            ObjectType::Str => Ok(unsafe { obj.get::<Str>() }
+                .str
+                .chars()
+                .map(|c| Str::erased(c.to_string()))
+                .collect()),
+            _ => {
+                let iter = self.iter(obj)?;
+                let mut elements = vec![];
+
+                while let Some(element) = self.next(&iter)? {
+                    elements.push(element);
+                }
+
+                Ok(elements)
+            }
+        }
+    }
+
+    /// An iterator over `obj`, which is `obj` itself if it already is one.
+    ///
+    /// Collections are iterators if they implement `__next__`.
+    pub fn iter(&mut self, obj: &Reference) -> std::result::Result<Reference, Error> {
+        let next = Ident {
+            name: "__next__".into(),
+        };
+
+        match obj.r#type() {
+            ObjectType::Iter => Ok(obj.clone()),
+            ObjectType::Vector | ObjectType::Str => {
+                let mut elements = self.iterate(obj)?.into_iter();
+                Ok(Iter::erased(move |_| Ok(elements.next())))
+            }
+            // Channels are iterated until closed.
+            ObjectType::Channel => {
+                let obj = obj.clone();
+                Ok(Iter::erased(move |eval| eval.receive(&obj)))
+            }
+            ObjectType::Collection if Collection::lookup(obj, &next).is_some() => {
+                let obj = obj.clone();
+                Ok(Iter::erased(move |eval| eval.next(&obj)))
+            }
+            _ => Err(Error::Type(format!("{obj} is not iterable."))),
+        }
+    }
+
+    /// Advances the iterator `obj`, returning `None` once it is exhausted.
+    ///
+    /// Besides `Iter` objects, anything with a `next` slot is an iterator,
+    /// which returns `done` once exhausted.
+    pub fn next(&mut self, obj: &Reference) -> std::result::Result<Option<Reference>, Error> {
+        if matches!(obj.r#type(), ObjectType::Iter) {
+            return unsafe { obj.get::<Iter>() }.next(self);
+        }
+
+        let value = self
+            .call_slot(obj, "next", None)?
+            .ok_or_else(|| Error::Type(format!("{obj} is not an iterator.")))?;
+
+        Ok((!Variant::is_done(&value)).then_some(value))
+    }
+
+    /// Calls `function` with `args`.
+    ///
+    /// Functions invoked as a method, i.e. `obj.method(args)` on a collection,
+    /// receive `obj` as the implicit `self` binding and the parent of the
+    /// collection defining the method as `super`.
+    pub fn apply(
+        &mut self,
+        function: Reference,
+        args: Vec<Reference>,
+        receiver: Option<Receiver>,
+    ) -> Result<Reference> {
+        self.apply_named(function, args, vec![], receiver)
+    }
+
+    /// Calls `function` with positional `args` followed by `named` arguments,
+    /// see [`Eval::apply`].
+    ///
+    /// Calls the body makes in tail position are made here in turn, in place
+    /// of returning from the body. Their frames replace the frame of the
+    /// caller, so traces of errors they raise list the function that started
+    /// the chain of tail calls but none of the functions in between. The
+    /// return annotations of the functions in the chain are checked against
+    /// the value of the last call, innermost function first.
+    pub fn apply_named(
+        &mut self,
+        mut function: Reference,
+        mut args: Vec<Reference>,
+        mut named: Vec<(Ident, Reference)>,
+        mut receiver: Option<Receiver>,
+    ) -> Result<Reference> {
+        let mut chained = false;
+        let mut returns = Vec::new();
+
+        let ret = loop {
+            let ret = self.apply_once(function.clone(), args, named, receiver);
+            let call = self.tail_call.take();
+
+            match (ret, call) {
+                (Ok(Flow::Break(_)), Some(call)) => {
+                    if !chained {
+                        self.calls
+                            .push(call_name(unsafe { function.get::<Function>() }));
+                        chained = true;
+                    }
+
+                    returns.extend(call.returns);
+                    function = call.function;
+                    args = call.args;
+                    named = call.named;
+                    receiver = call.receiver;
+                }
+                (ret, _) => break ret,
+            }
+        };
+
+        if chained {
+            self.calls.pop();
+        }
+
+        let ret = ret?.unwrap();
+
+        for (r#type, function) in returns.iter().rev() {
+            if !conforms(&ret, r#type) {
+                return Err(Error::Type(format!(
+                    "{} should return `{}` but returned {ret}.",
+                    function_name(unsafe { function.get::<Function>() }),
+                    r#type,
+                )));
+            }
+        }
+
+        Ok(Flow::Continue(ret))
+    }
+
+    /// Calls `function` once, breaking without a value if its body ended with
+    /// a deferred tail call.
+    fn apply_once(
+        &mut self,
+        function: Reference,
+        args: Vec<Reference>,
+        named: Vec<(Ident, Reference)>,
+        receiver: Option<Receiver>,
+    ) -> Result<Reference> {
+        if matches!(function.r#type(), ObjectType::Builtin) {
+            if !named.is_empty() {
+                return Err(Error::Eval(
+                    "Builtins do not accept named arguments.".to_string(),
+                ));
+            }
+
+            let builtin = unsafe { function.get::<Builtin>() };
+            return builtin.call(self, args);
+        }
+
+        if !matches!(function.r#type(), ObjectType::Function) {
+            return Err(Error::Eval(
+                "Inovking non-function types is not supported".to_string(),
+            ));
+        }
+
+        let callee = function.clone();
+        let function = unsafe { function.get::<Function>() };
+
+        self.stack.push_frame().map_err(Error::Recursion)?;
+
+        // The frame is new, so none of the captures can be declared already.
+        for (ident, (captured, constant)) in &function.capture {
+            let _ = self
+                .stack
+                .declare(ident.name.clone(), captured.clone(), *constant);
+        }
+
+        if let Some(binding) = &function.binding {
+            self.stack.add(binding.name.clone(), callee.clone());
+        }
+
+        if let Some(Receiver { object, parent }) = receiver {
+            self.stack.add("self".to_string(), object);
+
+            if let Some(parent) = parent {
+                self.stack.add("super".to_string(), parent);
+            }
+        }
+
+        self.calls.push(call_name(function));
+        let tries = std::mem::take(&mut self.tries);
+
+        let ret = match self.select_clause(function, args, named) {
+            Ok((clause, Some(returned))) => Ok((clause, Flow::Break(returned))),
+            Ok((clause, None)) if clause.generator => {
+                self.tries = tries;
+                self.calls.pop();
+                let frame = self.stack.pop_frame().unwrap();
+
+                return Ok(Flow::Continue(Self::generator(
+                    frame,
+                    (*clause.body).clone(),
+                    self.child(),
+                )));
+            }
+            Ok((clause, None)) if clause.r#async => {
+                self.tries = tries;
+                self.calls.pop();
+                let frame = self.stack.pop_frame().unwrap();
+
+                return Ok(Flow::Continue(self.start_async(
+                    frame,
+                    clause.clone(),
+                    function_name(function),
+                )));
+            }
+            Ok((clause, None)) => {
+                self.tail = true;
+                self.eval(Node::Expression((*clause.body).clone()))
+                    .map(|ret| (clause, ret))
+            }
+            Err(e) => Err(e),
+        };
+
+        if ret.is_err() && self.trace.is_none() {
+            self.trace = Some(self.calls.clone());
+        }
+
+        self.tries = tries;
+        self.calls.pop();
+        self.stack.pop_frame();
+
+        let (clause, ret) = ret?;
+
+        if let Some(returns) = &clause.returns {
+            // The result of a deferred tail call is checked once the chain of
+            // tail calls has ended.
+            if let Some(call) = &mut self.tail_call {
+                if ret.is_break() {
+                    call.returns = Some((returns.clone(), callee));
+                    return Ok(ret);
+                }
+            }
+
+            if !conforms(ret.as_ref().unwrap(), returns) {
+                return Err(Error::Type(format!(
+                    "{} should return `{returns}` but returned {}.",
+                    function_name(function),
+                    ret.unwrap()
+                )));
+            }
+        }
+
+        // A `return` only unwinds up to the function it was issued in.
+        if ret.is_break() && self.tail_call.is_some() {
+            return Ok(ret);
+        }
+
+        Ok(Flow::Continue(ret.unwrap()))
+    }
+
+    fn eval_throw(&mut self, value: Expression) -> Result<Reference> {
+        let value = self.eval(Node::Expression(value))?;
+        if value.is_break() {
+            return Ok(value);
+        }
+        let value = value.unwrap();
+
+        if error::is_value(&value) {
+            return Err(Error::Thrown(value));
+        }
+
+        // Anything else is wrapped into an error value holding it.
+        let message = self
+            .call_slot(&value, "str", None)?
+            .map(|str| str.to_string())
+            .unwrap_or_default();
+        let error = error::value("Error", &message);
+        error.set_idx(Str::erased("value".into()), value)?;
+
+        Err(Error::Thrown(error))
+    }
+
+    fn eval_try(
+        &mut self,
+        body: Expression,
+        catch: Option<Catch>,
+        finally: Option<Expression>,
+    ) -> Result<Reference> {
+        // Only traces of errors raised inside of this `try` are relevant.
+        let outer = self.trace.take();
+        self.tries += 1;
+
+        let ret = match (self.eval(Node::Expression(body)), catch) {
+            (Err(e), Some(catch)) => self.eval_catch(e, catch),
+            (ret, _) => ret,
+        };
+
+        let ret = match finally.map(|finally| self.eval(Node::Expression(finally))) {
+            Some(Err(e)) => Err(e),
+            Some(Ok(Flow::Break(value))) => Ok(Flow::Break(value)),
+            _ => ret,
+        };
+
+        self.tries -= 1;
+
+        if ret.is_ok() {
+            self.trace = outer;
+        }
+
+        ret
+    }
+
+    fn eval_catch(&mut self, e: Error, catch: Catch) -> Result<Reference> {
+        let error = e.into_value()?;
+
+        let trace = self.trace.take().unwrap_or(self.calls.clone());
+        let trace = Vector::erased(
+            trace
+                .iter()
+                .map(|call| Str::erased(call.to_string()))
+                .collect(),
+        );
+        let untraced = Collection::lookup(
+            &error,
+            &Ident {
+                name: "trace".into(),
+            },
+        )
+        .is_some_and(|(trace, _)| object::equals(&trace, &Vector::erased(vec![])));
+        if untraced {
+            // Frozen error values keep their empty trace.
+            let _ = error.set_idx(Str::erased("trace".into()), trace);
+        }
+
+        self.stack.push();
+        let ret = match catch.binding {
+            Some(binding) => self
+                .stack
+                .declare(binding.name, error, false)
+                .map_err(Error::Eval),
+            None => Ok(()),
+        }
+        .and_then(|()| self.eval(Node::Expression(*catch.body)));
+        self.stack.pop();
+
+        ret
+    }
+
+    /// Creates the iterator returned by calling a generator function, which
+    /// evaluates `body` in the bound `frame` up to the next `yield` whenever
+    /// it is advanced.
+    ///
+    /// The body runs on a coroutine with its own evaluator, which keeps the
+    /// state of the suspended evaluation on the coroutine's stack.
+    fn generator(frame: Frame, body: Expression, mut eval: Eval) -> Reference {
+        let mut coroutine = Coroutine::new(move |yielder| {
+            eval.stack.resume_frame(frame);
+            eval.yielder = Some(yielder);
+
+            eval.eval(Node::Expression(body)).map(|_| ())
+        });
+
+        Iter::erased(move |_| coroutine.resume())
+    }
+
+    /// Queues a green thread evaluating the body of an async function in the
+    /// bound `frame`, giving the future it settles with the result.
+    fn start_async(&mut self, frame: Frame, clause: FunctionClause, name: String) -> Reference {
+        let future = Future::erased();
+        let settled = future.clone();
+        let mut eval = self.child();
+
+        self.scheduler.spawn(Coroutine::new(move |thread| {
+            eval.stack.resume_frame(frame);
+            eval.thread = Some(thread);
+
+            let outcome = eval
+                .eval(Node::Expression(*clause.body))
+                .map(Flow::unwrap)
+                .and_then(|ret| match &clause.returns {
+                    Some(returns) if !conforms(&ret, returns) => Err(Error::Type(format!(
+                        "{name} should return `{returns}` but returned {ret}."
+                    ))),
+                    _ => Ok(ret),
+                });
+
+            // Dropping the coroutine unwinds the body, which settles nothing.
+            if !matches!(outcome, Err(Error::Abort(_))) {
+                Future::settle(&settled, outcome);
+                eval.scheduler.progressed();
+            }
+
+            Ok(())
+        }));
+
+        future
+    }
+
+    /// Blocks until `future` is settled, giving its value or failing with its
+    /// error.
+    pub fn wait_for(&mut self, future: &Reference) -> std::result::Result<Reference, Error> {
+        if !matches!(future.r#type(), ObjectType::Future) {
+            return Err(Error::Type(format!(
+                "Cannot await {future}, which is not a future."
+            )));
+        }
+
+        self.wait(|| Future::is_settled(future))?;
+
+        unsafe { future.get::<Future>() }
+            .outcome
+            .clone()
+            .unwrap()
+    }
+
+    fn eval_await(&mut self, future: Expression) -> Result<Reference> {
+        let future = self.eval(Node::Expression(future))?;
+        if future.is_break() {
+            return Ok(future);
+        }
+
+        Ok(Flow::Continue(self.wait_for(&future.unwrap())?))
+    }
+
+    /// Creates a future resolving to unit after `ms` milliseconds.
+    pub fn sleep(&mut self, ms: u64) -> Reference {
+        let future = Future::erased();
+        self.scheduler
+            .start_timer(ms, Timer::Resolve(future.clone()));
+        future
+    }
+
+    /// Creates a future settled like `future`, unless that takes longer than
+    /// `ms` milliseconds, in which case it fails with a timeout error.
+    pub fn timeout(
+        &mut self,
+        future: &Reference,
+        ms: u64,
+    ) -> std::result::Result<Reference, Error> {
+        if !matches!(future.r#type(), ObjectType::Future) {
+            return Err(Error::Type(format!(
+                "Cannot time out {future}, which is not a future."
+            )));
+        }
+
+        let timed = Future::erased();
+        Future::forward(future, timed.clone());
+        if !Future::is_settled(&timed) {
+            self.scheduler.start_timer(ms, Timer::Expire(timed.clone()));
+        }
+
+        Ok(timed)
+    }
+
+    /// Queues a green thread calling `function`, which runs on a coroutine
+    /// with its own evaluator like generators do.
+    fn eval_spawn(&mut self, function: Expression) -> Result<Reference> {
+        let function = self.eval(Node::Expression(function))?;
+        if function.is_break() {
+            return Ok(function);
+        }
+        let function = function.unwrap();
+
+        if !matches!(
+            function.r#type(),
+            ObjectType::Function | ObjectType::Builtin
+        ) {
+            return Err(Error::Type(format!(
+                "`spawn` expects a function but received {function}."
+            )));
+        }
+
+        let mut eval = self.child();
+
+        self.scheduler.spawn(Coroutine::new(move |thread| {
+            eval.thread = Some(thread);
+
+            eval.apply(function, vec![], None).map(|_| ())
+        }));
+
+        Ok(Flow::Continue(Unit::erased()))
+    }
+
+    /// Calls `function` with each of `values` on a pool of worker threads, each
+    /// running its own evaluator, and gives the results in order.
+    ///
+    /// The workers read the function and values alongside each other, which
+    /// is why they have to be shared. Builtins which can't be shared, e.g.
+    /// mutable values the host defined, aren't visible to the workers.
+    pub fn par_map(
+        &mut self,
+        function: Shareable,
+        values: Vec<Shareable>,
+    ) -> std::result::Result<Reference, Error> {
+        let workers = thread::available_parallelism()
+            .map_or(1, usize::from)
+            .min(values.len());
+        let max_depth = self.stack.max_depth();
+        let builtins = self
+            .stack
+            .builtins()
+            .filter_map(|(name, value)| Some((name.clone(), value.share()?)))
+            .collect::<Vec<_>>();
+        let claimed = AtomicUsize::new(0);
+
+        let work = || {
+            // Workers don't share green threads, as each runs its own.
+            let mut eval = Eval::new().with_max_depth(max_depth);
+            for (name, value) in &builtins {
+                eval.define(name, value.reference());
+            }
+            let mut results = vec![];
+
+            // Workers claim the next value until none are left, so that slow
+            // calls don't hold up the values of a fixed share.
+            loop {
+                let index = claimed.fetch_add(1, atomic::Ordering::Relaxed);
+                let Some(value) = values.get(index) else {
+                    break;
+                };
+
+                let result = eval
+                    .apply(function.reference(), vec![value.reference()], None)
+                    .and_then(|result| {
+                        eval.run_threads()?;
+                        Ok(result.unwrap())
+                    });
+
+                let failed = result.is_err();
+                results.push((index, result));
+                if failed {
+                    break;
+                }
+            }
+
+            // The results are only used once the worker finished.
+            Handoff(results)
+        };
+
+        let mut results = thread::scope(|scope| {
+            let workers = (0..workers)
+                .map(|_| {
+                    stack::spawn_scoped(scope, NATIVE_STACK_SIZE, work)
+                        .map_err(|e| Error::Eval(format!("Could not start worker: {e}")))
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            let mut results = vec![];
+            for worker in workers {
+                match worker.join() {
+                    Ok(Handoff(worker)) => results.extend(worker),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+
+            Ok::<_, Error>(results)
+        })?;
+
+        results.sort_by_key(|(index, _)| *index);
+
+        Ok(Vector::erased(
+            results
+                .into_iter()
+                .map(|(_, result)| result)
+                .collect::<std::result::Result<_, _>>()?,
+        ))
+    }
+
+    /// Blocks until `ready`, letting the other green threads run meanwhile.
+    ///
+    /// Green threads suspend to the evaluator running them, anything else runs
+    /// the queued threads itself, and the event loop once all of them are
+    /// blocked. Fails with a deadlock once there is nothing left to wait for,
+    /// which only concerns the caller, so the other threads stay queued.
+    fn wait(&mut self, mut ready: impl FnMut() -> bool) -> std::result::Result<(), Error> {
+        while !ready() {
+            if let Some(thread) = &self.thread {
+                thread.suspend(Unit::erased())?;
+                continue;
+            }
+
+            let progress = self.scheduler.progress();
+            let ran = self.scheduler.run_round()?;
+            let stuck = !ran || self.scheduler.progress() == progress;
+
+            if !ready() && stuck && !self.scheduler.advance() {
+                return Err(Error::Deadlock("Deadlock: every thread is blocked.".into()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the green threads until every one of them has finished.
+    ///
+    /// Threads that can never finish are dropped along with the deadlock.
+    pub fn run_threads(&mut self) -> std::result::Result<(), Error> {
+        let scheduler = self.scheduler.clone();
+        let ret = self.wait(|| scheduler.is_empty());
+
+        if matches!(ret, Err(Error::Deadlock(_))) {
+            self.scheduler.clear();
+        }
+
+        ret
+    }
+
+    /// Sends `value` over `channel` once its buffer has room, waiting until
+    /// it is received if the channel has no capacity.
+    pub fn send(
+        &mut self,
+        channel: &Reference,
+        value: Reference,
+    ) -> std::result::Result<(), Error> {
+        if !matches!(channel.r#type(), ObjectType::Channel) {
+            return Err(Error::Type(format!(
+                "Cannot send to {channel}, which is not a channel."
+            )));
+        }
+
+        let state = || unsafe { channel.get_mut::<Channel>() };
+
+        self.wait(|| {
+            let channel = state();
+            channel.closed || channel.capacity == 0 || channel.buffer.len() < channel.capacity
+        })?;
+
+        let channel = state();
+        if channel.closed {
+            return Err(Error::Eval("Cannot send to a closed channel.".into()));
+        }
+
+        channel.buffer.push_back(value);
+        channel.sent += 1;
+        let sent = channel.sent;
+        self.scheduler.progressed();
+
+        if channel.capacity == 0 {
+            self.wait(|| state().received >= sent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Receives the next value sent over `channel`, or `None` once it is
+    /// closed and every value was received.
+    pub fn receive(
+        &mut self,
+        channel: &Reference,
+    ) -> std::result::Result<Option<Reference>, Error> {
+        if !matches!(channel.r#type(), ObjectType::Channel) {
+            return Err(Error::Type(format!(
+                "Cannot receive from {channel}, which is not a channel."
+            )));
+        }
+
+        let state = || unsafe { channel.get_mut::<Channel>() };
+
+        self.wait(|| {
+            let channel = state();
+            channel.closed || !channel.buffer.is_empty()
+        })?;
+
+        let channel = state();
+        let value = channel.buffer.pop_front();
+        if value.is_some() {
+            channel.received += 1;
+            self.scheduler.progressed();
+        }
+
+        Ok(value)
+    }
+
+    /// Closes `channel`, after which receiving from it gives `done` once the
+    /// values already sent are received.
+    pub fn close(&mut self, channel: &Reference) -> std::result::Result<(), Error> {
+        if !matches!(channel.r#type(), ObjectType::Channel) {
+            return Err(Error::Type(format!(
+                "Cannot close {channel}, which is not a channel."
+            )));
+        }
+
+        unsafe { channel.get_mut::<Channel>() }.closed = true;
+        self.scheduler.progressed();
+
+        Ok(())
+    }
+
+    fn eval_yield(&mut self, value: Expression) -> Result<Reference> {
+        let value = self.eval(Node::Expression(value))?;
+        if value.is_break() {
+            return Ok(value);
+        }
+
+        let yielder = self.yielder.as_ref().ok_or(Error::Eval(
+            "`yield` can only be used inside of generators.".into(),
+        ))?;
+        yielder.suspend(value.unwrap())?;
+
+        Ok(Flow::Continue(Unit::erased()))
+    }
+
+    /// Unwraps an `ok` result, or breaks out with an `err` result the same way
+    /// `return` does.
+    fn eval_propagate(&mut self, value: Expression) -> Result<Reference> {
+        let value = self.eval(Node::Expression(value))?;
+        if value.is_break() {
+            return Ok(value);
+        }
+
+        let value = value.unwrap();
+
+        match Variant::as_result(&value) {
+            Some(Ok(value)) => Ok(Flow::Continue(value)),
+            Some(Err(_)) => Ok(Flow::Break(value)),
+            None => Err(Error::Type(format!(
+                "`?` expects an ok or err result but received {value}."
+            ))),
+        }
+    }
+
+    /// Binds `args` to the parameters of the first clause of `function` they
+    /// match, in a new scope of the current frame.
+    ///
+    /// Also gives the value a `?` in a default or guard of the clause returned
+    /// early from the function, if any.
+    fn select_clause<'f>(
+        &mut self,
+        function: &'f Function,
+        args: Vec<Reference>,
+        named: Vec<(Ident, Reference)>,
+    ) -> std::result::Result<(&'f FunctionClause, Option<Reference>), Error> {
+        // A single clause reports why the arguments don't fit it.
+        let strict = function.clauses.len() == 1;
+
+        for clause in &function.clauses {
+            self.stack.push();
+
+            let mut returned = None;
+
+            match self.bind_arguments(
+                function,
+                clause,
+                args.clone(),
+                named.clone(),
+                strict,
+                &mut returned,
+            ) {
+                Ok(true) => return Ok((clause, returned)),
+                Ok(false) => self.stack.pop(),
+                Err(e) => {
+                    self.stack.pop();
+                    return Err(e);
+                }
+            }
+        }
+
+        let args = args
+            .iter()
+            .map(ToString::to_string)
+            .chain(
+                named
+                    .iter()
+                    .map(|(ident, arg)| format!("{}: {arg}", ident.name)),
+            )
+            .collect::<Vec<_>>();
+
+        Err(Error::Eval(format!(
+            "{} has no clause matching the arguments ({}).",
+            function_name(function),
+            args.join(", ")
+        )))
+    }
+
+    /// Binds the parameters of `clause` in the current scope, giving whether
+    /// the arguments match its patterns and guard.
+    ///
+    /// Parameters without an argument take their default, which is evaluated
+    /// after binding the parameters before it, and surplus positional
+    /// arguments are collected into the rest parameter. Unless `strict`, the
+    /// wrong number of arguments or arguments of the wrong type don't match
+    /// rather than being errors.
+    ///
+    /// A `?` in a default or the guard returns from the function early, which
+    /// selects the clause and puts the value in `returned`.
+    fn bind_arguments(
+        &mut self,
+        function: &Function,
+        clause: &FunctionClause,
+        mut args: Vec<Reference>,
+        named: Vec<(Ident, Reference)>,
+        strict: bool,
+        returned: &mut Option<Reference>,
+    ) -> std::result::Result<bool, Error> {
+        let received = args.len() + named.len();
+        let name = || function_name(function);
+
+        let reject = |e: Error| if strict { Err(e) } else { Ok(false) };
+
+        let arity_error = || {
+            let required = clause
+                .parameters
+                .iter()
+                .filter(|parameter| parameter.default.is_none())
+                .count();
+            let total = clause.parameters.len();
+
+            let (expected, count) = match (&clause.rest, required == total) {
+                (Some(_), _) => (format!("at least {required}"), required),
+                (None, true) => (format!("{total}"), total),
+                (None, false) => (format!("{required} to {total}"), total),
+            };
+            let arguments = if count == 1 { "argument" } else { "arguments" };
+
+            Error::Eval(format!(
+                "{} expects {expected} {arguments} but received {received}.",
+                name()
+            ))
+        };
+
+        let surplus = args.split_off(args.len().min(clause.parameters.len()));
+        if !surplus.is_empty() && clause.rest.is_none() {
+            return reject(arity_error());
+        }
+
+        let mut bound: Vec<Option<Reference>> = args.into_iter().map(Some).collect();
+        bound.resize(clause.parameters.len(), None);
+
+        for (ident, arg) in named {
+            let Some(position) = clause
+                .parameters
+                .iter()
+                .position(|parameter| parameter.ident() == Some(&ident))
+            else {
+                return reject(Error::Eval(format!(
+                    "{} has no parameter named {}.",
+                    name(),
+                    ident.name
+                )));
+            };
+
+            if bound[position].replace(arg).is_some() {
+                return Err(Error::Eval(format!(
+                    "{} received argument {} more than once.",
+                    name(),
+                    ident.name
+                )));
+            }
+        }
+
+        for (parameter, arg) in clause.parameters.iter().zip(bound) {
+            let arg = match (arg, &parameter.default) {
+                (Some(arg), _) => arg,
+                (None, Some(default)) => match self.eval(Node::Expression(default.clone()))? {
+                    Flow::Continue(arg) => arg,
+                    Flow::Break(value) => {
+                        *returned = Some(value);
+                        return Ok(true);
+                    }
+                },
+                (None, None) => return reject(arity_error()),
+            };
+
+            if let Some(annotation) = &parameter.annotation {
+                if !conforms(&arg, annotation) {
+                    let parameter = parameter.ident().map_or("_", |ident| &ident.name);
+                    return reject(Error::Type(format!(
+                        "{} expects `{annotation}` for parameter `{parameter}` but received {arg}.",
+                        name()
+                    )));
+                }
+            }
+
+            if !self.bind_pattern(&parameter.pattern, &arg)? {
+                return Ok(false);
+            }
+        }
+
+        if let Some(rest) = &clause.rest {
+            self.stack.add(rest.name.clone(), Vector::erased(surplus));
+        }
+
+        if let Some(guard) = &clause.guard {
+            return match self.eval(Node::Expression(guard.clone()))? {
+                Flow::Continue(guard) => self.truthy(&guard),
+                Flow::Break(value) => {
+                    *returned = Some(value);
+                    Ok(true)
+                }
+            };
+        }
+
+        Ok(true)
+    }
+
+    /// Evaluates `statements` in order, the last one in tail position if the
+    /// block is.
+    fn eval_statements(&mut self, statements: Vec<Statement>, tail: bool) -> Result<Reference> {
+        let mut ret = Flow::Continue(Unit::erased());
+        let len = statements.len();
+        for (i, st) in statements.into_iter().enumerate() {
+            self.tail = tail && i + 1 == len;
+            ret = match self.eval(Node::Statement(st))? {
+                f @ Flow::Continue(_) => f,
+                f @ Flow::Break(_) => {
+                    return Ok(f);
+                }
+            };
+        }
+
+        Ok(ret)
+    }
+
+    fn eval_prefix(&mut self, operator: Token, operand: Expression) -> Result<Reference> {
+        let mut operand = self.eval(Node::Expression(operand))?;
+        if operand.is_break() {
+            return Ok(operand);
+        };
+
+        let err = || {
+            Error::Type(format!(
+                "Unsupported operator {:?} for operand type {}",
+                operator, operand
+            ))
+        };
+
+        match &operator {
+            Token::Operator(Operator::Bang) => {
+                let inverted = self.call_slot(&operand, "inv", None)?.ok_or_else(err)?;
+                operand = Flow::Continue(inverted);
+            }
+            Token::Operator(Operator::Minus) => {
+                let negated = self.call_slot(&operand, "neg", None)?.ok_or_else(err)?;
+                operand = Flow::Continue(negated);
+            }
+            Token::Operator(Operator::LeftArrow) => {
+                operand = Flow::Continue(self.receive(&operand)?.unwrap_or(Variant::done()));
+            }
+            _ => unsafe { core::hint::unreachable_unchecked() },
+        }
+
+        Ok(operand)
+    }
+
+    fn eval_access(
+        &mut self,
+        _operator: Token,
+        lhs: Expression,
+        rhs: Expression,
+    ) -> Result<Reference> {
+        let collection = self.eval(Node::Expression(lhs))?;
+        if collection.is_break() {
+            return Ok(collection);
+        }
+
+        self.eval_member(&collection, rhs)
+    }
+
+    fn eval_member(&mut self, collection: &Reference, accessor: Expression) -> Result<Reference> {
+        if !matches!(collection.r#type(), ObjectType::Collection) {
+            return Err(Error::Eval(
+                "Accessing non-collection types is not supported".to_string(),
+            ));
+        }
+
+        let ident = match accessor {
+            Expression::Ident(i) => i,
+            _ => {
+                let accessor = self.eval(Node::Expression(accessor))?;
+                return Err(Error::Eval(format!(
+                    "Exprected identifier as accessor {} instead",
+                    accessor
+                )));
+            }
+        };
+
+        Collection::lookup(collection, &ident)
+            .map(|(mem, _)| Flow::Continue(mem))
+            .ok_or_else(|| self.missing_member(collection, &ident))
+    }
+
+    /// The error for `collection` not having the member `ident`, which names
+    /// the module if the collection is the namespace of one.
+    fn missing_member(&self, collection: &Reference, ident: &Ident) -> Error {
+        let module = self
+            .modules
+            .loaded
+            .iter()
+            .find(|(_, namespace)| namespace.ptr_eq(collection));
+
+        match module {
+            Some((path, _)) => Error::Name(format!(
+                "Module `{}` does not export `{}`.",
+                path.display(),
+                ident.name
+            )),
+            None => Error::Eval(format!(
+                "Collection does not contain the member {}.",
+                ident.name
+            )),
+        }
+    }
+
+    /// Assigns to `container[index]`, where member assignment passes the
+    /// member name as a `Str` index.
+    ///
+    /// Collections and vectors are shared by reference: the container is
+    /// mutated in place, so every binding that refers to it observes the
+    /// new value.
+    fn eval_index_assign(
+        &mut self,
+        operator: Token,
+        container: Reference,
+        index: Reference,
+        rhs: Expression,
+    ) -> Result<Reference> {
+        let rhs = self.eval(Node::Expression(rhs))?;
+        if rhs.is_break() {
+            return Ok(rhs);
+        };
+
+        let rhs = match operator {
+            Token::Operator(Operator::PlusEqual | Operator::MinusEqual) => {
+                let current = self
+                    .call_slot(&container, "idx", Some(index.clone()))?
+                    .ok_or_else(|| {
+                        Error::Eval(format!(
+                            "Cannot read {} of {} for compound assignment.",
+                            index, container
+                        ))
+                    })?;
+
+                Flow::Continue(self.eval_compound(&operator, current, rhs.unwrap())?)
+            }
+            _ => rhs,
+        };
+
+        container.set_idx(index, rhs.as_ref().map(|t| t.clone()).unwrap())?;
+
+        Ok(rhs)
+    }
+
+    fn eval_compound(
+        &mut self,
+        operator: &Token,
+        lhs: Reference,
+        rhs: Reference,
+    ) -> std::result::Result<Reference, Error> {
+        let err = || {
+            Error::Type(format!(
+                "Unsupported operator {:?} for operand types {} and {}",
+                operator, lhs, rhs
+            ))
+        };
+
+        let op = match operator {
+            Token::Operator(Operator::PlusEqual) => "add_lhs",
+            Token::Operator(Operator::MinusEqual) => "sub_lhs",
+            _ => unsafe { core::hint::unreachable_unchecked() },
+        };
+
+        self.call_slot(&lhs, op, Some(rhs.clone()))?.ok_or_else(err)
+    }
+
+    fn eval_declare(
+        &mut self,
+        name: Ident,
+        annotation: Option<Type>,
+        value: Expression,
+        constant: bool,
+    ) -> Result<Reference> {
+        let value = self.eval_bound(&name.name, value)?;
+        if value.is_break() {
+            return Ok(value);
+        }
+
+        if let Some(annotation) = annotation {
+            if !conforms(value.as_ref().unwrap(), &annotation) {
+                return Err(Error::Type(format!(
+                    "`{}` is declared as `{annotation}` but was bound to {}.",
+                    name.name,
+                    value.unwrap()
+                )));
+            }
+        }
+
+        self.stack
+            .declare(
+                name.name,
+                value.as_ref().map(|t| t.clone()).unwrap(),
+                constant,
+            )
+            .map_err(Error::Eval)?;
+
+        Ok(value)
+    }
+
+    fn eval_assign(
+        &mut self,
+        operator: Token,
+        lhs: Expression,
+        rhs: Expression,
+    ) -> Result<Reference> {
+        let ident = match lhs {
+            Expression::Ident(Ident { name }) => name,
+            Expression::Infix {
+                operator: Token::Operator(Operator::Dot),
+                lhs: collection,
+                rhs: accessor,
+            } => {
+                let Expression::Ident(ident) = *accessor else {
+                    return Err(Error::Eval(
+                        "Collection can only be accessed via an ident.".into(),
+                    ));
+                };
+
+                let collection = self.eval(Node::Expression(*collection))?;
+                if collection.is_break() {
+                    return Ok(collection);
+                }
+
+                return self.eval_index_assign(
+                    operator,
+                    collection.unwrap(),
+                    Str::erased(ident.name),
+                    rhs,
+                );
+            }
+            Expression::Indexed { indexee, index } => {
+                let container = self.eval(Node::Expression(*indexee))?;
+                if container.is_break() {
+                    return Ok(container);
+                }
+
+                let index = self.eval(Node::Expression(*index))?;
+                if index.is_break() {
+                    return Ok(index);
+                }
+
+                return self.eval_index_assign(operator, container.unwrap(), index.unwrap(), rhs);
+            }
+            _ => {
+                let lhs = self.eval(Node::Expression(lhs))?;
+                return Err(Error::Eval(format!(
+                    "Exprected identifier in assignment got {} instead",
+                    lhs
+                )));
+            }
+        };
+        let rhs = self.eval(Node::Expression(rhs))?;
+        if rhs.is_break() {
+            return Ok(Flow::Break(rhs.unwrap()));
+        };
+
+        let rhs = match operator {
+            Token::Operator(Operator::MinusEqual | Operator::PlusEqual) => {
+                let lhs = self.stack.get(&ident).ok_or_else(|| {
+                    Error::Name(format!("Identifier {} not found in scope", ident))
+                })?;
+
+                Flow::Continue(self.eval_compound(&operator, lhs, rhs.unwrap())?)
+            }
+            _ => rhs,
+        };
+
+        self.stack
+            .assign(ident, rhs.as_ref().map(|t| t.clone()).unwrap())
+            .map_err(Error::Eval)?;
+
+        Ok(rhs)
+    }
+
+    fn eval_infix(
+        &mut self,
+        operator: Token,
+        lhs: Expression,
+        rhs: Expression,
+    ) -> Result<Reference> {
+        if matches!(
+            operator,
+            Token::Operator(Operator::Assign)
+                | Token::Operator(Operator::PlusEqual)
+                | Token::Operator(Operator::MinusEqual)
+        ) {
+            return self.eval_assign(operator, lhs, rhs);
+        }
+
+        if matches!(operator, Token::Operator(Operator::Dot)) {
+            return self.eval_access(operator, lhs, rhs);
+        }
+
+        let lhs = self.eval(Node::Expression(lhs))?;
+        if lhs.is_break() {
+            return Ok(lhs);
+        }
+        let rhs = self.eval(Node::Expression(rhs))?;
+        if rhs.is_break() {
+            return Ok(rhs);
+        }
+
+        if matches!(operator, Token::Operator(Operator::LeftArrow)) {
+            self.send(&lhs, rhs.unwrap())?;
+            return Ok(Flow::Continue(Unit::erased()));
+        }
+
+        let err = || {
+            Error::Type(format!(
+                "Unsupported operator {:?} for operand types {} and {}",
+                operator, lhs, rhs
+            ))
+        };
+
+        let op = match operator {
+            Token::Operator(Operator::Minus) => "sub_lhs",
+            Token::Operator(Operator::MinusEqual) => "sub_lhs",
+            Token::Operator(Operator::Plus) => "add_lhs",
+            Token::Operator(Operator::PlusEqual) => "add_lhs",
+            Token::Operator(Operator::Multiply) => "mul_lhs",
+            Token::Operator(Operator::Divide) => "div_lhs",
+            Token::Operator(Operator::Equal) => "eq_lhs",
+            Token::Operator(Operator::NotEqual) => "neq_lhs",
+            Token::Operator(Operator::Less) => "le_lhs",
+            Token::Operator(Operator::LessOrEqual) => "leq_lhs",
+            Token::Operator(Operator::Greater) => "ge_lhs",
+            Token::Operator(Operator::GreaterOrEqual) => "geq_lhs",
+            Token::Operator(Operator::Ampersand) => "ins_lhs",
+            Token::Operator(Operator::Pipe) => "uni_lhs",
+            _ => Err(Error::Eval("Infix operator is not supported".into()))?,
+        };
+
+        self.call_slot(&lhs, op, Some(rhs.as_ref().unwrap().clone()))?
+            .map(Flow::Continue)
+            .ok_or_else(err)
+    }
+
+    fn eval_if(
+        &mut self,
+        condition: Expression,
+        consequence: Expression,
+        alternative: Option<Expression>,
+        tail: bool,
+    ) -> Result<Reference> {
+        let cond = self.eval(Node::Expression(condition))?;
+        if cond.is_break() {
+            return Ok(cond);
+        }
+
+        if self.truthy(&cond)? {
+            self.tail = tail;
+            return self.eval(Node::Expression(consequence));
+        }
+
+        if let Some(alt) = alternative {
+            self.tail = tail;
+            return self.eval(Node::Expression(alt));
+        }
+
+        Ok(Flow::Continue(Unit::erased()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{check::Checker, lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    #[test]
+    fn test_integer_comp() {
+        let input = r#"
+            5;
+            10;
+            4 * (10 + 2);
+            "#;
+
+        let mut p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap()
+            .statements
+            .into_iter();
+
+        let mut r = Eval::new();
+
+        let mut e = r.eval(Node::Statement(p.next().unwrap()));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "5");
+
+            e = r.eval(Node::Statement(p.next().unwrap()));
+
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "10");
+
+            e = r.eval(Node::Statement(p.next().unwrap()));
+
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "48");
+        }
+
+        let p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        r.clear();
+
+        let e = r.eval(Node::Expression(Expression::Program(p)));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "48");
+        }
+    }
+
+    #[test]
+    fn test_boolean_comp() {
+        let input = r#"
+            true == true;
+            4 < 10;
+            (5 >= 8) == true;
+            false == (3 > 20);
+            "#;
+
+        let mut p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap()
+            .statements
+            .into_iter();
+
+        let mut r = Eval::new();
+
+        let mut e = r.eval(Node::Statement(p.next().unwrap()));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
+
+            e = r.eval(Node::Statement(p.next().unwrap()));
+
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
+
+            e = r.eval(Node::Statement(p.next().unwrap()));
+
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "false");
+
+            e = r.eval(Node::Statement(p.next().unwrap()));
+
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
+        }
+
+        let p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        r.clear();
+
+        let e = r.eval(Node::Expression(Expression::Program(p)));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
+        }
+    }
+
+    #[test]
+    fn test_if() {
+        let input = r#"
+            if 1 > 10 { 5 } else { 10 * 8 };
+            if 4 == 3 { 4 };
+            "#;
+
+        let mut p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap()
+            .statements
+            .into_iter();
+
+        let mut r = Eval::new();
+
+        let mut e = r.eval(Node::Statement(p.next().unwrap()));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "80");
+
+            e = r.eval(Node::Statement(p.next().unwrap()));
+
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "()");
+        }
+
+        let p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        r.clear();
+
+        let e = r.eval(Node::Expression(Expression::Program(p)));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "()");
+        }
+    }
+
+    #[test]
+    fn test_let() {
+        let input = r#"
+            let a = 4;
+            a;
+            "#;
+
+        let mut p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap()
+            .statements
+            .into_iter();
+
+        let mut r = Eval::new();
+
+        let mut e = r.eval(Node::Statement(p.next().unwrap()));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "4");
+
+            e = r.eval(Node::Statement(p.next().unwrap()));
+
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "4");
+        }
+
+        let p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        r.clear();
+
+        let e = r.eval(Node::Expression(Expression::Program(p)));
+
+        unsafe {
+            assert_eq!(format!("{}", e.unwrap_unchecked()), "4");
+        }
+    }
+
+    /// Evaluates the statements of `input` one by one, asserting that each
+    /// succeeds with a value displayed as the corresponding entry of
+    /// `expected`. Entries of `_` match any value, as collections don't display
+    /// their members in a particular order.
+    fn assert_evals(r: &mut Eval, input: &str, expected: &[&str]) {
+        let p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        assert_eq!(p.errors, vec![]);
+
+        let p = r.expand(p).unwrap();
+
+        assert_eq!(p.statements.len(), expected.len(), "{input}");
+
+        for (statement, expected) in p.statements.into_iter().zip(expected) {
+            let value = r.eval(Node::Statement(statement)).unwrap().unwrap();
+            if *expected != "_" {
+                assert_eq!(value.to_string(), *expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_equality() {
+        let input = r#"
+            "a" == "a";
+            "a" != "b";
+            [1, [2, "x"]] == [1, [2, "x"]];
+            [1, 2] == [1, 2, 3];
+            def { a = 1, b = [true] } == def { b = [true], a = 1 };
+            def { a = 1 } != def { a = 2 };
+            let f = fn() {};
+            let g = f;
+            f == g;
+            f == fn() {};
+            1 == "1";
+            [1, "a"] == [1, 1];
+            "#;
+
+        let expected = [
+            "true", "true", "true", "false", "true", "true", "Function", "Function", "true",
+            "false", "false", "false",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let input = r#"
+            "abc" < "abd";
+            "b" >= "abc";
+            [1, 2] < [1, 3];
+            [1, 2] < [1, 2, 0];
+            [2] <= [1, 9];
+            ["b", 1] > ["a", 5];
+            "#;
+
+        let expected = ["true", "true", "true", "true", "false", "true"];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        let p = Parser::new(Lexer::new("[1] < [\"a\"];"))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+    }
+
+    #[test]
+    fn test_index_assign() {
+        let input = r#"
+            let v = [1, 2, 3];
+            v[0] = 10;
+            v[1] += 5;
+            v == [10, 7, 3];
+            let c = def { a = def { b = def { c = 1 } }, items = [1, [2]] };
+            c.a.b.c = 5;
+            c.items[1][0] = 7;
+            c["a"].b.c += 1;
+            c.a == def { b = def { c = 6 } };
+            c.items == [1, [7]];
+            let alias = c.a;
+            alias.b.c = 42;
+            c.a == def { b = def { c = 42 } };
+            c["d"] = "new";
+            c.d;
+            "#;
+
+        let expected = [
+            "[1, 2, 3]",
+            "10",
+            "7",
+            "true",
+            "_",
+            "5",
+            "7",
+            "6",
+            "true",
+            "true",
+            "_",
+            "42",
+            "true",
+            "new",
+            "new",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in ["v[3] = 1;", "v[-4] = 1;", "v.a = 1;", "c.x.y = 1;"] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
+
+        // Writing out of range fails the same way reading does.
+        for (input, expected) in [
+            ("try { v[3] } catch e { e.kind };", "IndexError"),
+            ("try { v[3] = 9 } catch e { e.kind };", "IndexError"),
+            ("try { v.a = 9 } catch e { e.kind };", "TypeError"),
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            let e = r.eval(Node::Expression(Expression::Program(p)));
+            assert_eq!(format!("{}", e.unwrap().unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn test_slicing() {
+        let input = r#"
+            let v = [1, 2, 3, 4, 5];
+            v[-1];
+            v[-5];
+            v[1:3];
+            v[:-1];
+            v[::2];
+            v[3:];
+            v[::-1];
+            v[-2:0:-1];
+            v[10:];
+            v[-1] = 9;
+            v;
+            let s = "héllo";
+            s[1];
+            s[-1];
+            s[1:4];
+            s[::-1];
+            len(s);
+            "hello"[-2:];
+            "#;
+
+        let expected = [
+            "[1, 2, 3, 4, 5]",
+            "5",
+            "1",
+            "[2, 3]",
+            "[1, 2, 3, 4]",
+            "[1, 3, 5]",
+            "[4, 5]",
+            "[5, 4, 3, 2, 1]",
+            "[4, 3, 2]",
+            "[]",
+            "9",
+            "[1, 2, 3, 4, 9]",
+            "héllo",
+            "é",
+            "o",
+            "éll",
+            "olléh",
+            "5",
+            "lo",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in [
+            "v[5];",
+            "v[-6];",
+            "s[5];",
+            "v[::0];",
+            "v[\"a\":];",
+            "1[1:2];",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parameters() {
+        let input = r#"
+            let add = fn(a, b = 2, c = a * 10) { a + b + c };
+            add(1);
+            add(1, 5);
+            add(1, 5, 0);
+            add(1, c: 0);
+            add(c: 3, a: 1);
+            let count = fn(first, ..rest) { len(rest) };
+            count(1);
+            count(1, 2, 3);
+            let tail = fn(..rest) { rest };
+            tail(1, 2);
+            let alias = add;
+            let wrap = fn(f) { let inner = f; inner };
+            let anonymous = wrap(fn(a) { a });
+            "#;
+
+        let expected = [
+            "Function", "13", "16", "6", "3", "6", "Function", "0", "2", "Function", "[1, 2]",
+            "Function", "Function", "Function",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for (input, message) in [
+            (
+                "add();",
+                "Function `add` expects 1 to 3 arguments but received 0.",
+            ),
+            (
+                "add(1, 2, 3, 4);",
+                "Function `add` expects 1 to 3 arguments but received 4.",
+            ),
+            (
+                "count();",
+                "Function `count` expects at least 1 argument but received 0.",
+            ),
+            (
+                "fn(a) { a }();",
+                "Anonymous function expects 1 argument but received 0.",
+            ),
+            (
+                "alias();",
+                "Function `add` expects 1 to 3 arguments but received 0.",
+            ),
+            (
+                "anonymous();",
+                "Anonymous function expects 1 argument but received 0.",
+            ),
+            ("add(1, d: 2);", "Function `add` has no parameter named d."),
+            (
+                "add(1, a: 2);",
+                "Function `add` received argument a more than once.",
+            ),
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            let Err(Error::Eval(e)) = r.eval(Node::Expression(Expression::Program(p))) else {
+                panic!("expected {input} to fail");
+            };
+            assert_eq!(e, message);
+        }
+    }
+
+    #[test]
+    fn test_function_clauses() {
+        let input = r#"
+            let fact = fn fact(0) { 1 } | fact(n) if n > 0 { n * fact(n - 1) };
+            fact(5);
+            let sum = fn sum([]) { 0 } | sum([x, ..xs]) { x + sum(xs) };
+            sum([1, 2, 3]);
+            enum Shape { Circle(r), Rect(w, h) }
+            let area = fn[Shape] area(Shape.Circle(r)) { 3 * r * r } | area(Rect(w, h)) { w * h };
+            area(Shape.Rect(3, 4));
+            let describe = fn describe(n: int) { "int" } | describe(s: str) { "str" } | describe(_) { "other" };
+            describe(1);
+            describe("a");
+            describe(true);
+            let arity = fn arity(a) { 1 } | arity(a, b) { 2 } | arity(..rest) { len(rest) };
+            arity(1, 2);
+            arity(1, 2, 3);
+            let down = fn down(0) { "done" } | down(n) { down(n - 1) };
+            down(5000);
+            match [1, 2, 3] { [a, ..rest] -> rest };
+            "#;
+
+        let expected = [
+            "Function", "120", "Function", "6", "_", "Function", "12", "Function", "int", "str",
+            "other", "Function", "2", "3", "Function", "done", "[2, 3]",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for (input, message) in [
+            (
+                "fact(-1);",
+                "Function `fact` has no clause matching the arguments (-1).",
+            ),
+            (
+                "sum([1], 2);",
+                "Function `sum` has no clause matching the arguments ([1], 2).",
+            ),
+            (
+                "fn(0) { 0 }(1);",
+                "Anonymous function has no clause matching the arguments (1).",
+            ),
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            let Err(Error::Eval(e)) = r.eval(Node::Expression(Expression::Program(p))) else {
+                panic!("expected {input} to fail");
+            };
+            assert_eq!(e, message);
+        }
+
+        // Further clauses have to repeat the function's name.
+        let p = Parser::new(Lexer::new("fn f(0) { 0 } | g(n) { n };"))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+        assert!(matches!(
+            p.errors.as_slice(),
+            [crate::error::Error::FunctionError(_)]
+        ));
+    }
+
+    #[test]
+    fn test_spread() {
+        let input = r#"
+            let a = [1, 2];
+            let b = [4];
+            [..a, 3, ..b];
+            [..a];
+            [.."ab", ..[]];
+            let base = def { x = 1, y = 2 };
+            let point = def { ..base, y = 3, z = 4 };
+            point == def { x = 1, y = 3, z = 4 };
+            base.y;
+            def { ..base, ..def { x = 5 } } == def { x = 5, y = 2 };
+            def { x = 9, ..base }.x;
+            let sum = fn(x, y, z) { x + y + z };
+            sum(..a, 3);
+            sum(..[1, 2, 3]);
+            sum(1, ..b, z: 5);
+            let count = fn(..rest) { len(rest) };
+            count(..a, ..b, ..a);
+            "#;
+
+        let expected = [
+            "[1, 2]",
+            "[4]",
+            "[1, 2, 3, 4]",
+            "[1, 2]",
+            "[a, b]",
+            "_",
+            "_",
+            "true",
+            "2",
+            "true",
+            "1",
+            "Function",
+            "6",
+            "6",
+            "10",
+            "Function",
+            "5",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in ["[..1];", "sum(..base);", "def { ..a };"] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
+    }
+
+    #[test]
+    fn test_comprehensions() {
+        let input = r#"
+            let v = [1, 2, 3, 4];
+            [x * x for x in v];
+            [x for x in v if x > 2];
+            [x + y for x in [10, 20] for y in [1, 2] if x + y != 21];
+            [c + c for c in "ab"];
+            [x for x in []];
+            let x = 7;
+            [x for x in v][0];
+            x;
+            let names = ["a", "b"];
+            let c = def { name: len(name) for name in names + ["cc"] };
+            c.cc;
+            c == def { a = 1, b = 1, cc = 2 };
+            "#;
+
+        let expected = [
+            "[1, 2, 3, 4]",
+            "[1, 4, 9, 16]",
+            "[3, 4]",
+            "[11, 12, 22]",
+            "[aa, bb]",
+            "[]",
+            "7",
+            "1",
+            "7",
+            "[a, b]",
+            "_",
+            "2",
+            "true",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in [
+            "[y for y in v]; y;",
+            "[y for y in 1];",
+            "def { y: y for y in v };",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
+    }
+
+    #[test]
+    fn test_generators() {
+        let input = r#"
+            let gen = fn(a) { yield a; yield a * 2; yield a * 3; };
+            let g = gen(2);
+            next(g);
+            [..g];
+            next(g) == done;
+            [..map(gen(1), fn(x) { x + 1 })];
+            [..filter([1, 2, 3, 4], fn(x) { x > 2 })];
+            let nat = def { from = fn(n) { yield n; [yield x for x in self.from(n + 1)]; } };
+            [..take(nat.from(0), 4)];
+            [..zip("ab", nat.from(1))];
+            [..enumerate(["x", "y"])];
+            let counter = def {
+                n = 0,
+                __next__ = fn() { if self.n < 3 { self.n += 1; self.n } else { done } },
+            };
+            [x * 2 for x in counter];
+            let log = def { calls = 0 };
+            let m = map([1, 2, 3], fn[log](x) { log.calls += 1; x });
+            log.calls;
+            next(m);
+            log.calls;
+            let suspended = gen(5);
+            next(suspended);
+            let me = def { it = 0 };
+            let reentrant = fn[me]() { yield 1; yield next(me.it); };
+            me.it = reentrant();
+            next(me.it);
+            try { next(me.it) } catch e { e.message };
+            next(me.it) == done;
+            "#;
+
+        let expected = [
+            "Function",
+            "Iterator",
+            "2",
+            "[4, 6]",
+            "true",
+            "[2, 3, 4]",
+            "[3, 4]",
+            "_",
+            "[0, 1, 2, 3]",
+            "[[a, 1], [b, 2]]",
+            "[[0, x], [1, y]]",
+            "_",
+            "[2, 4, 6]",
+            "_",
+            "Iterator",
+            "0",
+            "1",
+            "1",
+            "Iterator",
+            "5",
+            "_",
+            "Function",
+            "Iterator",
+            "1",
+            "Iterator is already running.",
+            "true",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in [
+            "let bad = fn() { yield 1; 1 + \"a\"; }(); next(bad); next(bad);",
+            "next(1);",
+            "[..def { a = 1 }];",
+            "take([1], \"a\");",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
+
+        let p = Parser::new(Lexer::new("yield 1;"))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+        assert!(matches!(
+            p.errors.as_slice(),
+            [crate::error::Error::FunctionError(_)]
+        ));
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let input = r#"
+            try { 1 + "a" } catch e { e.kind };
+            try { [1][5] } catch e { e.kind + ": " + e.message };
+            try { missing } catch e { e.kind };
+            try { throw "boom"; } catch e { e.message };
+            try { throw error("ValueError", "bad"); } catch e { e.kind };
+            try { throw 42; } catch e { e.value };
+            let inner = fn() { throw error("X", "deep"); };
+            let outer = fn[inner]() { inner() };
+            try { outer() } catch e { e.trace };
+            let log = def { steps = [] };
+            try {
+                try { throw 1; } finally { log.steps = log.steps + ["finally"]; }
+            } catch {
+                log.steps = log.steps + ["caught"];
+            };
+            log.steps;
+            let f = fn() { try { return 1; } finally { 2 } };
+            f();
+            try { 1 } finally { 2 };
+            let g = fn() { try { throw 1; } catch e { return e.value; }; 5 };
+            g();
+            let x = 1;
+            try { let x = 2; 1 + "a" } catch { x };
+            "#;
+
+        let expected = [
+            "TypeError",
+            "IndexError: Index 5 out of range for length 1.",
+            "NameError",
+            "boom",
+            "ValueError",
+            "42",
+            "Function",
+            "Function",
+            "[outer, inner]",
+            "_",
+            "[finally, caught]",
+            "[finally, caught]",
+            "Function",
+            "1",
+            "1",
+            "Function",
+            "1",
+            "1",
+            "1",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in [
+            "try { throw 1; } finally { 2 };",
+            "try { throw 1; } catch e { 1 }; e;",
+            "try { 1 } finally { throw 2; };",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        let input = r#"
+            let loops = def {
+                down = fn(n) { if n { self.down(n - 1) } else { "done" } },
+                count = fn(n, acc) { if n == 0 { acc } else { self.count(n - 1, acc + 1) } },
+                returned = fn(n) { if n == 0 { return "returned"; }; return self.returned(n - 1); },
+                even = fn(n) { match n { 0 -> true, _ -> self.odd(n - 1) } },
+                odd = fn(n) { match n { 0 -> false, _ -> self.even(n - 1) } },
+                guarded = fn(n) { try { return self.fail(n); } catch e { e.value } },
+                fail = fn(n) { throw n; },
+            };
+            loops.down(10000);
+            loops.count(10000, 0);
+            loops.returned(10000);
+            loops.even(10001);
+            loops.guarded(7);
+            let cnt = fn cnt(n) -> int { if n { cnt(n - 1) } else { 0 } };
+            cnt(20000);
+            let last = fn(n) { if n { "s" } else { 0 } };
+            let outer = fn[last](n) -> int { last(n) };
+            outer(0);
+            try { outer(1) } catch e { e.message };
+            "#;
+
+        let expected = [
+            "_",
+            "done",
+            "10000",
+            "returned",
+            "false",
+            "7",
+            "Function",
+            "0",
+            "Function",
+            "Function",
+            "0",
+            "Function `outer` should return `int` but returned s.",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+    }
+
+    #[test]
+    fn test_deep_tail_calls() {
+        let input = r#"
+            let loops = def {
+                down = fn(n) { if n { self.down(n - 1) } else { "done" } },
+            };
+            loops.down(1000000);
+            "#;
+
+        let expected = ["_", "done"];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+    }
+
+    #[test]
+    fn test_recursion_depth() {
+        let input = r#"
+            let deep = def {
+                sum = fn(n) { if n == 0 { 0 } else { n + self.sum(n - 1) } },
+                down = fn(n) { if n == 0 { "done" } else { self.down(n - 1) } },
+            };
+            deep.sum(5);
+            try { deep.sum(100) } catch e { e.kind };
+            deep.sum(5);
+            deep.down(1000);
+            "#;
+
+        let expected = ["_", "15", "RecursionError", "15", "done"];
+
+        let mut r = Eval::new().with_max_depth(10);
 
-                let op = lhs.v_table().get(op).ok_or(err.clone())?;
+        assert_evals(&mut r, input, &expected);
 
-                op(Some(rhs.unwrap()))
-                    .map(|op| Flow::Continue(op))
-                    .ok_or(err)?
-            }
-            _ => rhs,
-        };
+        // The stack of the test's own thread is known, so evaluation only moves
+        // to a new one once it runs low.
+        #[cfg(target_os = "linux")]
+        assert!(!stack::exhausted());
 
-        self.stack
-            .assign(ident, rhs.as_ref().map(|t| t.clone()).unwrap());
+        // The default limit is reached before the native stack overflows, even
+        // on the small stack of the test's own thread.
+        let input = r#"
+            let deep = def {
+                sum = fn(n) { if n == 0 { 0 } else { n + self.sum(n - 1) } },
+            };
+            deep.sum(999);
+            try { deep.sum(100000) } catch e { e.message };
+            "#;
 
-        Ok(rhs)
+        let expected = ["_", "499500", "Maximum recursion depth of 1000 exceeded."];
+
+        assert_evals(&mut Eval::new(), input, &expected);
     }
 
-    fn eval_infix(
-        &mut self,
-        operator: Token,
-        lhs: Expression,
-        rhs: Expression,
-    ) -> Result<Reference> {
-        if matches!(
-            operator,
-            Token::Operator(Operator::Assign)
-                | Token::Operator(Operator::PlusEqual)
-                | Token::Operator(Operator::MinusEqual)
-        ) {
-            return self.eval_assign(operator, lhs, rhs);
+    #[test]
+    fn test_types() {
+        for (input, mismatches) in [
+            (r#"let x: int = "a";"#, 1),
+            ("let f = fn(a: str) -> int { a }; f(1);", 2),
+            ("let p: { name: str } = def { name = 1 };", 1),
+            ("let q: { name: str } = def { age = 1 };", 1),
+            (r#"let v: [int] = [1, 2]; let w: [str] = v;"#, 1),
+            ("let x: int = 1; x = true;", 1),
+            ("let g: fn(int) -> int = fn(a: str) { a };", 1),
+            (r#"let h = fn() -> int { if true { return "a"; }; 1 };"#, 1),
+            (r#"let never = fn() { if false { 1 + "a" } };"#, 0),
+            (r#"let caught = try { 1 + "a" } catch e { e.kind };"#, 0),
+            (
+                r#"let p = def { n = 1 }; let set = fn(q) { q.n = "s" }; set(p); let s: str = p.n + "x";"#,
+                0,
+            ),
+            (r#"let v = [1]; let s: str = v[0];"#, 0),
+            (r#"let s: str = [1][0];"#, 1),
+            (r#"let x = 1; if true { x = "a" }; let y: int = x;"#, 0),
+            ("let f = fn(a: any, b) -> int { a + b };", 0),
+            (r#"let v: [int] = [1, "a"];"#, 0),
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert_eq!(p.errors, vec![]);
+            assert_eq!(
+                Checker::new().check_program(&p).len(),
+                mismatches,
+                "{input}"
+            );
         }
 
-        if matches!(operator, Token::Operator(Operator::Dot)) {
-            return self.eval_access(operator, lhs, rhs);
-        }
+        // Values of type `any` are checked once they reach an annotation.
+        let input = r#"
+            let id = fn(x) { x };
+            let inc = fn(a: int) -> int { a + 1 };
+            inc(id(1));
+            try { inc(id("a")) } catch e { e.kind };
+            let shout = fn[id](a) -> str { id(a) };
+            shout("hey");
+            try { shout(1) } catch e { e.kind };
+            let name = fn(p: { name: str }) { p.name };
+            name(def { name = "ann", age = 1 });
+            try { name(id(def { age = 1 })) } catch e { e.kind };
+            let xs: [int] = id([1, 2]);
+            try { let ys: [int] = id([1, "a"]); ys } catch e { e.kind };
+            "#;
 
-        let lhs = self.eval(Node::Expression(lhs))?;
-        if lhs.is_break() {
-            return Ok(lhs);
-        }
-        let rhs = self.eval(Node::Expression(rhs))?;
-        if rhs.is_break() {
-            return Ok(rhs);
-        }
-        let err = Error::Eval(format!(
-            "Unsupported operator {:?} for operand types {} and {}",
-            operator, lhs, rhs
-        ));
+        let expected = [
+            "Function",
+            "Function",
+            "2",
+            "TypeError",
+            "Function",
+            "hey",
+            "TypeError",
+            "Function",
+            "ann",
+            "TypeError",
+            "[1, 2]",
+            "TypeError",
+        ];
 
-        let op = match operator {
-            Token::Operator(Operator::Minus) => "sub_lhs",
-            Token::Operator(Operator::MinusEqual) => "sub_lhs",
-            Token::Operator(Operator::Plus) => "add_lhs",
-            Token::Operator(Operator::PlusEqual) => "add_lhs",
-            Token::Operator(Operator::Multiply) => "mul_lhs",
-            Token::Operator(Operator::Divide) => "div_lhs",
-            Token::Operator(Operator::Equal) => "eq_lhs",
-            Token::Operator(Operator::NotEqual) => "neq_lhs",
-            Token::Operator(Operator::Less) => "le_lhs",
-            Token::Operator(Operator::LessOrEqual) => "leq_lhs",
-            Token::Operator(Operator::Greater) => "ge_lhs",
-            Token::Operator(Operator::GreaterOrEqual) => "geq_lhs",
-            Token::Operator(Operator::Ampersand) => "ins_lhs",
-            Token::Operator(Operator::Pipe) => "uni_lhs",
-            _ => Err(Error::Eval("Infix operator is not supported".into()))?,
-        };
+        let p = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        assert_eq!(Checker::new().check_program(&p), vec![]);
 
-        let sub = lhs.v_table().get(op).ok_or(err.clone())?;
+        let mut r = Eval::new();
 
-        sub(Some(rhs.unwrap()))
-            .map(|op| Flow::Continue(op))
-            .ok_or(err)
+        assert_evals(&mut r, input, &expected);
     }
 
-    fn eval_if(
-        &mut self,
-        condition: Expression,
-        consequence: Expression,
-        alternative: Option<Expression>,
-    ) -> Result<Reference> {
-        let cond = self.eval(Node::Expression(condition))?;
-        let err = Error::Eval(format!(
-            "Condition type of {} is not fit for conditions.",
-            cond
-        ));
-        if cond.is_break() {
-            return Ok(cond);
+    #[test]
+    fn test_macros() {
+        let input = r#"
+            let unless = macro(condition, consequence) {
+                quote(if !(unquote(condition)) { unquote(consequence) })
+            };
+            let assert_that = macro(condition) {
+                quote(if !(unquote(condition)) { throw error("AssertionError", "failed"); })
+            };
+            let with_tmp = macro(a, b) { quote({ let tmp = unquote(a); tmp + unquote(b) }) };
+            let twice = macro(x) { quote(unquote(x) + unquote(x)) };
+            let double_unless = macro(x) { quote(unless(false, twice(unquote(x)))) };
+            unless(1 > 2, 10);
+            let tmp = 10;
+            with_tmp(1, tmp);
+            try { assert_that(1 > 2); 0 } catch e { e.kind };
+            assert_that(2 > 1);
+            twice(3);
+            double_unless(4);
+            quote(1 + 2) == quote(1 + 2);
+            let n = 4;
+            quote(unquote(n) + 1) == quote(4 + 1);
+            quote(fn(a) { a }) == quote(fn(a) { a });
+            quote(fn(a) { a }) == quote(fn(b) { a });
+            { let error = fn(kind, message) { 0 }; try { assert_that(1 > 2); 0 } catch e { e.kind } };
+            { let quote = fn(x) { x + 1 }; quote(1) };
+            "#;
+
+        let expected = [
+            "10",
+            "10",
+            "11",
+            "AssertionError",
+            "()",
+            "6",
+            "8",
+            "true",
+            "4",
+            "true",
+            "true",
+            "false",
+            "AssertionError",
+            "2",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in [
+            "unless(true);",
+            "let m = macro(x) { x + 1 }; m(quote(1));",
+            "let f = fn() { 1 }; quote(unquote(f));",
+            "{ let m = macro() { quote(1) }; };",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            let e = r
+                .expand(p)
+                .and_then(|p| r.eval(Node::Expression(Expression::Program(p))));
+            assert!(e.is_err());
         }
-        let cond_fn = cond.v_table().get("truthy").ok_or(err)?;
+    }
 
-        if cond_fn(None).is_some() {
-            return self.eval(Node::Expression(consequence));
+    #[test]
+    fn test_results() {
+        let input = r#"
+            let parse = fn(s) { if s == "1" { ok(1) } else { err("not a number: " + s) } };
+            let add = fn[parse](a, b) { ok(parse(a)? + parse(b)?) };
+            add("1", "1");
+            add("1", "x");
+            let first = fn[parse](s) { parse(s)?; 5 };
+            first("x");
+            is_ok(ok(1));
+            is_ok(err(1));
+            unwrap_or(err(1), 0);
+            unwrap_or(ok(1), 0);
+            map_err(err("x"), fn(e) { e + "!" });
+            map_err(ok(2), fn(e) { e + "!" });
+            match add("x", "1") { Result.Ok(v) -> v, Result.Err(e) -> e };
+            ok(def { x = 3 })?.x;
+            let each = fn[parse](v) { ok([parse(x)? for x in v]) };
+            each(["1", "x"]);
+            let index = fn[parse]() { ok([10, 20][parse("x")?]) };
+            index();
+            let slice = fn[parse]() { ok([10, 20][parse("x")?:]) };
+            slice();
+            let define = fn[parse]() { ok(def { a = parse("x")? }) };
+            define();
+            let guarded = fn[parse](s) if parse(s)? == 1 { ok(s) };
+            guarded("x");
+            let defaulted = fn[parse](s = parse("x")?) { ok(s) };
+            defaulted();
+            "#;
+
+        let expected = [
+            "Function",
+            "Function",
+            "Result.Ok(2)",
+            "Result.Err(not a number: x)",
+            "Function",
+            "Result.Err(not a number: x)",
+            "true",
+            "false",
+            "0",
+            "1",
+            "Result.Err(x!)",
+            "Result.Ok(2)",
+            "not a number: x",
+            "3",
+            "Function",
+            "Result.Err(not a number: x)",
+            "Function",
+            "Result.Err(not a number: x)",
+            "Function",
+            "Result.Err(not a number: x)",
+            "Function",
+            "Result.Err(not a number: x)",
+            "Function",
+            "Result.Err(not a number: x)",
+            "Function",
+            "Result.Err(not a number: x)",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        for input in ["1?;", "is_ok(1);", "unwrap_or(1, 2);"] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(matches!(
+                r.eval(Node::Expression(Expression::Program(p))),
+                Err(Error::Type(_))
+            ));
         }
+    }
 
-        if let Some(alt) = alternative {
-            return self.eval(Node::Expression(alt));
+    /// Writes `files` into a fresh directory under the system's temporary
+    /// directory and returns its path.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("monkey-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
         }
 
-        Ok(Flow::Continue(Unit::erased()))
+        dir
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::{lexer::Lexer, parser::Parser};
+    #[test]
+    fn test_modules() {
+        let dir = write_files(
+            "modules",
+            &[
+                (
+                    "main.mk",
+                    r#"
+                    import "lib/strings.mk" as s;
+                    import "lib/util.mk" as u;
+                    import { greet, shout } from "lib/strings.mk";
+                    "#,
+                ),
+                (
+                    "lib/strings.mk",
+                    r#"
+                    import "util.mk" as util;
+                    let prefix = "hello ";
+                    export let greet = fn[prefix](name) { prefix + name };
+                    export const shout = fn[util](name) { util.exclaim(name) };
+                    export let util = util;
+                    "#,
+                ),
+                (
+                    "lib/util.mk",
+                    r#"
+                    export let state = def { loads = 0 };
+                    state.loads += 1;
+                    export let exclaim = fn(s) { s + "!" };
+                    "#,
+                ),
+                ("cycle/a.mk", r#"import "b.mk" as b;"#),
+                ("cycle/b.mk", r#"import "a.mk" as a;"#),
+            ],
+        );
 
-    use super::*;
+        let mut r = Eval::new();
+        r.run_file(dir.join("main.mk")).unwrap();
 
-    #[test]
-    fn test_integer_comp() {
         let input = r#"
-            5;
-            10;
-            4 * (10 + 2);
+            s.greet("you");
+            s.shout("you");
+            u.state.loads;
+            s.util == u;
+            u.state.loads = 5;
+            s.util.state.loads;
+            greet("me") + shout("!");
+            try { s.prefix } catch { "private" };
+            try { u.exclaim = 1; "mutated" } catch { "frozen" };
+            s.util.exclaim("x");
+            freeze(s);
+            try { s.util.state.loads = 6; "mutated" } catch { "frozen" };
+            u.state.loads;
             "#;
 
-        let mut p = Parser::new(Lexer::new(input))
+        let expected = [
+            "hello you",
+            "you!",
+            "1",
+            "true",
+            "5",
+            "5",
+            "hello me!!",
+            "private",
+            "frozen",
+            "x!",
+            "_",
+            "frozen",
+            "5",
+        ];
+
+        assert_evals(&mut r, input, &expected);
+
+        let e = Eval::new().run_file(dir.join("cycle/a.mk"));
+        assert!(matches!(e, Err(Error::Eval(message)) if message.starts_with("Import cycle")));
+
+        assert!(Eval::new().run_file(dir.join("missing.mk")).is_err());
+
+        let p = Parser::new(Lexer::new(r#"import "nowhere.mk" as n;"#))
             .unwrap()
             .parse_program()
-            .unwrap()
-            .statements
-            .into_iter();
+            .unwrap();
+        assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+
+        let input = format!(
+            r#"import {{ prefix }} from "{}";"#,
+            dir.join("lib/strings.mk").display()
+        );
+        for input in [input.as_str(), "s.prefix;", "s.prefix();"] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+            assert!(matches!(
+                r.eval(Node::Expression(Expression::Program(p))),
+                Err(Error::Name(message)) if message.contains("lib/strings.mk") && message.contains("prefix")
+            ));
+        }
 
-        let mut r = Eval::new();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 
-        let mut e = r.eval(Node::Statement(p.next().unwrap()));
+    #[test]
+    fn test_methods() {
+        let input = r#"
+            let make = fn() { def { field = def { inner = 3 } } };
+            make().field.inner;
+            let counter = def {
+                count = 0,
+                step = 2,
+                bump = fn(by) { self.count += by * self.step; self },
+                get = fn() { self.count },
+            };
+            counter.bump(1).bump(2);
+            counter.get();
+            let early = fn(a) { if a > 1 { return a; }; 0 };
+            early(5) + 1;
+            "#;
 
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "5");
+        let expected = ["Function", "3", "_", "_", "6", "Function", "6"];
 
-            e = r.eval(Node::Statement(p.next().unwrap()));
+        let mut r = Eval::new();
 
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "10");
+        assert_evals(&mut r, input, &expected);
+    }
 
-            e = r.eval(Node::Statement(p.next().unwrap()));
+    #[test]
+    fn test_overloading() {
+        let input = r#"
+            def Money {
+                __add__ = fn(other) { def { cents = self.cents + other.cents } },
+                __eq__ = fn(other) { self.cents == other.cents },
+                __lt__ = fn(other) { self.cents < other.cents },
+                __len__ = fn() { self.cents / 100 },
+                __index__ = fn(i) { self.cents * i },
+                __truthy__ = fn() { self.cents > 0 },
+            }
+            let money = fn[Money](cents) { def < Money { cents = cents } };
+            (money(150) + money(250)).cents;
+            money(1) == money(1);
+            money(1) != money(2);
+            money(1) < money(2);
+            len(money(1250));
+            money(3)[2];
+            if money(0) { "rich" } else { "broke" };
+            [money(1), 2] == [def < Money { cents = 1, note = "x" }, 2];
+            def { m = money(5) } != def { m = def < Money { cents = 5, note = "x" } };
+            "#;
 
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "48");
-        }
+        let expected = [
+            "_", "Function", "400", "true", "true", "true", "12", "6", "broke", "true", "false",
+        ];
 
-        let p = Parser::new(Lexer::new(input))
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        let p = Parser::new(Lexer::new("if quote(1) { 1 };"))
             .unwrap()
             .parse_program()
             .unwrap();
 
-        r.clear();
-
-        let e = r.eval(Node::Expression(Expression::Program(p)));
-
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "48");
-        }
+        assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
     }
 
     #[test]
-    fn test_boolean_comp() {
+    fn test_enums() {
         let input = r#"
-            true == true;
-            4 < 10;
-            (5 >= 8) == true;
-            false == (3 > 20);
+            enum Shape { Circle(r), Rect(w, h), Empty }
+            let area = fn[Shape](shape) {
+                match shape {
+                    Shape.Circle(r) -> 3 * r * r,
+                    Rect(w, h) -> w * h,
+                    Shape.Empty -> 0,
+                }
+            };
+            area(Shape.Circle(2));
+            area(Shape.Rect(3, 4));
+            area(Shape.Empty);
+            Shape.Rect(1, 2) == Shape.Rect(1, 2);
+            Shape.Rect(1, 2) == Shape.Rect(2, 1);
+            Shape.Circle(5);
+            match Shape.Rect(1, -1) {
+                Rect(1, 1) -> "square",
+                Rect(_, -1) -> "negative",
+                _ -> "other",
+            };
+            match Shape.Circle(1) { Empty -> "empty", _ -> "other" };
+            if Shape.Empty { "present" } else { "absent" };
             "#;
 
-        let mut p = Parser::new(Lexer::new(input))
-            .unwrap()
-            .parse_program()
-            .unwrap()
-            .statements
-            .into_iter();
+        let expected = [
+            "_",
+            "Function",
+            "12",
+            "12",
+            "0",
+            "true",
+            "false",
+            "Shape.Circle(5)",
+            "negative",
+            "other",
+            "present",
+        ];
 
         let mut r = Eval::new();
 
-        let mut e = r.eval(Node::Statement(p.next().unwrap()));
+        assert_evals(&mut r, input, &expected);
 
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
+        for input in [
+            "Shape.Rect(1);",
+            "match Shape.Empty { Shape.Circle(r) -> r };",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
 
-            e = r.eval(Node::Statement(p.next().unwrap()));
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
+    }
 
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
+    #[test]
+    fn test_const_and_freeze() {
+        let input = r#"
+            const limit = 10;
+            limit + 1;
+            { let limit = 2; limit };
+            let config = freeze(def { inner = def { values = [1, 2] } });
+            config.inner.values[0];
+            let copy = config | def { extra = 1 };
+            copy.extra = 2;
+            copy.extra;
+            "#;
 
-            e = r.eval(Node::Statement(p.next().unwrap()));
+        let expected = ["10", "11", "2", "_", "1", "_", "2", "2"];
 
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "false");
+        let mut r = Eval::new();
 
-            e = r.eval(Node::Statement(p.next().unwrap()));
+        assert_evals(&mut r, input, &expected);
+
+        for input in [
+            "limit = 1;",
+            "limit += 1;",
+            "let limit = 1;",
+            "{ limit = 1; };",
+            "config.extra = 1;",
+            "config.inner.values[0] = 3;",
+            "config.inner.values = [];",
+            "proto(config, def {});",
+            "let f = fn[limit]() { limit = 2; limit }; f();",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+        }
 
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
+        for (input, expected) in [
+            (
+                "try { config.extra = 1 } catch e { e.message };",
+                "Cannot assign to member `extra` of a frozen collection.",
+            ),
+            (
+                "try { config.inner.values[0] = 3 } catch e { e.message };",
+                "Cannot assign to index 0 of a frozen vector.",
+            ),
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            let e = r.eval(Node::Expression(Expression::Program(p)));
+            assert_eq!(format!("{}", e.unwrap().unwrap()), expected);
         }
 
-        let p = Parser::new(Lexer::new(input))
+        // Assignments to constants are also rejected while parsing.
+        for input in [
+            "const a = 1; a = 2;",
+            "const a = 1; { a += 1; };",
+            "const a = 1; const a = 2;",
+            "const a = 1; let f = fn[a]() { a = 2; };",
+        ] {
+            let p = Parser::new(Lexer::new(input))
+                .unwrap()
+                .parse_program()
+                .unwrap();
+
+            assert!(matches!(
+                p.errors.as_slice(),
+                [crate::error::Error::Const(_)]
+            ));
+        }
+
+        let p = Parser::new(Lexer::new("const a = 1; let f = fn(a) { a = 2; };"))
             .unwrap()
             .parse_program()
             .unwrap();
 
-        r.clear();
-
-        let e = r.eval(Node::Expression(Expression::Program(p)));
-
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "true");
-        }
+        assert_eq!(p.errors, vec![]);
     }
 
     #[test]
-    fn test_if() {
+    fn test_prototypes() {
         let input = r#"
-            if 1 > 10 { 5 } else { 10 * 8 };
-            if 4 == 3 { 4 };
+            def Base {
+                name = "base",
+                greet = fn() { "hello " + self.name },
+                kind = fn() { "base" },
+            }
+            def Child < Base {
+                name = "child",
+                kind = fn() { "child of " + super.kind() },
+            }
+            Child.greet();
+            Child.kind();
+            Child.name = "renamed";
+            Base.name;
+            Child.greet();
+            let other = proto(def { name = "other" }, Child);
+            other.kind();
+            proto(other) == Child;
+            let grand = def < other { kind = fn() { super.kind() + "!" } };
+            grand.kind();
+            def { name = "x" } == def < Base { name = "x" };
+            def < Base { name = "x" } == def < Base { name = "x" };
+            if true { def Base { name = "inner" } Base.name };
+            Base.name;
             "#;
 
-        let mut p = Parser::new(Lexer::new(input))
+        let expected = [
+            "_",
+            "_",
+            "hello child",
+            "child of base",
+            "renamed",
+            "base",
+            "hello renamed",
+            "_",
+            "child of base",
+            "true",
+            "_",
+            "child of base!",
+            "false",
+            "true",
+            "inner",
+            "base",
+        ];
+
+        let mut r = Eval::new();
+
+        assert_evals(&mut r, input, &expected);
+
+        let p = Parser::new(Lexer::new("proto(Base, grand);"))
             .unwrap()
             .parse_program()
-            .unwrap()
-            .statements
-            .into_iter();
+            .unwrap();
 
-        let mut r = Eval::new();
+        assert!(r.eval(Node::Expression(Expression::Program(p))).is_err());
+    }
 
-        let mut e = r.eval(Node::Statement(p.next().unwrap()));
+    #[test]
+    fn test_channels() {
+        let input = r#"
+            let numbers = channel();
+            let squares = channel(2);
+            spawn fn[numbers]() {
+                [numbers <- n for n in [1, 2, 3, 4]];
+                close(numbers);
+            };
+            spawn fn[numbers, squares]() {
+                [squares <- n * n for n in numbers];
+                close(squares);
+            };
+            [n for n in squares];
+            <- squares;
+            let ping = channel();
+            let pong = channel();
+            spawn fn[ping, pong]() { pong <- (<- ping) + 1 };
+            ping <- 1;
+            <- pong;
+            let buffered = channel(1);
+            buffered <- "sent";
+            <- buffered;
+            buffered <- 1;
+            try { buffered <- 2 } catch e { e.kind };
+            let empty = channel();
+            try { <- empty } catch e { e.message };
+            close(empty);
+            try { empty <- 1 } catch e { e.message };
+            let got = def { value = 0 };
+            let ch = channel();
+            let other = channel();
+            spawn fn[ch, got]() { got.value = <- ch };
+            try { <- other } catch e { e.kind };
+            ch <- "v";
+            got.value;
+            "#;
 
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "80");
+        let expected = [
+            "Channel",
+            "Channel",
+            "()",
+            "()",
+            "[1, 4, 9, 16]",
+            "Iteration.Done",
+            "Channel",
+            "Channel",
+            "()",
+            "()",
+            "2",
+            "Channel",
+            "()",
+            "sent",
+            "()",
+            "DeadlockError",
+            "Channel",
+            "Deadlock: every thread is blocked.",
+            "()",
+            "Cannot send to a closed channel.",
+            "_",
+            "Channel",
+            "Channel",
+            "()",
+            "DeadlockError",
+            "()",
+            "v",
+        ];
 
-            e = r.eval(Node::Statement(p.next().unwrap()));
+        let mut r = Eval::new();
 
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "null");
-        }
+        assert_evals(&mut r, input, &expected);
 
+        // Threads still blocked once the program is done deadlock too.
+        let input = "let ch = channel(); spawn fn[ch]() { <- ch };";
         let p = Parser::new(Lexer::new(input))
             .unwrap()
             .parse_program()
             .unwrap();
 
-        r.clear();
-
-        let e = r.eval(Node::Expression(Expression::Program(p)));
-
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "null");
+        for statement in p.statements {
+            r.eval(Node::Statement(statement)).unwrap();
         }
+
+        assert!(matches!(r.run_threads(), Err(Error::Deadlock(_))));
+        assert!(r.run_threads().is_ok());
     }
 
     #[test]
-    fn test_let() {
+    fn test_par_map() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Shareable>();
+        assert_send_sync::<Completer>();
+
+        // Calls to `check` only compile for types that are neither `Send` nor
+        // `Sync`, as the implementations are ambiguous for the others.
+        trait NotSend<A> {
+            fn check() {}
+        }
+        impl<T> NotSend<()> for T {}
+        impl<T: Send> NotSend<u8> for T {}
+
+        trait NotSync<A> {
+            fn check() {}
+        }
+        impl<T> NotSync<()> for T {}
+        impl<T: Sync> NotSync<u8> for T {}
+
+        <Reference as NotSend<_>>::check();
+        <Reference as NotSync<_>>::check();
+        <Eval as NotSend<_>>::check();
+
         let input = r#"
-            let a = 4;
-            a;
+            let fib = fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+            par_map(fib, [10, 15, 1, 0, 20]);
+            par_map(fib, []);
+            let config = def { offset = 100, scale = [2] };
+            let shift = fn[config](x) { x * config.scale[0] + config.offset };
+            try { par_map(shift, [1, 2]) } catch e { e.kind };
+            freeze(config);
+            par_map(shift, [1, 2, 3]);
+            let rows = [[1, 2], [3]];
+            try { par_map(len, rows) } catch e { e.kind };
+            freeze(rows);
+            par_map(len, rows);
+            try { par_map(fn(x) { x.missing }, [1, 2]) } catch e { e.kind };
             "#;
 
-        let mut p = Parser::new(Lexer::new(input))
-            .unwrap()
-            .parse_program()
-            .unwrap()
-            .statements
-            .into_iter();
+        let expected = [
+            "Function",
+            "[55, 610, 1, 0, 6765]",
+            "[]",
+            "_",
+            "Function",
+            "TypeError",
+            "_",
+            "[102, 104, 106]",
+            "[[1, 2], [3]]",
+            "TypeError",
+            "[[1, 2], [3]]",
+            "[2, 1]",
+            "Error",
+        ];
 
         let mut r = Eval::new();
 
-        let mut e = r.eval(Node::Statement(p.next().unwrap()));
-
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "4");
+        assert_evals(&mut r, input, &expected);
+    }
 
-            e = r.eval(Node::Statement(p.next().unwrap()));
+    #[test]
+    fn test_async() {
+        let input = r#"
+            let fetch = async fn(name, ms) { await sleep(ms); name + " done" };
+            let a = fetch("a", 300);
+            let b = fetch("b", 100);
+            await b;
+            await a;
+            let slow = fetch("slow", 1000);
+            try { await timeout(slow, 200) } catch e { e.kind };
+            await timeout(fetch("fast", 10), 200);
+            await slow;
+            let failing = async fn() { throw error("Boom", "no") };
+            try { await failing() } catch e { e.kind };
+            let typed = async fn() -> int { "one" };
+            try { await typed() } catch e { e.kind };
+            try { await 1 } catch e { e.kind };
+            let host = [lookup("x", 1), lookup("y", 2)];
+            [await value for value in host];
+            try { await lookup("z", 3) } catch e { e.message };
+            "#;
 
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "4");
-        }
+        let expected = [
+            ("Function", 0),
+            ("Future", 0),
+            ("Future", 0),
+            ("b done", 100),
+            ("a done", 300),
+            ("Future", 300),
+            ("TimeoutError", 500),
+            ("fast done", 510),
+            ("slow done", 1300),
+            ("Function", 1300),
+            ("Boom", 1300),
+            ("Function", 1300),
+            ("TypeError", 1300),
+            ("TypeError", 1300),
+            ("[Future, Future]", 1300),
+            ("[x: 1, y: 2]", 1300),
+            (
+                "The host dropped the operation without completing it.",
+                1300,
+            ),
+        ];
 
         let p = Parser::new(Lexer::new(input))
             .unwrap()
             .parse_program()
             .unwrap();
 
-        r.clear();
+        assert_eq!(p.errors, vec![]);
 
-        let e = r.eval(Node::Expression(Expression::Program(p)));
+        let mut r = Eval::new().with_virtual_clock();
 
-        unsafe {
-            assert_eq!(format!("{}", e.unwrap_unchecked()), "4");
+        // Completes on a thread of its own, except for `z`, which it drops.
+        r.define(
+            "lookup",
+            Builtin::erased(|eval, args| {
+                let (future, completer) = eval.pending();
+                let key = args[0].to_string();
+                let value = args[1].to_string();
+
+                std::thread::spawn(move || {
+                    if key != "z" {
+                        let value = Str::erased(format!("{key}: {value}"));
+                        completer.complete(Ok(value.share().unwrap()));
+                    }
+                });
+
+                Ok(Flow::Continue(future))
+            }),
+        );
+
+        assert_eq!(p.statements.len(), expected.len());
+
+        for (statement, (expected, now)) in p.statements.into_iter().zip(expected) {
+            let e = r.eval(Node::Statement(statement));
+            assert_eq!(format!("{}", e.unwrap().unwrap()), expected);
+            assert_eq!(r.now(), now);
         }
     }
 }
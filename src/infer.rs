@@ -0,0 +1,1365 @@
+//! Hindley–Milner type inference over programs without annotations, used to
+//! lint them.
+//!
+//! Every expression gets a type, with type variables for the parts that are
+//! only known once unified with the types of other expressions. Functions and
+//! collections bound with `let` are generalized over the variables left, so
+//! that they can be used at different types. Collections get row types, which
+//! also fit collections with more members than the ones used.
+//!
+//! The language is dynamic, so a failed unification only means the program
+//! likely fails, and is reported as a warning.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    ast::{
+        Catch, Clause, Expression, FunctionClause, ImportStatement, Imported, LetStatement,
+        Literal, Member, Pattern, Program, ReturnStatement, Statement, ThrowStatement, Type,
+    },
+    token::{Operator, Token},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ty {
+    Var(usize),
+    Int,
+    Bool,
+    Str,
+    Unit,
+    Vector(Box<Ty>),
+    Function(Vec<Ty>, Box<Ty>),
+    /// A collection with the given members, and any others if it has a row
+    /// variable.
+    Record(BTreeMap<String, Ty>, Option<usize>),
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Variables are renumbered by `normalize`, so few of them need
+            // more than a letter.
+            Ty::Var(var) => match u8::try_from(*var) {
+                Ok(var @ 0..=25) => write!(f, "'{}", (b'a' + var) as char),
+                _ => write!(f, "'t{var}"),
+            },
+            Ty::Int => write!(f, "int"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Str => write!(f, "str"),
+            Ty::Unit => write!(f, "unit"),
+            Ty::Vector(element) => write!(f, "[{element}]"),
+            Ty::Function(parameters, returns) => {
+                let parameters = parameters
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                write!(f, "fn({}) -> {returns}", parameters.join(", "))
+            }
+            Ty::Record(members, rest) => {
+                let mut members = members
+                    .iter()
+                    .map(|(name, ty)| format!("{name}: {ty}"))
+                    .collect::<Vec<_>>();
+                if rest.is_some() {
+                    members.push("..".into());
+                }
+                write!(f, "{{ {} }}", members.join(", "))
+            }
+        }
+    }
+}
+
+/// A type generalized over `vars`, which are replaced by fresh variables
+/// wherever the binding is used.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Ty,
+}
+
+impl Scheme {
+    fn mono(ty: Ty) -> Self {
+        Self { vars: vec![], ty }
+    }
+}
+
+/// What inferring the types of a program found.
+#[derive(Debug, Default)]
+pub struct Inference {
+    /// Likely type errors, with the types that didn't unify.
+    pub warnings: Vec<String>,
+    /// The types of function parameters and collection members by their path,
+    /// e.g. `add.a` for parameter `a` of the function bound to `add`.
+    pub types: Vec<(String, Ty)>,
+}
+
+/// Failed to unify two types.
+struct Mismatch;
+
+pub struct Inferrer {
+    /// What each type variable was unified with, if anything yet.
+    bindings: Vec<Option<Ty>>,
+    scopes: Vec<HashMap<String, Scheme>>,
+    builtins: HashMap<String, Scheme>,
+    /// The type of the results of the function being inferred.
+    returns: Option<Ty>,
+    /// The type of `self` for the method being inferred next.
+    receiver: Option<Ty>,
+    /// The path of the binding the expression inferred next is bound to.
+    name: Option<String>,
+    /// The path of the function or collection being inferred.
+    prefix: String,
+    /// The path of the binding whose value is being inferred, which warnings
+    /// are about.
+    binding: String,
+    /// Whether the value of the expression inferred next is unused, in which
+    /// case its branches may have different types.
+    discarded: bool,
+    types: Vec<(String, Ty)>,
+    warnings: Vec<String>,
+}
+
+impl Inferrer {
+    pub fn new() -> Self {
+        let mut inferrer = Self {
+            bindings: vec![],
+            scopes: vec![HashMap::new()],
+            builtins: HashMap::new(),
+            returns: None,
+            receiver: None,
+            name: None,
+            prefix: String::new(),
+            binding: String::new(),
+            discarded: false,
+            types: vec![],
+            warnings: vec![],
+        };
+
+        let a = inferrer.fresh_var();
+        let builtins = [
+            ("len", Ty::Function(vec![Ty::Var(a)], Box::new(Ty::Int))),
+            ("print", Ty::Function(vec![Ty::Var(a)], Box::new(Ty::Unit))),
+            (
+                "freeze",
+                Ty::Function(vec![Ty::Var(a)], Box::new(Ty::Var(a))),
+            ),
+        ];
+
+        inferrer.builtins = builtins
+            .into_iter()
+            .map(|(name, ty)| (name.to_string(), Scheme { vars: vec![a], ty }))
+            .collect();
+
+        inferrer
+    }
+
+    /// Infers the types of `program`, keeping its top-level bindings for the
+    /// programs inferred after it.
+    pub fn infer_program(&mut self, program: &Program) -> Inference {
+        for statement in &program.statements {
+            self.discarded = true;
+            self.infer_statement(statement);
+        }
+
+        let types = std::mem::take(&mut self.types)
+            .into_iter()
+            .map(|(path, ty)| {
+                let ty = self.resolve(&ty);
+                (path, normalize(&[ty]).remove(0))
+            })
+            .collect();
+
+        Inference {
+            warnings: std::mem::take(&mut self.warnings),
+            types,
+        }
+    }
+
+    fn fresh_var(&mut self) -> usize {
+        self.bindings.push(None);
+        self.bindings.len() - 1
+    }
+
+    fn fresh(&mut self) -> Ty {
+        Ty::Var(self.fresh_var())
+    }
+
+    fn bind(&mut self, name: &str, scheme: Scheme) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), scheme);
+    }
+
+    fn lookup(&mut self, name: &str) -> Ty {
+        let scheme = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.builtins.get(name))
+            .cloned();
+
+        match scheme {
+            Some(scheme) => self.instantiate(&scheme),
+            // Unknown names are left to the evaluator to report.
+            None => self.fresh(),
+        }
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{name}", self.prefix)
+        }
+    }
+
+    fn warn(&mut self, message: String) {
+        if self.binding.is_empty() {
+            self.warnings.push(message);
+        } else {
+            self.warnings
+                .push(format!("In `{}`: {message}", self.binding));
+        }
+    }
+
+    /// Unifies `found` with `expected`, warning about `context` if they don't
+    /// unify.
+    fn expect(&mut self, context: impl FnOnce() -> String, expected: &Ty, found: &Ty) {
+        if self.unify(expected, found).is_err() {
+            let types = normalize(&[self.resolve(expected), self.resolve(found)]);
+            let message = format!(
+                "{} should be `{}` but is `{}`.",
+                context(),
+                types[0],
+                types[1]
+            );
+            self.warn(message);
+        }
+    }
+
+    /// Follows the bindings of `ty` and of its row variable, if any.
+    fn prune(&self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Var(var) => match &self.bindings[*var] {
+                Some(bound) => self.prune(bound),
+                None => ty.clone(),
+            },
+            Ty::Record(members, Some(rest)) => match &self.bindings[*rest] {
+                Some(bound) => match self.prune(bound) {
+                    Ty::Record(more, rest) => {
+                        let mut members = members.clone();
+                        members.extend(more);
+                        Ty::Record(members, rest)
+                    }
+                    _ => Ty::Record(members.clone(), None),
+                },
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    /// Replaces every bound variable in `ty` by what it is bound to.
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match self.prune(ty) {
+            Ty::Vector(element) => Ty::Vector(Box::new(self.resolve(&element))),
+            Ty::Function(parameters, returns) => Ty::Function(
+                parameters.iter().map(|ty| self.resolve(ty)).collect(),
+                Box::new(self.resolve(&returns)),
+            ),
+            Ty::Record(members, rest) => Ty::Record(
+                members
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), self.resolve(ty)))
+                    .collect(),
+                rest,
+            ),
+            ty => ty,
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &Ty) -> bool {
+        let mut vars = HashSet::new();
+        free_vars(&self.resolve(ty), &mut vars);
+        vars.contains(&var)
+    }
+
+    fn bind_var(&mut self, var: usize, ty: Ty) -> Result<(), Mismatch> {
+        if self.occurs(var, &ty) {
+            return Err(Mismatch);
+        }
+
+        self.bindings[var] = Some(ty);
+        Ok(())
+    }
+
+    fn unify(&mut self, a: &Ty, b: &Ty) -> Result<(), Mismatch> {
+        match (self.prune(a), self.prune(b)) {
+            (Ty::Var(a), Ty::Var(b)) if a == b => Ok(()),
+            (Ty::Var(var), ty) | (ty, Ty::Var(var)) => self.bind_var(var, ty),
+            (Ty::Int, Ty::Int)
+            | (Ty::Bool, Ty::Bool)
+            | (Ty::Str, Ty::Str)
+            | (Ty::Unit, Ty::Unit) => Ok(()),
+            (Ty::Vector(a), Ty::Vector(b)) => self.unify(&a, &b),
+            (Ty::Function(a, a_returns), Ty::Function(b, b_returns)) => {
+                if a.len() != b.len() {
+                    return Err(Mismatch);
+                }
+
+                for (a, b) in a.iter().zip(&b) {
+                    self.unify(a, b)?;
+                }
+
+                self.unify(&a_returns, &b_returns)
+            }
+            (Ty::Record(a, a_rest), Ty::Record(b, b_rest)) => self.unify_rows(a, a_rest, b, b_rest),
+            _ => Err(Mismatch),
+        }
+    }
+
+    fn unify_rows(
+        &mut self,
+        a: BTreeMap<String, Ty>,
+        a_rest: Option<usize>,
+        b: BTreeMap<String, Ty>,
+        b_rest: Option<usize>,
+    ) -> Result<(), Mismatch> {
+        for (name, ty) in &a {
+            if let Some(other) = b.get(name) {
+                self.unify(ty, other)?;
+            }
+        }
+
+        // The members only one side has must be in the rest of the other.
+        let only = |of: &BTreeMap<String, Ty>, without: &BTreeMap<String, Ty>| {
+            of.iter()
+                .filter(|(name, _)| !without.contains_key(*name))
+                .map(|(name, ty)| (name.clone(), ty.clone()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let only_a = only(&a, &b);
+        let only_b = only(&b, &a);
+
+        match (a_rest, b_rest) {
+            (None, None) if only_a.is_empty() && only_b.is_empty() => Ok(()),
+            (Some(a_rest), None) if only_a.is_empty() => {
+                self.bind_var(a_rest, Ty::Record(only_b, None))
+            }
+            (None, Some(b_rest)) if only_b.is_empty() => {
+                self.bind_var(b_rest, Ty::Record(only_a, None))
+            }
+            (Some(a_rest), Some(b_rest)) if a_rest == b_rest => {
+                if only_a.is_empty() && only_b.is_empty() {
+                    Ok(())
+                } else {
+                    Err(Mismatch)
+                }
+            }
+            (Some(a_rest), Some(b_rest)) => {
+                let rest = self.fresh_var();
+                self.bind_var(a_rest, Ty::Record(only_b, Some(rest)))?;
+                self.bind_var(b_rest, Ty::Record(only_a, Some(rest)))
+            }
+            _ => Err(Mismatch),
+        }
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let fresh = scheme
+            .vars
+            .iter()
+            .map(|var| (*var, self.fresh_var()))
+            .collect::<HashMap<_, _>>();
+
+        rename(&self.resolve(&scheme.ty), &fresh)
+    }
+
+    /// Generalizes `ty` over the variables not free in any binding in scope.
+    fn generalize(&self, ty: &Ty) -> Scheme {
+        let ty = self.resolve(ty);
+
+        let mut bound = HashSet::new();
+        for scheme in self.scopes.iter().flat_map(HashMap::values) {
+            let mut vars = HashSet::new();
+            free_vars(&self.resolve(&scheme.ty), &mut vars);
+            bound.extend(vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+        }
+
+        let mut vars = HashSet::new();
+        free_vars(&ty, &mut vars);
+        let mut vars = vars
+            .into_iter()
+            .filter(|var| !bound.contains(var))
+            .collect::<Vec<_>>();
+        vars.sort();
+
+        Scheme { vars, ty }
+    }
+
+    fn annotated(&mut self, annotation: &Type) -> Ty {
+        match annotation {
+            Type::Any => self.fresh(),
+            Type::Int => Ty::Int,
+            Type::Bool => Ty::Bool,
+            Type::Str => Ty::Str,
+            Type::Unit => Ty::Unit,
+            Type::Vector(element) => Ty::Vector(Box::new(self.annotated(element))),
+            Type::Record(members) => {
+                let members = members
+                    .iter()
+                    .map(|(name, ty)| (name.name.clone(), self.annotated(ty)))
+                    .collect();
+                Ty::Record(members, Some(self.fresh_var()))
+            }
+            Type::Function {
+                parameters,
+                returns,
+            } => Ty::Function(
+                parameters.iter().map(|ty| self.annotated(ty)).collect(),
+                Box::new(self.annotated(returns)),
+            ),
+        }
+    }
+
+    /// Infers `statements`, giving the type of the last one, which is only
+    /// used if `self.discarded` isn't set.
+    fn infer_statements(&mut self, statements: &[Statement]) -> Ty {
+        let discarded = self.discarded;
+        let mut ty = Ty::Unit;
+
+        for (i, statement) in statements.iter().enumerate() {
+            self.discarded = discarded || i + 1 < statements.len();
+            ty = self.infer_statement(statement);
+        }
+
+        ty
+    }
+
+    fn infer_statement(&mut self, statement: &Statement) -> Ty {
+        match statement {
+            Statement::Let(LetStatement {
+                name,
+                annotation,
+                value,
+            })
+            | Statement::Const(LetStatement {
+                name,
+                annotation,
+                value,
+            }) => {
+                self.discarded = false;
+                let ty = self.infer_bound(&name.name, value);
+
+                if let Some(annotation) = annotation {
+                    let annotation = self.annotated(annotation);
+                    self.expect(|| format!("`{}`", name.name), &annotation, &ty);
+                }
+
+                // Only values are generalized, as the types of mutable
+                // bindings could otherwise change between uses.
+                let scheme = match value {
+                    Expression::Literal(Literal::Function { .. } | Literal::Collection { .. }) => {
+                        self.generalize(&ty)
+                    }
+                    _ => Scheme::mono(ty.clone()),
+                };
+                self.bind(&name.name, scheme);
+
+                ty
+            }
+            Statement::Return(ReturnStatement { value }) => {
+                self.discarded = false;
+                let ty = self.infer(value);
+
+                if let Some(returns) = self.returns.clone() {
+                    self.expect(|| "Returned value".into(), &returns, &ty);
+                }
+
+                self.fresh()
+            }
+            Statement::Throw(ThrowStatement { value }) => {
+                self.discarded = false;
+                self.infer(value);
+                self.fresh()
+            }
+            Statement::Enum(statement) => {
+                let ty = self.fresh();
+                self.bind(&statement.name.name, Scheme::mono(ty.clone()));
+                ty
+            }
+            Statement::Import(ImportStatement { imported, .. }) => {
+                let names = match imported {
+                    Imported::Module(alias) => vec![alias],
+                    Imported::Names(names) => names.iter().collect(),
+                };
+
+                for name in names {
+                    let ty = self.fresh();
+                    self.bind(&name.name, Scheme::mono(ty));
+                }
+
+                Ty::Unit
+            }
+            Statement::Export(statement) => self.infer_statement(statement),
+            Statement::Expression(expression) => self.infer(expression),
+            Statement::Empty => Ty::Unit,
+        }
+    }
+
+    /// Infers `value` bound to `name`, recording the types of its parameters
+    /// or members under the name.
+    fn infer_bound(&mut self, name: &str, value: &Expression) -> Ty {
+        let path = self.qualify(name);
+        let binding = std::mem::replace(&mut self.binding, path.clone());
+        self.name = Some(path);
+        let ty = self.infer(value);
+        self.name = None;
+        self.binding = binding;
+        ty
+    }
+
+    /// Adds the members of the collection `value` to `types`, giving whether
+    /// it may have members beyond the known ones.
+    fn inherit(&mut self, types: &mut BTreeMap<String, Ty>, value: &Expression) -> bool {
+        let ty = self.infer(value);
+        match self.resolve(&ty) {
+            Ty::Record(members, rest) => {
+                types.extend(members);
+                rest.is_some()
+            }
+            _ => true,
+        }
+    }
+
+    fn infer(&mut self, expression: &Expression) -> Ty {
+        let name = self.name.take();
+        let discarded = std::mem::take(&mut self.discarded);
+
+        match expression {
+            Expression::Program(Program { statements, .. }) => {
+                self.discarded = discarded;
+                self.infer_statements(statements)
+            }
+            Expression::Block { statements } => {
+                self.scopes.push(HashMap::new());
+                self.discarded = discarded;
+                let ty = self.infer_statements(statements);
+                self.scopes.pop();
+                ty
+            }
+            Expression::Ident(ident) => self.lookup(&ident.name),
+            Expression::Literal(literal) => self.infer_literal(literal, name),
+            Expression::Infix { operator, lhs, rhs } => self.infer_infix(operator, lhs, rhs),
+            Expression::Prefix { operator, operand } => {
+                let operand = self.infer(operand);
+                match operator {
+                    Token::Operator(Operator::Bang) => Ty::Bool,
+                    // Channels aren't typed, so neither is what they receive.
+                    Token::Operator(Operator::LeftArrow) => self.fresh(),
+                    _ => operand,
+                }
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.infer(condition);
+                self.discarded = discarded;
+                let consequence = self.infer(consequence);
+
+                match alternative {
+                    Some(alternative) => {
+                        self.discarded = discarded;
+                        let alternative = self.infer(alternative);
+                        if !discarded {
+                            self.expect(|| "The `else` branch".into(), &consequence, &alternative);
+                        }
+                        consequence
+                    }
+                    None => self.fresh(),
+                }
+            }
+            Expression::Invoked { invoked, args } => self.infer_invoke(invoked, args),
+            Expression::Indexed { indexee, index } => {
+                let indexee = self.infer(indexee);
+                let index = self.infer(index);
+
+                match (self.prune(&indexee), self.prune(&index)) {
+                    (Ty::Str, _) => Ty::Str,
+                    (Ty::Vector(_), Ty::Vector(_)) => indexee,
+                    (Ty::Vector(element), _) => {
+                        self.expect(|| "The index".into(), &Ty::Int, &index);
+                        *element
+                    }
+                    _ => self.fresh(),
+                }
+            }
+            Expression::Sliced {
+                sliced,
+                start,
+                end,
+                step,
+            } => {
+                for bound in [start, end, step].into_iter().flatten() {
+                    let bound = self.infer(bound);
+                    self.expect(|| "The slice bound".into(), &Ty::Int, &bound);
+                }
+
+                let sliced = self.infer(sliced);
+                match self.prune(&sliced) {
+                    Ty::Vector(_) | Ty::Str => sliced,
+                    _ => self.fresh(),
+                }
+            }
+            Expression::Match { subject, arms } => {
+                let subject = self.infer(subject);
+                let ty = self.fresh();
+
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    self.bind_pattern(&arm.pattern, &subject);
+                    self.discarded = discarded;
+                    let body = self.infer(&arm.body);
+                    if !discarded {
+                        self.expect(|| "The `match` arm".into(), &ty, &body);
+                    }
+                    self.scopes.pop();
+                }
+
+                ty
+            }
+            Expression::Comprehension {
+                key,
+                element,
+                clauses,
+            } => {
+                self.scopes.push(HashMap::new());
+
+                for clause in clauses {
+                    match clause {
+                        Clause::For { binding, iterable } => {
+                            let iterable = self.infer(iterable);
+                            let element = match self.prune(&iterable) {
+                                Ty::Vector(element) => *element,
+                                _ => self.fresh(),
+                            };
+                            self.bind(&binding.name, Scheme::mono(element));
+                        }
+                        Clause::If(condition) => {
+                            self.infer(condition);
+                        }
+                    }
+                }
+
+                if let Some(key) = key {
+                    let key = self.infer(key);
+                    self.expect(|| "The key of the comprehension".into(), &Ty::Str, &key);
+                }
+                let element = self.infer(element);
+
+                self.scopes.pop();
+
+                match key {
+                    Some(_) => Ty::Record(BTreeMap::new(), Some(self.fresh_var())),
+                    None => Ty::Vector(Box::new(element)),
+                }
+            }
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.discarded = discarded;
+                let ty = self.infer(body);
+
+                if let Some(Catch { binding, body }) = catch {
+                    self.scopes.push(HashMap::new());
+                    if let Some(binding) = binding {
+                        let error = self.fresh();
+                        self.bind(&binding.name, Scheme::mono(error));
+                    }
+                    self.discarded = discarded;
+                    let caught = self.infer(body);
+                    if !discarded {
+                        self.expect(|| "The `catch` branch".into(), &ty, &caught);
+                    }
+                    self.scopes.pop();
+                }
+
+                if let Some(finally) = finally {
+                    self.discarded = true;
+                    self.infer(finally);
+                }
+
+                ty
+            }
+            Expression::Propagate(value) | Expression::Yield(value) | Expression::Spread(value) => {
+                self.infer(value);
+                self.fresh()
+            }
+            Expression::Spawn(function) => {
+                self.infer(function);
+                Ty::Unit
+            }
+            Expression::Await(future) => {
+                self.infer(future);
+                self.fresh()
+            }
+            Expression::Named { value, .. } => self.infer(value),
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, subject: &Ty) {
+        match pattern {
+            Pattern::Binding(name) => self.bind(&name.name, Scheme::mono(subject.clone())),
+            Pattern::Literal(literal) => {
+                let ty = self.infer_literal(literal, None);
+                self.expect(|| "The matched value".into(), &ty, subject);
+            }
+            Pattern::Vector { elements, rest } => {
+                let element = self.fresh();
+                let vector = Ty::Vector(Box::new(element.clone()));
+                self.expect(|| "The matched value".into(), &vector, subject);
+
+                for pattern in elements {
+                    self.bind_pattern(pattern, &element);
+                }
+                if let Some(rest) = rest {
+                    self.bind(&rest.name, Scheme::mono(vector));
+                }
+            }
+            Pattern::Variant { fields, .. } => {
+                for field in fields {
+                    let ty = self.fresh();
+                    self.bind_pattern(field, &ty);
+                }
+            }
+            Pattern::Wildcard => {}
+        }
+    }
+
+    fn infer_literal(&mut self, literal: &Literal, name: Option<String>) -> Ty {
+        match literal {
+            Literal::Int(_) => Ty::Int,
+            Literal::String(_) => Ty::Str,
+            Literal::Bool(_) => Ty::Bool,
+            Literal::Vector { elements } => {
+                let ty = self.fresh();
+
+                for element in elements {
+                    let found = self.infer(element);
+
+                    if matches!(element, Expression::Spread(_)) {
+                        continue;
+                    }
+
+                    self.expect(|| "The vector element".into(), &ty, &found);
+                }
+
+                Ty::Vector(Box::new(ty))
+            }
+            Literal::Collection { members, parent } => {
+                let name = name.unwrap_or_else(|| "<anonymous>".into());
+                let prefix = std::mem::replace(&mut self.prefix, name);
+
+                // Methods see the collection as `self`. Only the members that
+                // aren't methods are unified with it, as the type of methods
+                // returning `self` would have to contain itself otherwise.
+                let receiver = Ty::Record(BTreeMap::new(), Some(self.fresh_var()));
+                let mut fields = BTreeMap::new();
+
+                let mut named = members
+                    .iter()
+                    .filter_map(|member| match member {
+                        Member::Named(name, value) => Some((name, value)),
+                        Member::Spread(_) => None,
+                    })
+                    .collect::<Vec<_>>();
+                named.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+                let mut named_types = HashMap::new();
+                for (member, value) in named {
+                    let method = matches!(value, Expression::Literal(Literal::Function { .. }));
+                    if method {
+                        self.receiver = Some(receiver.clone());
+                    }
+
+                    let ty = self.infer_bound(&member.name, value);
+                    self.receiver = None;
+
+                    if !method {
+                        fields.insert(member.name.clone(), ty.clone());
+                    }
+
+                    self.types.push((self.qualify(&member.name), ty.clone()));
+                    named_types.insert(&member.name, ty);
+                }
+
+                let fields = Ty::Record(fields, Some(self.fresh_var()));
+                self.expect(|| "`self`".into(), &fields, &receiver);
+
+                self.prefix = prefix;
+
+                // Members are added in order after the inherited ones, so
+                // later members replace earlier ones.
+                let mut types = BTreeMap::new();
+                let mut open = false;
+                if let Some(parent) = parent {
+                    open |= self.inherit(&mut types, parent);
+                }
+                for member in members {
+                    match member {
+                        Member::Named(name, _) => {
+                            types.insert(name.name.clone(), named_types[&name.name].clone());
+                        }
+                        Member::Spread(value) => open |= self.inherit(&mut types, value),
+                    }
+                }
+
+                Ty::Record(types, open.then(|| self.fresh_var()))
+            }
+            Literal::Function {
+                name: own,
+                clauses,
+                capture,
+            } => {
+                let name = name
+                    .or_else(|| own.as_ref().map(|own| own.name.clone()))
+                    .unwrap_or_else(|| "<anonymous>".into());
+
+                // Function bodies only see their parameters, captures and
+                // name, which refers to the function itself.
+                let mut scope = capture
+                    .iter()
+                    .map(|ident| {
+                        let scheme = self
+                            .scopes
+                            .iter()
+                            .rev()
+                            .find_map(|scope| scope.get(&ident.name))
+                            .cloned()
+                            .unwrap_or_else(|| Scheme::mono(self.fresh()));
+                        (ident.name.clone(), scheme)
+                    })
+                    .collect::<HashMap<_, _>>();
+
+                let function = self.fresh();
+                if let Some(own) = own {
+                    scope.insert(own.name.clone(), Scheme::mono(function.clone()));
+                }
+
+                if let Some(receiver) = self.receiver.take() {
+                    scope.insert("self".into(), Scheme::mono(receiver));
+                }
+
+                let types = clauses
+                    .iter()
+                    .map(|clause| self.infer_clause(&name, clause, scope.clone()))
+                    .collect::<Vec<_>>();
+
+                // Clauses taking different numbers of arguments don't share a
+                // type.
+                let arity = |ty: &Ty| match ty {
+                    Ty::Function(parameters, _) => parameters.len(),
+                    _ => 0,
+                };
+                if types.iter().all(|ty| arity(ty) == arity(&types[0])) {
+                    for (i, ty) in types.iter().enumerate() {
+                        self.expect(|| format!("Clause {} of `{name}`", i + 1), &function, ty);
+                    }
+                }
+
+                function
+            }
+            Literal::Macro { .. } => self.fresh(),
+        }
+    }
+
+    /// Infers the type of a clause of the function bound to `name`, whose body
+    /// sees `scope`.
+    fn infer_clause(
+        &mut self,
+        name: &str,
+        clause: &FunctionClause,
+        scope: HashMap<String, Scheme>,
+    ) -> Ty {
+        let FunctionClause {
+            parameters,
+            rest,
+            returns,
+            guard,
+            generator,
+            r#async,
+            body,
+        } = clause;
+
+        let scopes = std::mem::replace(&mut self.scopes, vec![scope]);
+
+        let mut types = vec![];
+        for parameter in parameters {
+            let ty = match &parameter.annotation {
+                Some(annotation) => self.annotated(annotation),
+                None => self.fresh(),
+            };
+
+            if let Some(ident) = parameter.ident() {
+                self.types
+                    .push((format!("{name}.{}", ident.name), ty.clone()));
+            }
+            self.bind_pattern(&parameter.pattern, &ty);
+            types.push(ty);
+        }
+
+        if let Some(rest) = rest {
+            let ty = Ty::Vector(Box::new(self.fresh()));
+            self.bind(&rest.name, Scheme::mono(ty));
+        }
+
+        let returned = match returns {
+            Some(returns) => self.annotated(returns),
+            None => self.fresh(),
+        };
+
+        let outer = self.returns.replace(returned.clone());
+        let prefix = std::mem::replace(&mut self.prefix, name.to_string());
+
+        for (parameter, ty) in parameters.iter().zip(&types) {
+            if let Some(default) = &parameter.default {
+                let default = self.infer(default);
+                self.expect(|| "The default of the parameter".into(), ty, &default);
+            }
+        }
+
+        if let Some(guard) = guard {
+            self.infer(guard);
+        }
+
+        let body = self.infer(body);
+
+        self.scopes = scopes;
+        self.returns = outer;
+        self.prefix = prefix;
+
+        // Generators return an iterator over what they yield.
+        if *generator {
+            return Ty::Function(types, Box::new(self.fresh()));
+        }
+
+        self.expect(|| "The returned value".into(), &returned, &body);
+
+        // Async functions return a future of what they return.
+        if *r#async {
+            return Ty::Function(types, Box::new(self.fresh()));
+        }
+
+        Ty::Function(types, Box::new(returned))
+    }
+
+    fn infer_infix(&mut self, operator: &Token, lhs: &Expression, rhs: &Expression) -> Ty {
+        let Token::Operator(op) = operator else {
+            return self.fresh();
+        };
+
+        match op {
+            Operator::Assign => {
+                let ty = self.infer(rhs);
+                self.assign(lhs, &ty);
+                return ty;
+            }
+            Operator::PlusEqual | Operator::MinusEqual => {
+                let current = self.infer(lhs);
+                let ty = self.infer(rhs);
+                self.expect(|| format!("The operand of `{op}`"), &current, &ty);
+                return current;
+            }
+            Operator::Dot => {
+                let collection = self.infer(lhs);
+                let Expression::Ident(member) = rhs else {
+                    return self.fresh();
+                };
+
+                return self.member(&collection, &member.name);
+            }
+            _ => {}
+        }
+
+        let lhs = self.infer(lhs);
+        let rhs = self.infer(rhs);
+
+        match op {
+            Operator::Equal | Operator::NotEqual => Ty::Bool,
+            // Union and intersection keep the members of either or both
+            // collections, which needn't have the same members.
+            Operator::Ampersand | Operator::Pipe => {
+                for ty in [&lhs, &rhs] {
+                    if !matches!(self.prune(ty), Ty::Var(_) | Ty::Record(..)) {
+                        let ty = normalize(&[self.resolve(ty)]).remove(0);
+                        self.warn(format!("Operator `{op}` is not supported for `{ty}`."));
+                    }
+                }
+
+                Ty::Record(BTreeMap::new(), Some(self.fresh_var()))
+            }
+            Operator::Less
+            | Operator::LessOrEqual
+            | Operator::Greater
+            | Operator::GreaterOrEqual => {
+                self.expect(|| format!("The operand of `{op}`"), &lhs, &rhs);
+                Ty::Bool
+            }
+            Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide => {
+                self.expect(|| format!("The operand of `{op}`"), &lhs, &rhs);
+
+                let supported = match self.prune(&lhs) {
+                    Ty::Var(_) | Ty::Record(..) => true,
+                    Ty::Int => true,
+                    Ty::Str | Ty::Vector(_) => matches!(op, Operator::Plus),
+                    _ => false,
+                };
+
+                if !supported {
+                    let lhs = normalize(&[self.resolve(&lhs)]).remove(0);
+                    self.warn(format!("Operator `{op}` is not supported for `{lhs}`."));
+                }
+
+                lhs
+            }
+            Operator::LeftArrow => Ty::Unit,
+            _ => self.fresh(),
+        }
+    }
+
+    /// The type of member `name` of a value of type `collection`.
+    fn member(&mut self, collection: &Ty, name: &str) -> Ty {
+        match self.prune(collection) {
+            Ty::Var(_) | Ty::Record(..) => {
+                let ty = self.fresh();
+                let row = Ty::Record(
+                    BTreeMap::from([(name.to_string(), ty.clone())]),
+                    Some(self.fresh_var()),
+                );
+                self.expect(|| format!("The collection with `{name}`"), &row, collection);
+                ty
+            }
+            _ => self.fresh(),
+        }
+    }
+
+    /// Unifies the type of `target` with the type of a value assigned to it.
+    fn assign(&mut self, target: &Expression, ty: &Ty) {
+        match target {
+            Expression::Ident(name) => {
+                let current = self.lookup(&name.name);
+                self.expect(
+                    || format!("The value assigned to `{}`", name.name),
+                    &current,
+                    ty,
+                );
+            }
+            Expression::Infix {
+                operator: Token::Operator(Operator::Dot),
+                lhs,
+                rhs,
+            } => {
+                let collection = self.infer(lhs);
+                let Expression::Ident(member) = &**rhs else {
+                    return;
+                };
+
+                // Assigning to a missing member adds it, which the closed
+                // type of the collection can't follow.
+                if let Ty::Record(members, None) = self.prune(&collection) {
+                    if !members.contains_key(&member.name) {
+                        return;
+                    }
+                }
+
+                let current = self.member(&collection, &member.name);
+                self.expect(
+                    || format!("The value assigned to `{}`", member.name),
+                    &current,
+                    ty,
+                );
+            }
+            _ => {
+                self.infer(target);
+            }
+        }
+    }
+
+    fn infer_invoke(&mut self, invoked: &Expression, args: &[Expression]) -> Ty {
+        let function = self.infer(invoked);
+
+        let mut positional = vec![];
+        let mut known = true;
+        for arg in args {
+            let ty = self.infer(arg);
+            match arg {
+                Expression::Named { .. } | Expression::Spread(_) => known = false,
+                _ if known => positional.push(ty),
+                _ => {}
+            }
+        }
+
+        let callee = match invoked {
+            Expression::Ident(ident) => format!("`{}`", ident.name),
+            _ => "the function".to_string(),
+        };
+
+        match self.prune(&function) {
+            Ty::Function(parameters, returns) => {
+                // Parameters with defaults may be left out.
+                for (i, (parameter, arg)) in parameters.iter().zip(&positional).enumerate() {
+                    self.expect(|| format!("Argument {} of {callee}", i + 1), parameter, arg);
+                }
+
+                *returns
+            }
+            Ty::Var(_) if known => {
+                let returns = self.fresh();
+                let ty = Ty::Function(positional, Box::new(returns.clone()));
+                self.expect(|| callee.clone(), &ty, &function);
+                returns
+            }
+            Ty::Var(_) | Ty::Record(..) => self.fresh(),
+            ty => {
+                let ty = normalize(&[self.resolve(&ty)]).remove(0);
+                self.warn(format!("Calling {callee}, which is `{ty}`."));
+                self.fresh()
+            }
+        }
+    }
+}
+
+impl Default for Inferrer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn free_vars(ty: &Ty, vars: &mut HashSet<usize>) {
+    match ty {
+        Ty::Var(var) => {
+            vars.insert(*var);
+        }
+        Ty::Int | Ty::Bool | Ty::Str | Ty::Unit => {}
+        Ty::Vector(element) => free_vars(element, vars),
+        Ty::Function(parameters, returns) => {
+            for ty in parameters {
+                free_vars(ty, vars);
+            }
+            free_vars(returns, vars);
+        }
+        Ty::Record(members, rest) => {
+            for ty in members.values() {
+                free_vars(ty, vars);
+            }
+            vars.extend(rest);
+        }
+    }
+}
+
+/// Replaces the variables of `ty` that are keys of `vars`.
+fn rename(ty: &Ty, vars: &HashMap<usize, usize>) -> Ty {
+    let var = |var: &usize| *vars.get(var).unwrap_or(var);
+
+    match ty {
+        Ty::Var(v) => Ty::Var(var(v)),
+        Ty::Vector(element) => Ty::Vector(Box::new(rename(element, vars))),
+        Ty::Function(parameters, returns) => Ty::Function(
+            parameters.iter().map(|ty| rename(ty, vars)).collect(),
+            Box::new(rename(returns, vars)),
+        ),
+        Ty::Record(members, rest) => Ty::Record(
+            members
+                .iter()
+                .map(|(name, ty)| (name.clone(), rename(ty, vars)))
+                .collect(),
+            rest.as_ref().map(var),
+        ),
+        ty => ty.clone(),
+    }
+}
+
+/// Numbers the variables of `types` in the order they appear, so that they are
+/// displayed as `'a`, `'b` and so on.
+fn normalize(types: &[Ty]) -> Vec<Ty> {
+    fn visit(ty: &Ty, order: &mut Vec<usize>) {
+        let mut visit_var = |var: usize| {
+            if !order.contains(&var) {
+                order.push(var);
+            }
+        };
+
+        match ty {
+            Ty::Var(var) => visit_var(*var),
+            Ty::Vector(element) => visit(element, order),
+            Ty::Function(parameters, returns) => {
+                for ty in parameters {
+                    visit(ty, order);
+                }
+                visit(returns, order);
+            }
+            Ty::Record(members, _) => {
+                for ty in members.values() {
+                    visit(ty, order);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut order = vec![];
+    for ty in types {
+        visit(ty, &mut order);
+    }
+
+    let vars = order
+        .into_iter()
+        .enumerate()
+        .map(|(i, var)| (var, i))
+        .collect::<HashMap<_, _>>();
+
+    types.iter().map(|ty| rename(ty, &vars)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    fn infer(input: &str) -> Inference {
+        let program = Parser::new(Lexer::new(input))
+            .and_then(|mut parser| parser.parse_program())
+            .unwrap();
+        assert!(program.errors.is_empty(), "{:?}", program.errors);
+
+        Inferrer::new().infer_program(&program)
+    }
+
+    #[test]
+    fn test_inference() {
+        let tests = vec![
+            // Functions bound with `let` can be used at different types.
+            (
+                "let id = fn(x) { x }; id(1) + 1; id(\"a\") + \"b\";",
+                vec![],
+            ),
+            (
+                "let inc = fn(x) { x + 1 }; inc(\"a\");",
+                vec!["Argument 1 of `inc` should be `int` but is `str`."],
+            ),
+            (
+                "let bad = 1 + \"a\";",
+                vec!["In `bad`: The operand of `+` should be `int` but is `str`."],
+            ),
+            (
+                "let f = fn() { true * 2 };",
+                vec![
+                    "In `f`: The operand of `*` should be `bool` but is `int`.",
+                    "In `f`: Operator `*` is not supported for `bool`.",
+                ],
+            ),
+            (
+                "let b = true; b - b;",
+                vec!["Operator `-` is not supported for `bool`."],
+            ),
+            ("let n = 1; n(2);", vec!["Calling `n`, which is `int`."]),
+            (
+                "let v = [1, 2, \"a\"];",
+                vec!["In `v`: The vector element should be `int` but is `str`."],
+            ),
+            ("let v = [1, 2]; v[0] + 1; v[1:] + [3];", vec![]),
+            (
+                "let s = if true { 1 } else { \"a\" };",
+                vec!["In `s`: The `else` branch should be `int` but is `str`."],
+            ),
+            // Branches of discarded values may have different types.
+            ("if true { print(1) } else { 2 };", vec![]),
+            (
+                "let f = fn(n) { match n { 0 -> \"zero\", _ -> n } };",
+                vec!["In `f`: The `match` arm should be `str` but is `int`."],
+            ),
+            // Functions only use the members they access.
+            (
+                "let getx = fn(p) { p.x + 1 }; getx(def { x = 1, y = 2 }); getx(def { y = 2 });",
+                vec!["Argument 1 of `getx` should be `{ x: int, .. }` but is `{ y: int }`."],
+            ),
+            (
+                "let p = def { x = 1 }; p.x = \"a\";",
+                vec!["The value assigned to `x` should be `int` but is `str`."],
+            ),
+            // Assigning to a missing member adds it.
+            ("let p = def { x = 1 }; p.y = \"a\";", vec![]),
+            // Methods returning `self` have no finite type.
+            (
+                "let c = def { n = 0, bump = fn(by) { self.n += by; return self; } }; c.bump(1);",
+                vec![],
+            ),
+            // Methods use the members of their collection as `self`.
+            (
+                "let c = def { n = 0, set = fn() { self.n = \"a\"; } };",
+                vec!["In `c`: `self` should be `{ n: int, .. }` but is `{ n: str, .. }`."],
+            ),
+            (
+                "let x: int = \"a\";",
+                vec!["`x` should be `int` but is `str`."],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let inference = infer(input);
+            assert_eq!(inference.warnings, expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_inferred_types() {
+        let input = r#"
+        let add = fn(a, b) { a + b };
+        let inc = fn(x) { x + 1 };
+        let point = def { x = 1, y = "a" };
+        let norm = fn(p) { p.x * p.x + p.y * p.y };
+        let first = fn(v) { v[0] };
+        first([1]);
+        let counter = def {
+            count = 0,
+            bump = fn(by) { self.count += by; return self; },
+        };
+        "#;
+
+        let inference = infer(input);
+        assert_eq!(inference.warnings, Vec::<String>::new());
+
+        let types = inference
+            .types
+            .iter()
+            .map(|(path, ty)| (path.as_str(), ty.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let expected = [
+            ("add.a", "'a"),
+            ("add.b", "'a"),
+            ("inc.x", "int"),
+            ("point.x", "int"),
+            ("point.y", "str"),
+            ("norm.p", "{ x: 'a, y: 'a, .. }"),
+            ("first.v", "'a"),
+            ("counter.count", "int"),
+            ("counter.bump.by", "int"),
+            ("counter.bump", "fn(int) -> { count: int, .. }"),
+        ];
+
+        for (path, ty) in expected {
+            assert_eq!(types.get(path).map(String::as_str), Some(ty), "{path}");
+        }
+    }
+}
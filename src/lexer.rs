@@ -45,6 +45,7 @@ impl Lexer {
             ')' => Token::RParen,
             ';' => Token::Semicolon,
             ',' => Token::Comma,
+            ':' => Token::Colon,
             _ => Token::Illegal,
         }
     }
@@ -75,6 +76,7 @@ impl Lexer {
             '>' if self.c == '-' => Token::Operator(Operator::RightArrow),
             '-' if self.c == '<' => Token::Operator(Operator::LeftArrow),
             '&' if self.c == '&' => Token::Operator(Operator::And),
+            '.' if self.c == '.' => Token::Operator(Operator::Spread),
             '|' if self.c == '|' => Token::Operator(Operator::Or),
             _ => return self.read_single_token(),
         };
@@ -88,8 +90,8 @@ impl Lexer {
         self.skip_whitespace();
 
         let token = match self.c {
-            '=' | '!' | '-' | '+' | '&' | '|' | '<' | '>' => self.read_double_token(),
-            '/' | '*' | '.' | '?' | '{' | '}' | '(' | ')' | '[' | ']' | ';' | ',' => {
+            '=' | '!' | '-' | '+' | '&' | '|' | '<' | '>' | '.' => self.read_double_token(),
+            '/' | '*' | '?' | '{' | '}' | '(' | ')' | '[' | ']' | ';' | ',' | ':' => {
                 self.read_single_token()
             }
             '"' => return Ok(Token::Str(self.read_string())),
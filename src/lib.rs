@@ -1,10 +1,14 @@
 pub mod ast;
 pub mod builtin;
+pub mod check;
+pub mod coroutine;
 pub mod error;
 pub mod eval;
+pub mod infer;
 pub mod lexer;
 pub mod object;
 pub mod parser;
+pub mod scheduler;
 pub mod stack;
 pub mod token;
 
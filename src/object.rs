@@ -1,35 +1,77 @@
+// Objects are neither `Send` nor `Sync`, but they are counted atomically so
+// that immutable ones can be shared with other threads, see `Shareable`.
+#![allow(clippy::arc_with_non_send_sync)]
+
 use std::{
-    cell::UnsafeCell,
-    collections::HashMap,
+    cell::{Cell, RefCell, UnsafeCell},
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{Debug, Display},
-    sync::Arc,
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc, OnceLock,
+    },
 };
 
-use crate::ast::{Expression, Ident};
+use crate::ast::{Expression, FunctionClause, Ident};
 
-use crate::eval::error::Result;
+use crate::eval::{
+    error::{Error, Result},
+    numbered, Eval,
+};
 
 pub enum ObjectType {
     Bool,
     Builtin,
+    Channel,
     Collection,
     Vector,
     Function,
+    Future,
     Integer,
+    Iter,
+    Quote,
+    Slice,
     Str,
     Unit,
+    Variant,
 }
 
+type Slot = Arc<dyn Fn(Option<Reference>) -> Option<Reference>>;
+
 pub struct VTable {
-    inner: HashMap<&'static str, Arc<dyn Fn(Option<Reference>) -> Option<Reference>>>,
+    inner: Slots,
 }
 
 impl VTable {
-    pub fn get(&self, s: &str) -> Option<&Arc<dyn Fn(Option<Reference>) -> Option<Reference>>> {
+    pub fn get(&self, s: &str) -> Option<&Slot> {
         self.inner.get(s)
     }
 }
 
+/// The slots of a `VTable` by name.
+///
+/// Every object builds its own v-table and holds only about a dozen slots,
+/// so scanning a list beats hashing into a map, which is costly to build.
+#[derive(Default)]
+struct Slots(Vec<(&'static str, Slot)>);
+
+impl Slots {
+    fn insert(&mut self, name: &'static str, slot: Slot) {
+        match self.0.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, s)) => *s = slot,
+            None => self.0.push((name, slot)),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Slot> {
+        self.0
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, slot)| slot)
+    }
+}
+
 impl Debug for VTable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("VTable").finish()
@@ -39,17 +81,150 @@ impl Debug for VTable {
 pub trait Object: Debug + Display {
     fn r#type(&self) -> ObjectType;
     fn v_table(&self) -> &VTable;
+
+    /// Stores `value` under `index` in place, as done by `obj[index] = value`
+    /// and `obj.member = value`.
+    ///
+    /// Member assignment passes the member name as a `Str` index.
+    fn set_idx(&self, _index: Reference, _value: Reference) -> std::result::Result<(), Error> {
+        Err(Error::Type(format!(
+            "Object {} does not support index assignment.",
+            self
+        )))
+    }
+
+    /// Makes the object and everything reachable from it reject mutation.
+    ///
+    /// Objects that cannot be mutated don't need to do anything.
+    fn freeze(&self) {}
+
+    /// The objects this one references, or `None` if it can be mutated.
+    ///
+    /// See [`Reference::is_shareable`].
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        None
+    }
+}
+
+/// The cell an object lives in, which is mutated in place through shared
+/// references.
+///
+/// Like `UnsafeCell` it is neither `Send` nor `Sync`, so neither are
+/// references to objects. Only a [`Shareable`] can be sent to other threads.
+#[derive(Debug)]
+struct Shared<T: ?Sized>(UnsafeCell<T>);
+
+impl<T> Shared<T> {
+    fn new(obj: T) -> Self {
+        Self(UnsafeCell::new(obj))
+    }
+}
+
+impl<T: ?Sized> Shared<T> {
+    fn get(&self) -> *mut T {
+        self.0.get()
+    }
+}
+
+/// The contents of a container, shared with the slots of its v-table so that
+/// they see the container being mutated in place.
+#[derive(Debug)]
+pub struct Contents<T>(Arc<Shared<T>>);
+
+impl<T> Contents<T> {
+    fn new(contents: T) -> Self {
+        Self(Arc::new(Shared::new(contents)))
+    }
+}
+
+impl<T> Clone for Contents<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> std::ops::Deref for Contents<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &(*self.0.get()) }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Reference {
-    inner: Arc<UnsafeCell<dyn Object>>,
+    inner: Arc<Shared<dyn Object>>,
 }
 
 impl Reference {
+    /// The referenced object as a `T`.
+    ///
+    /// Objects may be read by several threads at once, see [`Shareable`], so
+    /// this is how they are read. Mutable state lives in a [`Contents`] or a
+    /// `Cell`.
+    ///
+    /// # Safety
+    ///
+    /// The referenced object has to be a `T`.
+    pub unsafe fn get<T>(&self) -> &T {
+        &(*(self.inner.get() as *const T))
+    }
+
+    /// Like [`Reference::get`], for mutating objects which are never shared,
+    /// such as channels and futures.
+    ///
+    /// # Safety
+    ///
+    /// The referenced object has to be a `T`, and no other reference to it may
+    /// be in use while the returned one is.
     pub unsafe fn get_mut<T>(&self) -> &mut T {
         &mut (*(self.inner.get() as *mut T))
     }
+
+    /// Whether the referenced object and everything reachable from it are
+    /// immutable, which lets other threads read it while this one does.
+    pub fn is_shareable(&self) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![self.clone()];
+
+        while let Some(reference) = pending.pop() {
+            // Frozen objects can be cyclic.
+            if !seen.insert(Arc::as_ptr(&reference.inner) as *const ()) {
+                continue;
+            }
+
+            match reference.immutable_references() {
+                Some(references) => pending.extend(references),
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    /// A handle to the referenced object that can be sent to other threads,
+    /// or `None` unless it is [shareable](Reference::is_shareable).
+    pub fn share(&self) -> Option<Shareable> {
+        self.is_shareable().then(|| Shareable(self.clone()))
+    }
+
+    /// Assigns `value` to `index` of the referenced object.
+    ///
+    /// The object is mutated in place, so the change is visible through every
+    /// reference to it.
+    pub fn set_idx(&self, index: Reference, value: Reference) -> std::result::Result<(), Error> {
+        (**self).set_idx(index, value)
+    }
+
+    /// Deeply freezes the referenced object, see [`Object::freeze`].
+    pub fn freeze(&self) {
+        (**self).freeze()
+    }
+
+    /// Whether both references point to the same object.
+    pub fn ptr_eq(&self, other: &Reference) -> bool {
+        std::ptr::addr_eq(Arc::as_ptr(&self.inner), Arc::as_ptr(&other.inner))
+    }
 }
 
 impl std::ops::Deref for Reference {
@@ -62,7 +237,35 @@ impl std::ops::Deref for Reference {
 
 impl Display for Reference {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}", unsafe { &(*(*self.inner).get()) }))
+        f.write_fmt(format_args!("{}", unsafe { &(*self.inner.get()) }))
+    }
+}
+
+/// A reference to an object which, like everything reachable from it, can't
+/// be mutated, created by [`Reference::share`].
+///
+/// Other threads can read the object while this one does, e.g. the workers
+/// of `par_map`, or settle a future with it, see
+/// [`Completer`](crate::scheduler::Completer).
+#[derive(Debug, Clone)]
+pub struct Shareable(Reference);
+
+// Immutable objects are only ever read, and their reference counts are
+// atomic. Objects that can be mutated aren't reachable from them, as
+// `Reference::share` checks, and freezing can't be undone.
+unsafe impl Send for Shareable {}
+unsafe impl Sync for Shareable {}
+
+impl Shareable {
+    /// A reference to the object for the current thread.
+    pub fn reference(&self) -> Reference {
+        self.0.clone()
+    }
+}
+
+impl Display for Shareable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
     }
 }
 
@@ -80,16 +283,103 @@ impl Object for Integer {
     fn v_table(&self) -> &VTable {
         &self.v_table
     }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(vec![])
+    }
 }
 
-fn erase(obj: Arc<UnsafeCell<dyn Object>>) -> Arc<UnsafeCell<dyn Object>> {
+fn erase(obj: Arc<Shared<dyn Object>>) -> Arc<Shared<dyn Object>> {
     obj
 }
 
+/// Hands out unique ids used for the identity equality of callables.
+fn next_id() -> usize {
+    static ID: AtomicUsize = AtomicUsize::new(0);
+    ID.fetch_add(1, atomic::Ordering::Relaxed)
+}
+
+fn as_bool(obj: &Reference) -> Option<bool> {
+    if !matches!(obj.r#type(), ObjectType::Bool) {
+        return None;
+    }
+
+    Some(unsafe { obj.get::<Bool>().val })
+}
+
+/// Structural equality of two objects, as seen by `==`.
+///
+/// Objects without an `eq_lhs` slot are never equal to anything.
+pub fn equals(lhs: &Reference, rhs: &Reference) -> bool {
+    lhs.v_table()
+        .get("eq_lhs")
+        .and_then(|eq| eq(Some(rhs.clone())))
+        .and_then(|eq| as_bool(&eq))
+        .unwrap_or(false)
+}
+
+/// Ordering of two objects, as seen by `<`, `<=`, `>` and `>=`.
+///
+/// Returns `None` if the objects cannot be ordered relative to each other.
+pub fn compare(lhs: &Reference, rhs: &Reference) -> Option<Ordering> {
+    let le = lhs.v_table().get("le_lhs")?(Some(rhs.clone()))?;
+
+    if as_bool(&le)? {
+        return Some(Ordering::Less);
+    }
+
+    if equals(lhs, rhs) {
+        return Some(Ordering::Equal);
+    }
+
+    Some(Ordering::Greater)
+}
+
+type Accepts = fn(Ordering) -> bool;
+
+/// The ordering slots and the orderings each of them accepts.
+const ORDERINGS: [(&str, Accepts); 4] = [
+    ("le_lhs", Ordering::is_lt),
+    ("leq_lhs", Ordering::is_le),
+    ("ge_lhs", Ordering::is_gt),
+    ("geq_lhs", Ordering::is_ge),
+];
+
+/// The positions selected by indexing a sequence.
+enum Selection {
+    One(usize),
+    Many(Vec<usize>),
+}
+
+/// Resolves an `Integer` or `Slice` index into positions of a sequence of
+/// length `len`, counting negative indices from the end.
+///
+/// Returns `None` for other indices and for integers out of range.
+fn select(len: usize, index: Option<Reference>) -> Option<Selection> {
+    let index = index?;
+
+    match index.r#type() {
+        ObjectType::Integer => {
+            let idx = unsafe { index.get::<Integer>().val } as i64;
+            let idx = if idx < 0 { idx + len as i64 } else { idx };
+
+            (0..len as i64)
+                .contains(&idx)
+                .then_some(Selection::One(idx as usize))
+        }
+        ObjectType::Slice => unsafe { index.get::<Slice>() }
+            .indices(len)
+            .map(Selection::Many),
+        _ => None,
+    }
+}
+
 impl Integer {
     pub fn erased(val: i32) -> Reference {
+        // Integers are created by most operations, so their v-table is
+        // allocated at its final size rather than grown slot by slot.
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots(Vec::with_capacity(13)),
         };
 
         let is_int = |obj: Option<Reference>| -> Option<i32> {
@@ -101,7 +391,7 @@ impl Integer {
                 return None;
             }
 
-            let rhs = unsafe { obj.get_mut::<Integer>().val };
+            let rhs = unsafe { obj.get::<Integer>().val };
 
             Some(rhs)
         };
@@ -145,18 +435,12 @@ impl Integer {
 
         v_table.inner.insert(
             "eq_lhs",
-            Arc::new(move |obj| {
-                let rhs = is_int(obj)?;
-                Some(Bool::erased(val == rhs))
-            }),
+            Arc::new(move |obj| Some(Bool::erased(is_int(obj) == Some(val)))),
         );
 
         v_table.inner.insert(
             "neq_lhs",
-            Arc::new(move |obj| {
-                let rhs = is_int(obj)?;
-                Some(Bool::erased(val != rhs))
-            }),
+            Arc::new(move |obj| Some(Bool::erased(is_int(obj) != Some(val)))),
         );
 
         v_table.inner.insert(
@@ -191,13 +475,17 @@ impl Integer {
             }),
         );
 
+        v_table
+            .inner
+            .insert("neg", Arc::new(move |_| Some(Integer::erased(-val))));
+
         v_table.inner.insert(
             "truthy",
             Arc::new(move |_| if val > 0 { Some(Unit::erased()) } else { None }),
         );
 
         Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Integer { val, v_table }))),
+            inner: erase(Arc::new(Shared::new(Integer { val, v_table }))),
         }
     }
 }
@@ -222,12 +510,26 @@ impl Object for Bool {
     fn v_table(&self) -> &VTable {
         &self.v_table
     }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(vec![])
+    }
 }
 
 impl Bool {
+    /// The boolean `val`, which is created once per thread as it has no state
+    /// besides its value.
     pub fn erased(val: bool) -> Reference {
+        thread_local! {
+            static BOOLS: [Reference; 2] = [Bool::create(false), Bool::create(true)];
+        }
+
+        BOOLS.with(|bools| bools[val as usize].clone())
+    }
+
+    fn create(val: bool) -> Reference {
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots::default(),
         };
 
         let is_bool = |obj: Option<Reference>| -> Option<bool> {
@@ -239,7 +541,7 @@ impl Bool {
                 return None;
             }
 
-            let rhs = unsafe { obj.get_mut::<Bool>().val };
+            let rhs = unsafe { obj.get::<Bool>().val };
 
             Some(rhs)
         };
@@ -251,18 +553,12 @@ impl Bool {
 
         v_table.inner.insert(
             "eq_lhs",
-            Arc::new(move |obj| {
-                let rhs = is_bool(obj)?;
-                Some(Bool::erased(val == rhs))
-            }),
+            Arc::new(move |obj| Some(Bool::erased(is_bool(obj) == Some(val)))),
         );
 
         v_table.inner.insert(
             "neq_lhs",
-            Arc::new(move |obj| {
-                let rhs = is_bool(obj)?;
-                Some(Bool::erased(val == rhs))
-            }),
+            Arc::new(move |obj| Some(Bool::erased(is_bool(obj) != Some(val)))),
         );
 
         v_table
@@ -279,7 +575,7 @@ impl Bool {
         );
 
         Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Bool { val, v_table }))),
+            inner: erase(Arc::new(Shared::new(Bool { val, v_table }))),
         }
     }
 }
@@ -303,18 +599,46 @@ impl Object for Unit {
     fn v_table(&self) -> &VTable {
         &self.v_table
     }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(vec![])
+    }
 }
 
 impl Unit {
+    /// The unit value, which is created once per thread as it has no state.
     pub fn erased() -> Reference {
+        thread_local! {
+            static UNIT: Reference = Unit::create();
+        }
+
+        UNIT.with(Reference::clone)
+    }
+
+    fn create() -> Reference {
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots::default(),
+        };
+
+        let is_unit = |obj: Option<Reference>| {
+            obj.map(|obj| matches!(obj.r#type(), ObjectType::Unit))
+                .unwrap_or(false)
         };
 
         v_table.inner.insert("truthy", Arc::new(move |_| None));
 
+        v_table.inner.insert(
+            "eq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(is_unit(obj)))),
+        );
+
+        v_table.inner.insert(
+            "neq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(!is_unit(obj)))),
+        );
+
         Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Unit { v_table }))),
+            inner: erase(Arc::new(Shared::new(Unit { v_table }))),
         }
     }
 }
@@ -328,9 +652,15 @@ impl Display for Unit {
 #[derive(Debug)]
 pub struct Function {
     v_table: VTable,
-    pub parameters: Vec<Ident>,
-    pub body: Expression,
-    pub capture: HashMap<Ident, Reference>,
+    id: usize,
+    /// The name of the binding the function literal was declared as.
+    pub name: Option<Arc<str>>,
+    /// The name `fn name(...)` binds the function to in its own clauses.
+    pub binding: Option<Ident>,
+    /// Tried in order, calling the first one the arguments match.
+    pub clauses: Vec<FunctionClause>,
+    /// The captured bindings, and whether each of them is constant.
+    pub capture: HashMap<Ident, (Reference, bool)>,
 }
 
 impl Object for Function {
@@ -341,25 +671,62 @@ impl Object for Function {
     fn v_table(&self) -> &VTable {
         &self.v_table
     }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(
+            self.capture
+                .values()
+                .map(|(captured, _)| captured.clone())
+                .collect(),
+        )
+    }
 }
 
 impl Function {
     pub fn erased(
-        parameters: Vec<Ident>,
-        body: Expression,
-        capture: HashMap<Ident, Reference>,
+        name: Option<&str>,
+        binding: Option<Ident>,
+        clauses: Vec<FunctionClause>,
+        capture: HashMap<Ident, (Reference, bool)>,
     ) -> Reference {
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots::default(),
+        };
+
+        let id = next_id();
+
+        let is_same = move |obj: Option<Reference>| {
+            let Some(obj) = obj else {
+                return false;
+            };
+
+            matches!(obj.r#type(), ObjectType::Function)
+                && unsafe { obj.get::<Function>().id } == id
         };
 
         v_table.inner.insert("truthy", Arc::new(move |_| None));
 
+        v_table.inner.insert(
+            "eq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(is_same(obj)))),
+        );
+
+        v_table.inner.insert(
+            "neq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(!is_same(obj)))),
+        );
+
         Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Function {
+            inner: erase(Arc::new(Shared::new(Function {
                 v_table,
-                parameters,
-                body,
+                id,
+                name: binding
+                    .as_ref()
+                    .map(|binding| binding.name.as_str())
+                    .or(name)
+                    .map(Arc::from),
+                binding,
+                clauses,
                 capture,
             }))),
         }
@@ -372,15 +739,21 @@ impl Display for Function {
     }
 }
 
-#[derive(Debug)]
-pub struct Collection {
+type Step = dyn FnMut(&mut Eval) -> std::result::Result<Option<Reference>, Error>;
+
+/// A lazy iterator, as returned by generator functions and the iterator
+/// builtins.
+pub struct Iter {
     v_table: VTable,
-    pub members: Arc<HashMap<Ident, Reference>>,
+    id: usize,
+    /// Borrowed while the iterator advances, which may evaluate code that
+    /// tries to advance it again.
+    step: RefCell<Box<Step>>,
 }
 
-impl Object for Collection {
+impl Object for Iter {
     fn r#type(&self) -> ObjectType {
-        ObjectType::Collection
+        ObjectType::Iter
     }
 
     fn v_table(&self) -> &VTable {
@@ -388,92 +761,90 @@ impl Object for Collection {
     }
 }
 
-impl Collection {
-    pub fn erased(members: HashMap<Ident, Reference>) -> Reference {
-        let members = Arc::new(members);
+impl Iter {
+    /// Creates an iterator producing the values returned by `step` until it
+    /// returns `None`.
+    pub fn erased(
+        step: impl FnMut(&mut Eval) -> std::result::Result<Option<Reference>, Error> + 'static,
+    ) -> Reference {
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots::default(),
         };
 
-        let is_collection = |obj: Option<Reference>| {
+        let id = next_id();
+
+        let is_same = move |obj: Option<Reference>| {
             let Some(obj) = obj else {
-                return None;
+                return false;
             };
 
-            if !matches!(obj.r#type(), ObjectType::Collection) {
-                return None;
-            }
-
-            let rhs = unsafe { obj.get_mut::<Collection>().members.clone() };
-
-            Some(rhs)
+            matches!(obj.r#type(), ObjectType::Iter) && unsafe { obj.get::<Iter>().id } == id
         };
 
         v_table.inner.insert("truthy", Arc::new(move |_| None));
-        {
-            let members = members.clone();
-            v_table.inner.insert(
-                "uni_lhs",
-                Arc::new(move |obj| {
-                    let rhs = is_collection(obj)?;
-                    let mut union = HashMap::new();
 
-                    for (ident, member) in rhs.iter() {
-                        union.insert(ident.clone(), member.clone());
-                    }
+        v_table.inner.insert(
+            "eq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(is_same(obj)))),
+        );
 
-                    for (ident, member) in members.iter() {
-                        union.insert(ident.clone(), member.clone());
-                    }
+        v_table.inner.insert(
+            "neq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(!is_same(obj)))),
+        );
 
-                    Some(Collection::erased(union))
-                }),
-            );
+        Reference {
+            inner: erase(Arc::new(Shared::new(Iter {
+                v_table,
+                id,
+                step: RefCell::new(Box::new(step)),
+            }))),
         }
-        {
-            let members = members.clone();
-            v_table.inner.insert(
-                "ins_lhs",
-                Arc::new(move |obj| {
-                    let rhs = is_collection(obj)?;
-                    let mut intersection = HashMap::new();
+    }
 
-                    for (ident, member) in members.iter() {
-                        if rhs.contains_key(&ident) {
-                            intersection.insert(ident.clone(), member.clone());
-                        }
-                    }
+    /// Advances the iterator, returning `None` once it is exhausted.
+    ///
+    /// Fails if the iterator is already advancing, e.g. when a generator calls
+    /// `next` on itself.
+    pub fn next(&self, eval: &mut Eval) -> std::result::Result<Option<Reference>, Error> {
+        let Ok(mut step) = self.step.try_borrow_mut() else {
+            return Err(Error::Eval("Iterator is already running.".to_string()));
+        };
 
-                    Some(Collection::erased(intersection))
-                }),
-            );
-        }
+        step(eval)
+    }
+}
 
-        Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Collection { v_table, members }))),
-        }
+impl Display for Iter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Iterator")
     }
 }
 
-impl Display for Collection {
+impl Debug for Iter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut dbg = f.debug_struct("Collection");
-        for (ident, member) in self.members.iter() {
-            dbg.field(&ident.name, member);
-        }
-        dbg.finish()
+        f.write_str("Iterator")
     }
 }
 
-#[derive(Debug)]
-pub struct Vector {
+/// A queue of values sent between green threads with `<-`.
+pub struct Channel {
     v_table: VTable,
-    pub elements: Arc<Vec<Reference>>,
+    id: usize,
+    pub buffer: VecDeque<Reference>,
+    /// The number of values that can be sent without being received, where
+    /// sending over a channel without capacity waits until received.
+    pub capacity: usize,
+    /// The numbers of values sent and received so far.
+    pub sent: usize,
+    pub received: usize,
+    /// Whether no more values can be sent.
+    pub closed: bool,
 }
 
-impl Object for Vector {
+impl Object for Channel {
     fn r#type(&self) -> ObjectType {
-        ObjectType::Vector
+        ObjectType::Channel
     }
 
     fn v_table(&self) -> &VTable {
@@ -481,55 +852,613 @@ impl Object for Vector {
     }
 }
 
-impl Vector {
-    pub fn erased(elements: Vec<Reference>) -> Reference {
-        let elements = Arc::new(elements);
+impl Channel {
+    pub fn erased(capacity: usize) -> Reference {
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots::default(),
         };
 
-        let is_vec = |obj: Option<Reference>| {
+        let id = next_id();
+
+        let is_same = move |obj: Option<Reference>| {
             let Some(obj) = obj else {
-                return None;
+                return false;
             };
 
-            if !matches!(obj.r#type(), ObjectType::Vector) {
-                return None;
-            }
-
-            let rhs = unsafe { obj.get_mut::<Vector>().elements.clone() };
-
-            Some(rhs)
+            matches!(obj.r#type(), ObjectType::Channel)
+                && unsafe { obj.get::<Channel>().id } == id
         };
 
         v_table.inner.insert("truthy", Arc::new(move |_| None));
-        {
-            let elements = elements.clone();
-            v_table.inner.insert(
-                "add_lhs",
-                Arc::new(move |obj| {
-                    let rhs = is_vec(obj)?;
 
-                    let new = elements
-                        .iter()
-                        .cloned()
-                        .chain(rhs.iter().cloned())
-                        .collect();
+        v_table.inner.insert(
+            "eq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(is_same(obj)))),
+        );
 
-                    Some(Vector::erased(new))
-                }),
-            );
-        }
-        {
-            let elements = elements.clone();
-            v_table.inner.insert(
-                "len",
-                Arc::new(move |_| Some(Integer::erased(elements.len() as i32))),
-            );
+        v_table.inner.insert(
+            "neq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(!is_same(obj)))),
+        );
+
+        Reference {
+            inner: erase(Arc::new(Shared::new(Channel {
+                v_table,
+                id,
+                buffer: VecDeque::new(),
+                capacity,
+                sent: 0,
+                received: 0,
+                closed: false,
+            }))),
         }
-        {
-            let elements = elements.clone();
-            v_table.inner.insert(
+    }
+}
+
+impl Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Channel")
+    }
+}
+
+impl Debug for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Channel")
+    }
+}
+
+/// The eventual result of an async call, a timer or an operation of the host,
+/// which `await` waits for.
+pub struct Future {
+    v_table: VTable,
+    id: usize,
+    /// The value or error the future was settled with, if it is settled.
+    pub outcome: Option<std::result::Result<Reference, Error>>,
+    /// Futures settled along with this one, unless they are settled already.
+    pub forwards: Vec<Reference>,
+}
+
+impl Object for Future {
+    fn r#type(&self) -> ObjectType {
+        ObjectType::Future
+    }
+
+    fn v_table(&self) -> &VTable {
+        &self.v_table
+    }
+}
+
+impl Future {
+    /// Creates a future which is not settled yet.
+    pub fn erased() -> Reference {
+        let mut v_table = VTable {
+            inner: Slots::default(),
+        };
+
+        let id = next_id();
+
+        let is_same = move |obj: Option<Reference>| {
+            let Some(obj) = obj else {
+                return false;
+            };
+
+            matches!(obj.r#type(), ObjectType::Future)
+                && unsafe { obj.get::<Future>().id } == id
+        };
+
+        v_table.inner.insert("truthy", Arc::new(move |_| None));
+
+        v_table.inner.insert(
+            "eq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(is_same(obj)))),
+        );
+
+        v_table.inner.insert(
+            "neq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(!is_same(obj)))),
+        );
+
+        Reference {
+            inner: erase(Arc::new(Shared::new(Future {
+                v_table,
+                id,
+                outcome: None,
+                forwards: vec![],
+            }))),
+        }
+    }
+
+    /// Settles `future` with `outcome` along with the futures it forwards to,
+    /// giving whether it wasn't settled before.
+    pub fn settle(future: &Reference, outcome: std::result::Result<Reference, Error>) -> bool {
+        let future = unsafe { future.get_mut::<Future>() };
+        if future.outcome.is_some() {
+            return false;
+        }
+
+        for forward in std::mem::take(&mut future.forwards) {
+            Future::settle(&forward, outcome.clone());
+        }
+
+        future.outcome = Some(outcome);
+        true
+    }
+
+    /// Settles `forward` along with `future`, or right away if `future` is
+    /// settled already.
+    pub fn forward(future: &Reference, forward: Reference) {
+        let state = unsafe { future.get_mut::<Future>() };
+
+        match state.outcome.clone() {
+            Some(outcome) => {
+                Future::settle(&forward, outcome);
+            }
+            None => state.forwards.push(forward),
+        }
+    }
+
+    pub fn is_settled(future: &Reference) -> bool {
+        unsafe { future.get::<Future>() }.outcome.is_some()
+    }
+}
+
+impl Display for Future {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Future")
+    }
+}
+
+impl Debug for Future {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Future")
+    }
+}
+
+#[derive(Debug)]
+pub struct Collection {
+    v_table: VTable,
+    pub members: Contents<HashMap<Ident, Reference>>,
+    /// The collection that members missing from this one are delegated to.
+    proto: Contents<Option<Reference>>,
+    pub frozen: Cell<bool>,
+    /// Whether the members can't be reassigned, while the objects they refer
+    /// to may still change, as for the namespaces of modules.
+    pub sealed: Cell<bool>,
+}
+
+impl Object for Collection {
+    fn r#type(&self) -> ObjectType {
+        ObjectType::Collection
+    }
+
+    fn v_table(&self) -> &VTable {
+        &self.v_table
+    }
+
+    fn set_idx(&self, index: Reference, value: Reference) -> std::result::Result<(), Error> {
+        if !matches!(index.r#type(), ObjectType::Str) {
+            return Err(Error::Type(format!(
+                "Collections cannot be indexed by {}.",
+                index
+            )));
+        }
+
+        let name = unsafe { index.get::<Str>().str.to_string() };
+
+        if self.frozen.get() {
+            return Err(Error::Eval(format!(
+                "Cannot assign to member `{name}` of a frozen collection."
+            )));
+        }
+
+        if self.sealed.get() {
+            return Err(Error::Eval(format!(
+                "Cannot assign to member `{name}` of a sealed collection."
+            )));
+        }
+
+        unsafe { (*self.members.0.get()).insert(Ident { name }, value) };
+
+        Ok(())
+    }
+
+    fn freeze(&self) {
+        // Stopping at frozen objects also keeps cyclic structures finite.
+        if self.frozen.get() {
+            return;
+        }
+
+        self.frozen.set(true);
+
+        // The prototype is shared with other collections, so it is left alone.
+        for member in self.members.values() {
+            member.freeze();
+        }
+    }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        self.frozen.get().then(|| {
+            self.members
+                .values()
+                .chain(self.proto.as_ref())
+                .cloned()
+                .collect()
+        })
+    }
+}
+
+impl Collection {
+    pub fn erased(members: HashMap<Ident, Reference>) -> Reference {
+        let members = Contents::new(members);
+        let proto = Contents::new(None);
+        let v_table = Self::v_table_for(&members, &proto);
+
+        Reference {
+            inner: erase(Arc::new(Shared::new(Collection {
+                v_table,
+                members,
+                proto,
+                frozen: Cell::new(false),
+                sealed: Cell::new(false),
+            }))),
+        }
+    }
+
+    /// Looks up `ident` on `collection`, walking its prototype chain.
+    ///
+    /// Returns the member together with the collection that defines it.
+    pub fn lookup(collection: &Reference, ident: &Ident) -> Option<(Reference, Reference)> {
+        let mut owner = collection.clone();
+
+        loop {
+            if !matches!(owner.r#type(), ObjectType::Collection) {
+                return None;
+            }
+
+            let current = unsafe { owner.get::<Collection>() };
+
+            if let Some(member) = current.members.get(ident) {
+                return Some((member.clone(), owner));
+            }
+
+            owner = current.proto()?;
+        }
+    }
+
+    /// The collection that members missing from this one are delegated to.
+    pub fn proto(&self) -> Option<Reference> {
+        (*self.proto).clone()
+    }
+
+    /// Makes `collection` delegate missing members to `proto`.
+    ///
+    /// Fails if either isn't a collection or if `collection` is already part of
+    /// the prototype chain of `proto`.
+    pub fn set_proto(collection: &Reference, proto: Reference) -> std::result::Result<(), String> {
+        if !matches!(collection.r#type(), ObjectType::Collection)
+            || !matches!(proto.r#type(), ObjectType::Collection)
+        {
+            return Err("Prototypes can only be set between collections.".into());
+        }
+
+        let target = unsafe { collection.get::<Collection>() };
+        if target.frozen.get() || target.sealed.get() {
+            return Err("Cannot set the prototype of a frozen or sealed collection.".into());
+        }
+
+        let mut ancestor = Some(proto.clone());
+        while let Some(current) = ancestor {
+            if current.ptr_eq(collection) {
+                return Err("Prototype chains cannot be cyclic.".into());
+            }
+            ancestor = unsafe { current.get::<Collection>().proto() };
+        }
+
+        unsafe { *collection.get::<Collection>().proto.0.get() = Some(proto) };
+
+        Ok(())
+    }
+
+    fn v_table_for(
+        members: &Contents<HashMap<Ident, Reference>>,
+        proto: &Contents<Option<Reference>>,
+    ) -> VTable {
+        let mut v_table = VTable {
+            inner: Slots::default(),
+        };
+
+        let is_collection = |obj: Option<Reference>| {
+            let Some(obj) = obj else {
+                return None;
+            };
+
+            if !matches!(obj.r#type(), ObjectType::Collection) {
+                return None;
+            }
+
+            let rhs = unsafe { obj.get::<Collection>() };
+
+            Some((rhs.members.clone(), rhs.proto()))
+        };
+
+        v_table.inner.insert("truthy", Arc::new(move |_| None));
+        {
+            let members = members.clone();
+            v_table.inner.insert(
+                "uni_lhs",
+                Arc::new(move |obj| {
+                    let (rhs, _) = is_collection(obj)?;
+                    let mut union = HashMap::new();
+
+                    for (ident, member) in rhs.iter() {
+                        union.insert(ident.clone(), member.clone());
+                    }
+
+                    for (ident, member) in members.iter() {
+                        union.insert(ident.clone(), member.clone());
+                    }
+
+                    Some(Collection::erased(union))
+                }),
+            );
+        }
+        {
+            let members = members.clone();
+            v_table.inner.insert(
+                "ins_lhs",
+                Arc::new(move |obj| {
+                    let (rhs, _) = is_collection(obj)?;
+                    let mut intersection = HashMap::new();
+
+                    for (ident, member) in members.iter() {
+                        if rhs.contains_key(&ident) {
+                            intersection.insert(ident.clone(), member.clone());
+                        }
+                    }
+
+                    Some(Collection::erased(intersection))
+                }),
+            );
+        }
+        {
+            let members = members.clone();
+            let proto = proto.clone();
+            let is_equal = move |obj: Option<Reference>| {
+                let Some((rhs, rhs_proto)) = is_collection(obj) else {
+                    return false;
+                };
+
+                // Collections only equal each other if they inherit equal
+                // members as well.
+                let same_proto = match (&*proto, &rhs_proto) {
+                    (Some(lhs), Some(rhs)) => equals(lhs, rhs),
+                    (lhs, rhs) => lhs.is_none() && rhs.is_none(),
+                };
+
+                same_proto
+                    && members.len() == rhs.len()
+                    && members.iter().all(|(ident, member)| {
+                        rhs.get(ident)
+                            .map(|other| equals(member, other))
+                            .unwrap_or(false)
+                    })
+            };
+            let is_equal = Arc::new(is_equal);
+            {
+                let is_equal = is_equal.clone();
+                v_table.inner.insert(
+                    "eq_lhs",
+                    Arc::new(move |obj| Some(Bool::erased(is_equal(obj)))),
+                );
+            }
+            v_table.inner.insert(
+                "neq_lhs",
+                Arc::new(move |obj| Some(Bool::erased(!is_equal(obj)))),
+            );
+        }
+        {
+            let members = members.clone();
+            v_table.inner.insert(
+                "idx",
+                Arc::new(move |obj| {
+                    let obj = obj?;
+
+                    if !matches!(obj.r#type(), ObjectType::Str) {
+                        return None;
+                    }
+
+                    let name = unsafe { obj.get::<Str>().str.to_string() };
+
+                    members.get(&Ident { name }).cloned()
+                }),
+            );
+        }
+
+        v_table
+    }
+}
+
+impl Display for Collection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut dbg = f.debug_struct("Collection");
+        for (ident, member) in self.members.iter() {
+            dbg.field(&ident.name, member);
+        }
+        dbg.finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct Vector {
+    v_table: VTable,
+    pub elements: Contents<Vec<Reference>>,
+    pub frozen: Cell<bool>,
+}
+
+impl Object for Vector {
+    fn r#type(&self) -> ObjectType {
+        ObjectType::Vector
+    }
+
+    fn v_table(&self) -> &VTable {
+        &self.v_table
+    }
+
+    fn set_idx(&self, index: Reference, value: Reference) -> std::result::Result<(), Error> {
+        if !matches!(index.r#type(), ObjectType::Integer) {
+            return Err(Error::Type(format!(
+                "Vectors cannot be indexed by {}.",
+                index
+            )));
+        }
+
+        if self.frozen.get() {
+            return Err(Error::Eval(format!(
+                "Cannot assign to index {index} of a frozen vector."
+            )));
+        }
+
+        let len = self.elements.len();
+        let Some(Selection::One(idx)) = select(len, Some(index.clone())) else {
+            return Err(Error::Index(format!(
+                "Index {index} out of range for vector of length {len}."
+            )));
+        };
+
+        unsafe { (&mut (*self.elements.0.get()))[idx] = value };
+
+        Ok(())
+    }
+
+    fn freeze(&self) {
+        if self.frozen.get() {
+            return;
+        }
+
+        self.frozen.set(true);
+
+        for element in self.elements.iter() {
+            element.freeze();
+        }
+    }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        self.frozen.get().then(|| self.elements.to_vec())
+    }
+}
+
+impl Vector {
+    pub fn erased(elements: Vec<Reference>) -> Reference {
+        let elements = Contents::new(elements);
+        let v_table = Self::v_table_for(&elements);
+
+        Reference {
+            inner: erase(Arc::new(Shared::new(Vector {
+                v_table,
+                elements,
+                frozen: Cell::new(false),
+            }))),
+        }
+    }
+
+    fn v_table_for(elements: &Contents<Vec<Reference>>) -> VTable {
+        let mut v_table = VTable {
+            inner: Slots::default(),
+        };
+
+        let is_vec = |obj: Option<Reference>| {
+            let Some(obj) = obj else {
+                return None;
+            };
+
+            if !matches!(obj.r#type(), ObjectType::Vector) {
+                return None;
+            }
+
+            let rhs = unsafe { obj.get::<Vector>().elements.clone() };
+
+            Some(rhs)
+        };
+
+        v_table.inner.insert("truthy", Arc::new(move |_| None));
+        {
+            let elements = elements.clone();
+            v_table.inner.insert(
+                "add_lhs",
+                Arc::new(move |obj| {
+                    let rhs = is_vec(obj)?;
+
+                    let new = elements
+                        .iter()
+                        .cloned()
+                        .chain(rhs.iter().cloned())
+                        .collect();
+
+                    Some(Vector::erased(new))
+                }),
+            );
+        }
+        {
+            let elements = elements.clone();
+            v_table.inner.insert(
+                "len",
+                Arc::new(move |_| Some(Integer::erased(elements.len() as i32))),
+            );
+        }
+        {
+            let elements = elements.clone();
+            let is_equal = Arc::new(move |obj: Option<Reference>| {
+                let Some(rhs) = is_vec(obj) else {
+                    return false;
+                };
+
+                elements.len() == rhs.len()
+                    && elements
+                        .iter()
+                        .zip(rhs.iter())
+                        .all(|(lhs, rhs)| equals(lhs, rhs))
+            });
+            {
+                let is_equal = is_equal.clone();
+                v_table.inner.insert(
+                    "eq_lhs",
+                    Arc::new(move |obj| Some(Bool::erased(is_equal(obj)))),
+                );
+            }
+            v_table.inner.insert(
+                "neq_lhs",
+                Arc::new(move |obj| Some(Bool::erased(!is_equal(obj)))),
+            );
+        }
+        {
+            // Vectors are ordered lexicographically by their elements.
+            let ordering = {
+                let elements = elements.clone();
+                Arc::new(move |obj: Option<Reference>| -> Option<Ordering> {
+                    let rhs = is_vec(obj)?;
+
+                    for (lhs, rhs) in elements.iter().zip(rhs.iter()) {
+                        match compare(lhs, rhs)? {
+                            Ordering::Equal => continue,
+                            ordering => return Some(ordering),
+                        }
+                    }
+
+                    Some(elements.len().cmp(&rhs.len()))
+                })
+            };
+
+            for (slot, accepts) in ORDERINGS {
+                let ordering = ordering.clone();
+                v_table.inner.insert(
+                    slot,
+                    Arc::new(move |obj| Some(Bool::erased(accepts(ordering(obj)?)))),
+                );
+            }
+        }
+        {
+            let elements = elements.clone();
+            v_table.inner.insert(
                 "str",
                 Arc::new(move |_| {
                     let elements = elements
@@ -543,7 +1472,7 @@ impl Vector {
                                 .and_then(|obj| {
                                     if matches!(obj.r#type(), ObjectType::Str) {
                                         Ok(format!("{acc}{}, ", unsafe {
-                                            obj.get_mut::<Str>().str.as_ref()
+                                            obj.get::<Str>().str.as_ref()
                                         }))
                                     } else {
                                         Err(())
@@ -563,30 +1492,19 @@ impl Vector {
             let elements = elements.clone();
             v_table.inner.insert(
                 "idx",
-                Arc::new(move |obj| {
-                    let Some(obj) = obj else {
-                        return None;
-                    };
-
-                    if !matches!(obj.r#type(), ObjectType::Integer) {
-                        return None;
-                    }
-
-                    let rhs = unsafe { obj.get_mut::<Integer>().val };
-
-                    Some(
-                        elements
-                            .get(rhs as usize)
-                            .cloned()
-                            .unwrap_or(Unit::erased()),
-                    )
+                Arc::new(move |obj| match select(elements.len(), obj)? {
+                    Selection::One(idx) => Some(elements[idx].clone()),
+                    Selection::Many(indices) => Some(Vector::erased(
+                        indices
+                            .into_iter()
+                            .map(|idx| elements[idx].clone())
+                            .collect(),
+                    )),
                 }),
             );
         }
 
-        Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Vector { v_table, elements }))),
-        }
+        v_table
     }
 }
 
@@ -594,15 +1512,18 @@ impl Display for Vector {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut dbg = f.debug_list();
         for element in self.elements.iter() {
-            dbg.entry(element);
+            dbg.entry(&format_args!("{element}"));
         }
         dbg.finish()
     }
 }
 
+type BuiltinFn = dyn Fn(&mut Eval, Vec<Reference>) -> Result<Reference> + Send + Sync;
+
 pub struct Builtin {
     v_table: VTable,
-    r#fn: Arc<dyn Fn(Vec<Reference>) -> Result<Reference>>,
+    id: usize,
+    r#fn: Arc<BuiltinFn>,
 }
 
 impl Object for Builtin {
@@ -613,26 +1534,54 @@ impl Object for Builtin {
     fn v_table(&self) -> &VTable {
         &self.v_table
     }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(vec![])
+    }
 }
 
 impl Builtin {
-    pub fn erased(r#fn: impl Fn(Vec<Reference>) -> Result<Reference> + 'static) -> Reference {
+    pub fn erased(
+        r#fn: impl Fn(&mut Eval, Vec<Reference>) -> Result<Reference> + Send + Sync + 'static,
+    ) -> Reference {
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots::default(),
+        };
+
+        let id = next_id();
+
+        let is_same = move |obj: Option<Reference>| {
+            let Some(obj) = obj else {
+                return false;
+            };
+
+            matches!(obj.r#type(), ObjectType::Builtin)
+                && unsafe { obj.get::<Builtin>().id } == id
         };
 
         v_table.inner.insert("truthy", Arc::new(move |_| None));
 
+        v_table.inner.insert(
+            "eq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(is_same(obj)))),
+        );
+
+        v_table.inner.insert(
+            "neq_lhs",
+            Arc::new(move |obj| Some(Bool::erased(!is_same(obj)))),
+        );
+
         Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Builtin {
+            inner: erase(Arc::new(Shared::new(Builtin {
                 v_table,
+                id,
                 r#fn: Arc::new(r#fn),
             }))),
         }
     }
 
-    pub fn call(&self, args: Vec<Reference>) -> Result<Reference> {
-        (self.r#fn)(args)
+    pub fn call(&self, eval: &mut Eval, args: Vec<Reference>) -> Result<Reference> {
+        (self.r#fn)(eval, args)
     }
 }
 
@@ -662,15 +1611,20 @@ impl Object for Str {
     fn v_table(&self) -> &VTable {
         &self.v_table
     }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(vec![])
+    }
 }
 
 impl Str {
     pub fn erased(str: String) -> Reference {
         let mut v_table = VTable {
-            inner: HashMap::new(),
+            inner: Slots::default(),
         };
 
         let str: Arc<str> = Arc::from(str.as_str());
+        let len = str.chars().count();
 
         let is_str = |obj: Option<Reference>| -> Option<Arc<str>> {
             let Some(obj) = obj else {
@@ -681,7 +1635,7 @@ impl Str {
                 return None;
             }
 
-            let rhs = unsafe { obj.get_mut::<Str>().str.clone() };
+            let rhs = unsafe { obj.get::<Str>().str.clone() };
 
             Some(rhs)
         };
@@ -708,16 +1662,225 @@ impl Str {
                 .inner
                 .insert("str", Arc::new(move |_| Some(Str::erased(str.to_string()))));
         }
+        v_table
+            .inner
+            .insert("len", Arc::new(move |_| Some(Integer::erased(len as i32))));
         {
+            // Strings are indexed by characters rather than bytes. ASCII
+            // strings have one byte per character, other strings collect their
+            // characters the first time they are indexed.
             let str = str.clone();
+            let chars = OnceLock::new();
             v_table.inner.insert(
-                "len",
-                Arc::new(move |_| Some(Integer::erased(str.len() as i32))),
+                "idx",
+                Arc::new(move |obj| {
+                    let char_at = |idx: usize| {
+                        if len == str.len() {
+                            str.as_bytes()[idx] as char
+                        } else {
+                            chars.get_or_init(|| str.chars().collect::<Vec<_>>())[idx]
+                        }
+                    };
+
+                    match select(len, obj)? {
+                        Selection::One(idx) => Some(Str::erased(char_at(idx).to_string())),
+                        Selection::Many(indices) => {
+                            Some(Str::erased(indices.into_iter().map(char_at).collect()))
+                        }
+                    }
+                }),
+            );
+        }
+        {
+            let str = str.clone();
+            v_table.inner.insert(
+                "eq_lhs",
+                Arc::new(move |obj| Some(Bool::erased(is_str(obj).as_ref() == Some(&str)))),
+            );
+        }
+        {
+            let str = str.clone();
+            v_table.inner.insert(
+                "neq_lhs",
+                Arc::new(move |obj| Some(Bool::erased(is_str(obj).as_ref() != Some(&str)))),
+            );
+        }
+        // Strings are ordered lexicographically.
+        for (slot, accepts) in ORDERINGS {
+            let str = str.clone();
+            v_table.inner.insert(
+                slot,
+                Arc::new(move |obj| {
+                    let rhs = is_str(obj)?;
+                    Some(Bool::erased(accepts(str.cmp(&rhs))))
+                }),
+            );
+        }
+
+        Reference {
+            inner: erase(Arc::new(Shared::new(Str { v_table, str }))),
+        }
+    }
+}
+
+/// Unevaluated AST, as produced by `quote` and passed to macros.
+#[derive(Debug)]
+pub struct Quote {
+    v_table: VTable,
+    pub expression: Expression,
+}
+
+impl Object for Quote {
+    fn r#type(&self) -> ObjectType {
+        ObjectType::Quote
+    }
+
+    fn v_table(&self) -> &VTable {
+        &self.v_table
+    }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(vec![])
+    }
+}
+
+impl Quote {
+    pub fn erased(expression: Expression) -> Reference {
+        let mut v_table = VTable {
+            inner: Slots::default(),
+        };
+
+        {
+            let expression = expression.clone();
+            let is_equal = Arc::new(move |obj: Option<Reference>| {
+                let Some(obj) = obj else {
+                    return false;
+                };
+
+                // Quotes of the same code only differ in the names they made up.
+                matches!(obj.r#type(), ObjectType::Quote)
+                    && numbered(unsafe { &obj.get::<Quote>().expression })
+                        == numbered(&expression)
+            });
+            {
+                let is_equal = is_equal.clone();
+                v_table.inner.insert(
+                    "eq_lhs",
+                    Arc::new(move |obj| Some(Bool::erased(is_equal(obj)))),
+                );
+            }
+            v_table.inner.insert(
+                "neq_lhs",
+                Arc::new(move |obj| Some(Bool::erased(!is_equal(obj)))),
             );
         }
 
         Reference {
-            inner: erase(Arc::new(UnsafeCell::new(Str { v_table, str }))),
+            inner: erase(Arc::new(Shared::new(Quote {
+                v_table,
+                expression,
+            }))),
+        }
+    }
+}
+
+impl Display for Quote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("quote({:?})", self.expression))
+    }
+}
+
+/// The bounds of `obj[start:end:step]`, passed as index to the `idx` slot.
+#[derive(Debug)]
+pub struct Slice {
+    v_table: VTable,
+    pub start: Option<i32>,
+    pub end: Option<i32>,
+    pub step: Option<i32>,
+}
+
+impl Object for Slice {
+    fn r#type(&self) -> ObjectType {
+        ObjectType::Slice
+    }
+
+    fn v_table(&self) -> &VTable {
+        &self.v_table
+    }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(vec![])
+    }
+}
+
+impl Slice {
+    pub fn erased(start: Option<i32>, end: Option<i32>, step: Option<i32>) -> Reference {
+        let mut v_table = VTable {
+            inner: Slots::default(),
+        };
+
+        v_table.inner.insert("truthy", Arc::new(move |_| None));
+
+        Reference {
+            inner: erase(Arc::new(Shared::new(Slice {
+                v_table,
+                start,
+                end,
+                step,
+            }))),
+        }
+    }
+
+    /// The positions selected in a sequence of length `len`.
+    ///
+    /// Negative bounds count from the end and out of range bounds are clamped,
+    /// like in Python. Returns `None` if the step is zero.
+    fn indices(&self, len: usize) -> Option<Vec<usize>> {
+        let len = len as i64;
+        let step = self.step.unwrap_or(1) as i64;
+
+        if step == 0 {
+            return None;
+        }
+
+        let bound = |bound: i32, min: i64, max: i64| {
+            let bound = bound as i64;
+            let bound = if bound < 0 { bound + len } else { bound };
+            bound.clamp(min, max)
+        };
+
+        let (mut idx, end) = if step > 0 {
+            (
+                self.start.map_or(0, |start| bound(start, 0, len)),
+                self.end.map_or(len, |end| bound(end, 0, len)),
+            )
+        } else {
+            (
+                self.start
+                    .map_or(len - 1, |start| bound(start, -1, len - 1)),
+                self.end.map_or(-1, |end| bound(end, -1, len - 1)),
+            )
+        };
+
+        let mut indices = vec![];
+        while (step > 0 && idx < end) || (step < 0 && idx > end) {
+            indices.push(idx as usize);
+            idx += step;
+        }
+
+        Some(indices)
+    }
+}
+
+impl Display for Slice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bound = |bound: Option<i32>| bound.map(|b| b.to_string()).unwrap_or_default();
+
+        f.write_fmt(format_args!("{}:{}", bound(self.start), bound(self.end)))?;
+
+        match self.step {
+            Some(step) => f.write_fmt(format_args!(":{step}")),
+            None => Ok(()),
         }
     }
 }
@@ -727,3 +1890,167 @@ impl Display for Str {
         f.write_fmt(format_args!("{}", self.str))
     }
 }
+
+/// A tagged value created by the constructors of an `enum` declaration.
+#[derive(Debug)]
+pub struct Variant {
+    v_table: VTable,
+    pub enumeration: Arc<str>,
+    pub tag: Arc<str>,
+    pub fields: Arc<Vec<Reference>>,
+}
+
+impl Object for Variant {
+    fn r#type(&self) -> ObjectType {
+        ObjectType::Variant
+    }
+
+    fn v_table(&self) -> &VTable {
+        &self.v_table
+    }
+
+    fn immutable_references(&self) -> Option<Vec<Reference>> {
+        Some(self.fields.to_vec())
+    }
+}
+
+impl Variant {
+    /// The marker returned by `__next__` and the `next` builtin once an
+    /// iterator is exhausted.
+    pub fn done() -> Reference {
+        Variant::erased(Arc::from("Iteration"), Arc::from("Done"), vec![])
+    }
+
+    pub fn is_done(obj: &Reference) -> bool {
+        equals(obj, &Variant::done())
+    }
+
+    /// `Result.Ok(value)`, as returned by the `ok` builtin.
+    pub fn ok(value: Reference) -> Reference {
+        Variant::erased(Arc::from("Result"), Arc::from("Ok"), vec![value])
+    }
+
+    /// `Result.Err(error)`, as returned by the `err` builtin.
+    pub fn err(error: Reference) -> Reference {
+        Variant::erased(Arc::from("Result"), Arc::from("Err"), vec![error])
+    }
+
+    /// Splits a result into the value of an `Ok` or the error of an `Err`, or
+    /// returns `None` if `obj` is not a result.
+    pub fn as_result(obj: &Reference) -> Option<std::result::Result<Reference, Reference>> {
+        if !matches!(obj.r#type(), ObjectType::Variant) {
+            return None;
+        }
+
+        let variant = unsafe { obj.get::<Variant>() };
+
+        match (
+            &*variant.enumeration,
+            &*variant.tag,
+            variant.fields.as_slice(),
+        ) {
+            ("Result", "Ok", [value]) => Some(Ok(value.clone())),
+            ("Result", "Err", [error]) => Some(Err(error.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn erased(enumeration: Arc<str>, tag: Arc<str>, fields: Vec<Reference>) -> Reference {
+        let fields = Arc::new(fields);
+        let mut v_table = VTable {
+            inner: Slots::default(),
+        };
+
+        // Every variant is a value that is present, so all of them are truthy.
+        v_table
+            .inner
+            .insert("truthy", Arc::new(move |_| Some(Unit::erased())));
+        {
+            let enumeration = enumeration.clone();
+            let tag = tag.clone();
+            let fields = fields.clone();
+            let is_equal = Arc::new(move |obj: Option<Reference>| {
+                let Some(obj) = obj else {
+                    return false;
+                };
+
+                if !matches!(obj.r#type(), ObjectType::Variant) {
+                    return false;
+                }
+
+                let rhs = unsafe { obj.get::<Variant>() };
+
+                rhs.enumeration == enumeration
+                    && rhs.tag == tag
+                    && rhs.fields.len() == fields.len()
+                    && fields
+                        .iter()
+                        .zip(rhs.fields.iter())
+                        .all(|(lhs, rhs)| equals(lhs, rhs))
+            });
+            {
+                let is_equal = is_equal.clone();
+                v_table.inner.insert(
+                    "eq_lhs",
+                    Arc::new(move |obj| Some(Bool::erased(is_equal(obj)))),
+                );
+            }
+            v_table.inner.insert(
+                "neq_lhs",
+                Arc::new(move |obj| Some(Bool::erased(!is_equal(obj)))),
+            );
+        }
+        {
+            let enumeration = enumeration.clone();
+            let tag = tag.clone();
+            let fields = fields.clone();
+            v_table.inner.insert(
+                "str",
+                Arc::new(move |_| {
+                    let mut str = format!("{enumeration}.{tag}");
+
+                    if !fields.is_empty() {
+                        let fields = fields
+                            .iter()
+                            .map(|field| {
+                                let field = field.v_table().get("str")?(None)?;
+                                Some(unsafe { field.get::<Str>().str.to_string() })
+                            })
+                            .collect::<Option<Vec<_>>>()?;
+
+                        str = format!("{str}({})", fields.join(", "));
+                    }
+
+                    Some(Str::erased(str))
+                }),
+            );
+        }
+
+        Reference {
+            inner: erase(Arc::new(Shared::new(Variant {
+                v_table,
+                enumeration,
+                tag,
+                fields,
+            }))),
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}.{}", self.enumeration, self.tag))?;
+
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| field.to_string())
+            .collect::<Vec<_>>();
+
+        f.write_fmt(format_args!("({})", fields.join(", ")))
+    }
+}
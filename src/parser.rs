@@ -1,7 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    ast::{Bool, Expression, Ident, LetStatement, Literal, Program, ReturnStatement, Statement},
+    ast::{
+        Bool, Catch, Clause, EnumStatement, EnumVariant, Expression, FunctionClause, Ident,
+        ImportStatement, Imported, LetStatement, Literal, MatchArm, Member, Parameter, Pattern,
+        Program, ReturnStatement, Statement, ThrowStatement, Type,
+    },
     error::{Error, Result},
     lexer::Lexer,
     token::{Keyword, Operator, Token},
@@ -12,6 +16,15 @@ pub struct Parser {
     cur: Token,
     peek: Token,
     errors: Vec<Error>,
+    /// The names declared in each enclosing scope and whether they are
+    /// constant, used to reject assignments to constants while parsing.
+    scopes: Vec<HashMap<String, bool>>,
+    /// Whether the function body being parsed contains `yield`, `None`
+    /// outside of function bodies.
+    generator: Option<bool>,
+    /// The variants without fields of the enums declared so far, which
+    /// patterns match by name instead of binding the name.
+    variants: HashSet<String>,
 }
 
 impl Parser {
@@ -21,6 +34,9 @@ impl Parser {
             peek: lexer.next_token()?,
             lexer,
             errors: vec![],
+            scopes: vec![HashMap::new()],
+            generator: None,
+            variants: HashSet::new(),
         })
     }
 
@@ -50,8 +66,81 @@ impl Parser {
 
     fn parse_statement(&mut self) -> Result<Statement> {
         match self.cur {
-            Token::Keyword(Keyword::Let) => Ok(Statement::Let(self.parse_let()?)),
+            Token::Keyword(Keyword::Let) => {
+                let statement = self.parse_let()?;
+                self.declare(&statement.name, false)?;
+                Ok(Statement::Let(statement))
+            }
+            Token::Keyword(Keyword::Const) => {
+                let statement = self.parse_let()?;
+                self.declare(&statement.name, true)?;
+                Ok(Statement::Const(statement))
+            }
             Token::Keyword(Keyword::Return) => Ok(Statement::Return(self.parse_return()?)),
+            Token::Keyword(Keyword::Throw) => {
+                self.next_token()?;
+                let value = self.parse_expression(Precedence::Lowest)?;
+                if matches!(self.peek, Token::Semicolon) {
+                    self.next_token()?;
+                }
+                Ok(Statement::Throw(ThrowStatement { value }))
+            }
+            Token::Keyword(Keyword::Enum) => {
+                let statement = self.parse_enum()?;
+                self.declare(&statement.name, false)?;
+                Ok(Statement::Enum(statement))
+            }
+            Token::Keyword(Keyword::Import) => {
+                let statement = self.parse_import()?;
+                match &statement.imported {
+                    Imported::Module(alias) => self.declare(alias, false)?,
+                    Imported::Names(names) => {
+                        for name in names {
+                            self.declare(name, false)?;
+                        }
+                    }
+                }
+                Ok(Statement::Import(statement))
+            }
+            Token::Keyword(Keyword::Export) => {
+                if self.scopes.len() > 1 || self.generator.is_some() {
+                    return Err(Error::Import(
+                        "`export` can only be used at the top level of a module.".into(),
+                    ));
+                }
+
+                self.expect_peek(
+                    |t| {
+                        matches!(
+                            t,
+                            Token::Keyword(Keyword::Let | Keyword::Const | Keyword::Enum)
+                        )
+                    },
+                    Error::Import("Expected `let`, `const` or `enum` after `export`.".into()),
+                )?;
+
+                Ok(Statement::Export(Box::new(self.parse_statement()?)))
+            }
+            // `def Name { ... }` declares `Name` like `let Name = def { ... };`.
+            Token::Keyword(Keyword::Def) if matches!(self.peek, Token::Ident(_)) => {
+                self.next_token()?;
+                let Token::Ident(name) = &self.cur else {
+                    unreachable!()
+                };
+                let name = Ident { name: name.clone() };
+
+                let value = self.parse_definition()?;
+                if matches!(self.peek, Token::Semicolon) {
+                    self.next_token()?;
+                }
+
+                self.declare(&name, false)?;
+                Ok(Statement::Let(LetStatement {
+                    name,
+                    annotation: None,
+                    value,
+                }))
+            }
             Token::Semicolon => Ok(Statement::Empty),
             _ => {
                 let expression = self.parse_expression(Precedence::Lowest)?;
@@ -76,6 +165,206 @@ impl Parser {
         Ok(ReturnStatement { value })
     }
 
+    /// Parses `import "path" as alias;` and `import { name, ... } from "path";`.
+    fn parse_import(&mut self) -> Result<ImportStatement> {
+        let names = if matches!(self.peek, Token::LBrace) {
+            self.next_token()?;
+
+            let mut names: Vec<Ident> = vec![];
+
+            while let Token::Ident(name) = &self.peek {
+                let name = Ident { name: name.clone() };
+
+                if names.contains(&name) {
+                    return Err(Error::Import(format!(
+                        "Cannot import `{}` more than once.",
+                        name.name
+                    )));
+                }
+
+                names.push(name);
+                self.next_token()?;
+                if matches!(self.peek, Token::Comma) {
+                    self.next_token()?;
+                }
+            }
+
+            self.expect_peek(
+                |t| matches!(t, Token::RBrace),
+                Error::Import("Expected closing brace after imported names.".into()),
+            )?;
+            // `from` is only a keyword here, so it stays usable as a name.
+            self.expect_peek(
+                |t| matches!(t, Token::Ident(name) if name == "from"),
+                Error::Import("Expected `from` after imported names.".into()),
+            )?;
+
+            Some(names)
+        } else {
+            None
+        };
+
+        self.expect_peek(
+            |t| matches!(t, Token::Str(_)),
+            Error::Import("Expected module path.".into()),
+        )?;
+
+        let path = match &self.cur {
+            Token::Str(path) => path.clone(),
+            _ => unsafe { core::hint::unreachable_unchecked() },
+        };
+
+        let imported = match names {
+            Some(names) => Imported::Names(names),
+            None => {
+                self.expect_peek(
+                    |t| matches!(t, Token::Keyword(Keyword::As)),
+                    Error::Import("Expected `as` after module path.".into()),
+                )?;
+                self.expect_peek(
+                    |t| matches!(t, Token::Ident(_)),
+                    Error::Import("Expected identifier after `as`.".into()),
+                )?;
+
+                match &self.cur {
+                    Token::Ident(name) => Imported::Module(Ident { name: name.clone() }),
+                    _ => unsafe { core::hint::unreachable_unchecked() },
+                }
+            }
+        };
+
+        if matches!(self.peek, Token::Semicolon) {
+            self.next_token()?;
+        }
+
+        Ok(ImportStatement { path, imported })
+    }
+
+    fn parse_enum(&mut self) -> Result<EnumStatement> {
+        self.expect_peek(
+            |t| matches!(t, Token::Ident(_)),
+            Error::Enum("Expected identifier after `enum`".into()),
+        )?;
+
+        let name = match &self.cur {
+            Token::Ident(name) => Ident { name: name.clone() },
+            _ => unsafe { core::hint::unreachable_unchecked() },
+        };
+
+        self.expect_peek(
+            |t| matches!(t, Token::LBrace),
+            Error::Enum("Expected opening braces after enum name.".into()),
+        )?;
+
+        let mut variants: Vec<EnumVariant> = vec![];
+
+        self.next_token()?;
+
+        while let Token::Ident(variant) = &self.cur {
+            let variant = Ident {
+                name: variant.clone(),
+            };
+
+            let mut fields = vec![];
+
+            if matches!(self.peek, Token::LParen) {
+                self.next_token()?;
+                while let Token::Ident(field) = &self.peek {
+                    fields.push(Ident {
+                        name: field.clone(),
+                    });
+                    self.next_token()?;
+                    match self.peek {
+                        Token::Comma => self.next_token()?,
+                        Token::RParen => {}
+                        _ => {
+                            return Err(Error::Enum(
+                                "Expected `,` or `)` after variant field.".into(),
+                            ))
+                        }
+                    }
+                }
+
+                self.expect_peek(
+                    |t| matches!(t, Token::RParen),
+                    Error::Enum("Expected closing parentheses after variant fields.".into()),
+                )?;
+            }
+
+            if variants.iter().any(|v| v.name == variant) {
+                return Err(Error::Enum(format!(
+                    "Cannot define variant `{}` more than once.",
+                    variant.name
+                )));
+            }
+
+            variants.push(EnumVariant {
+                name: variant,
+                fields,
+            });
+
+            match self.peek {
+                Token::Comma => self.next_token()?,
+                Token::RBrace => {}
+                _ => {
+                    return Err(Error::Enum(
+                        "Expected `,` or `}` after enum variant.".into(),
+                    ))
+                }
+            }
+            self.next_token()?;
+        }
+
+        if !matches!(self.cur, Token::RBrace) {
+            return Err(Error::Enum(
+                "Expected closing braces at enum declaration".into(),
+            ));
+        }
+
+        self.variants.extend(
+            variants
+                .iter()
+                .filter(|variant| variant.fields.is_empty())
+                .map(|variant| variant.name.name.clone()),
+        );
+
+        Ok(EnumStatement { name, variants })
+    }
+
+    /// Records `name` as declared in the innermost scope.
+    fn declare(&mut self, name: &Ident, constant: bool) -> Result<()> {
+        let scope = self.scopes.last_mut().unwrap();
+
+        if scope.get(&name.name) == Some(&true) {
+            return Err(Error::Const(format!(
+                "Cannot redeclare constant `{}`.",
+                name.name
+            )));
+        }
+
+        scope.insert(name.name.clone(), constant);
+
+        Ok(())
+    }
+
+    fn is_const(&self, name: &Ident) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Records the names bound by `pattern` in the innermost scope.
+    fn declare_pattern(&mut self, pattern: &Pattern) -> Result<()> {
+        pattern
+            .bindings()
+            .into_iter()
+            .try_for_each(|name| self.declare(name, false))
+    }
+
+    /// Parses `let name = value;` and `const name: type = value;`.
     fn parse_let(&mut self) -> Result<LetStatement> {
         self.expect_peek(
             |t| matches!(t, Token::Ident(_)),
@@ -87,6 +376,8 @@ impl Parser {
             _ => unsafe { core::hint::unreachable_unchecked() },
         };
 
+        let annotation = self.parse_annotation()?;
+
         self.expect_peek(
             |t| matches!(t, Token::Operator(Operator::Assign)),
             Error::LetStatement("Expected assignment operator after identifier".into()),
@@ -100,7 +391,115 @@ impl Parser {
             Error::LetStatement("Expected semicolon at the end of statment".into()),
         )?;
 
-        Ok(LetStatement { name, value })
+        Ok(LetStatement {
+            name,
+            annotation,
+            value,
+        })
+    }
+
+    /// Parses the `: type` following the current token, if any.
+    fn parse_annotation(&mut self) -> Result<Option<Type>> {
+        if !matches!(self.peek, Token::Colon) {
+            return Ok(None);
+        }
+
+        self.next_token()?;
+        self.next_token()?;
+        Ok(Some(self.parse_type()?))
+    }
+
+    /// Parses `any`, `int`, `bool`, `str`, `unit`, `[type]`,
+    /// `{ name: type, ... }` or `fn(types) -> type`.
+    fn parse_type(&mut self) -> Result<Type> {
+        match &self.cur {
+            Token::Ident(name) => match name.as_str() {
+                "any" => Ok(Type::Any),
+                "int" => Ok(Type::Int),
+                "bool" => Ok(Type::Bool),
+                "str" => Ok(Type::Str),
+                "unit" => Ok(Type::Unit),
+                _ => Err(Error::Type(format!("Unknown type `{name}`."))),
+            },
+            Token::LBracket => {
+                self.next_token()?;
+                let element = self.parse_type()?;
+                self.expect_peek(
+                    |t| matches!(t, Token::RBracket),
+                    Error::Type("Expected `]` after element type.".into()),
+                )?;
+
+                Ok(Type::Vector(Box::new(element)))
+            }
+            Token::LBrace => {
+                let mut members: Vec<(Ident, Type)> = vec![];
+
+                while let Token::Ident(name) = &self.peek {
+                    let name = Ident { name: name.clone() };
+                    self.next_token()?;
+
+                    if members.iter().any(|(member, _)| *member == name) {
+                        return Err(Error::Type(format!(
+                            "Member `{}` appears more than once in type.",
+                            name.name
+                        )));
+                    }
+
+                    let Some(r#type) = self.parse_annotation()? else {
+                        return Err(Error::Type(format!(
+                            "Expected type of member `{}`.",
+                            name.name
+                        )));
+                    };
+                    members.push((name, r#type));
+
+                    if matches!(self.peek, Token::Comma) {
+                        self.next_token()?;
+                    }
+                }
+
+                self.expect_peek(
+                    |t| matches!(t, Token::RBrace),
+                    Error::Type("Expected `}` after member types.".into()),
+                )?;
+
+                Ok(Type::Record(members))
+            }
+            Token::Keyword(Keyword::Function) => {
+                self.expect_peek(
+                    |t| matches!(t, Token::LParen),
+                    Error::Type("Expected parameter types after `fn`.".into()),
+                )?;
+
+                let mut parameters = vec![];
+                self.next_token()?;
+                while !matches!(self.cur, Token::RParen) {
+                    parameters.push(self.parse_type()?);
+
+                    if matches!(self.peek, Token::Comma) {
+                        self.next_token()?;
+                    }
+                    self.next_token()?;
+                }
+
+                Ok(Type::Function {
+                    parameters,
+                    returns: Box::new(self.parse_returns()?.unwrap_or(Type::Any)),
+                })
+            }
+            _ => Err(Error::Type(format!("Expected type, found {:?}.", self.cur))),
+        }
+    }
+
+    /// Parses the `-> type` following the current token, if any.
+    fn parse_returns(&mut self) -> Result<Option<Type>> {
+        if !matches!(self.peek, Token::Operator(Operator::RightArrow)) {
+            return Ok(None);
+        }
+
+        self.next_token()?;
+        self.next_token()?;
+        Ok(Some(self.parse_type()?))
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression> {
@@ -112,15 +511,25 @@ impl Parser {
             Token::Int(_) => self.parse_int()?,
             Token::Str(_) => self.parse_str()?,
             Token::Keyword(Keyword::If) => self.parse_if()?,
+            Token::Keyword(Keyword::Match) => self.parse_match()?,
+            Token::Keyword(Keyword::Try) => self.parse_try()?,
             Token::Keyword(Keyword::True | Keyword::False) => self.parse_bool()?,
             Token::Keyword(Keyword::Function) => self.parse_function()?,
+            Token::Keyword(Keyword::Macro) => self.parse_macro()?,
             Token::Keyword(Keyword::Def) => self.parse_definition()?,
-            Token::Operator(Operator::Bang | Operator::Minus) => self.parse_prefix()?,
+            Token::Keyword(Keyword::Yield) => self.parse_yield()?,
+            Token::Keyword(Keyword::Spawn) => self.parse_spawn()?,
+            Token::Keyword(Keyword::Async) => self.parse_async()?,
+            Token::Keyword(Keyword::Await) => self.parse_await()?,
+            Token::Operator(Operator::Bang | Operator::Minus | Operator::LeftArrow) => {
+                self.parse_prefix()?
+            }
             Token::Semicolon
             | Token::Operator(_)
             | Token::Keyword(_)
             | Token::EOF
             | Token::Comma
+            | Token::Colon
             | Token::RParen
             | Token::RBrace
             | Token::RBracket
@@ -158,12 +567,17 @@ impl Parser {
                     self.next_token()?;
                     self.parse_index(lhs)?
                 }
+                Token::Operator(Operator::Hook) => {
+                    self.next_token()?;
+                    Expression::Propagate(Box::new(lhs))
+                }
                 Token::Semicolon
                 | Token::Str(_)
                 | Token::Operator(_)
                 | Token::Keyword(_)
                 | Token::EOF
                 | Token::Comma
+                | Token::Colon
                 | Token::Ident(_)
                 | Token::Int(_)
                 | Token::RParen
@@ -182,7 +596,24 @@ impl Parser {
 
         while !matches!(self.peek, Token::RBracket | Token::EOF) {
             self.next_token()?;
-            elements.push(self.parse_expression(Precedence::Lowest)?);
+            elements.push(self.parse_spreadable()?);
+
+            if elements.len() == 1 && matches!(self.peek, Token::Keyword(Keyword::For)) {
+                let element = Box::new(elements.pop().unwrap());
+                let clauses = self.parse_clauses()?;
+
+                self.expect_peek(
+                    |t| matches!(t, Token::RBracket),
+                    Error::Comprehension("Expected closing bracket after comprehension.".into()),
+                )?;
+
+                return Ok(Expression::Comprehension {
+                    key: None,
+                    element,
+                    clauses,
+                });
+            }
+
             if matches!(self.peek, Token::Comma) {
                 self.next_token()?;
             }
@@ -197,17 +628,81 @@ impl Parser {
     }
 
     fn parse_definition(&mut self) -> Result<Expression> {
+        // `def < Parent { ... }` delegates missing members to `Parent`.
+        let parent = if matches!(self.peek, Token::Operator(Operator::Less)) {
+            self.next_token()?;
+            self.next_token()?;
+            Some(Box::new(self.parse_expression(Precedence::Prefix)?))
+        } else {
+            None
+        };
+
         self.expect_peek(
             |t| matches!(t, Token::LBrace),
             Error::FunctionError("Expected opening braces after `def`.".into()),
         )?;
 
-        let mut members = HashMap::new();
-
         self.next_token()?;
 
-        while let Token::Ident(name) = &self.cur {
-            let name = name.clone();
+        // `def { key: value for ... }` builds the collection from a
+        // comprehension.
+        if !matches!(
+            (&self.cur, &self.peek),
+            (Token::RBrace | Token::Operator(Operator::Spread), _)
+                | (_, Token::Operator(Operator::Assign))
+        ) {
+            if parent.is_some() {
+                return Err(Error::Comprehension(
+                    "Comprehensions cannot have a parent.".into(),
+                ));
+            }
+
+            let key = Box::new(self.parse_expression(Precedence::Lowest)?);
+            self.expect_peek(
+                |t| matches!(t, Token::Colon),
+                Error::Comprehension("Expected `:` after key of comprehension.".into()),
+            )?;
+            self.next_token()?;
+            let element = Box::new(self.parse_expression(Precedence::Lowest)?);
+
+            if !matches!(self.peek, Token::Keyword(Keyword::For)) {
+                return Err(Error::Comprehension(
+                    "Expected `for` after value of comprehension.".into(),
+                ));
+            }
+            let clauses = self.parse_clauses()?;
+
+            self.expect_peek(
+                |t| matches!(t, Token::RBrace),
+                Error::Comprehension("Expected closing braces after comprehension.".into()),
+            )?;
+
+            let comprehension = Expression::Comprehension {
+                key: Some(key),
+                element,
+                clauses,
+            };
+
+            return Ok(comprehension);
+        }
+
+        let mut members = vec![];
+
+        loop {
+            let name = match &self.cur {
+                Token::Ident(name) => name.clone(),
+                Token::Operator(Operator::Spread) => {
+                    self.next_token()?;
+                    members.push(Member::Spread(self.parse_expression(Precedence::Lowest)?));
+
+                    if matches!(self.peek, Token::Comma) {
+                        self.next_token()?;
+                    }
+                    self.next_token()?;
+                    continue;
+                }
+                _ => break,
+            };
 
             self.expect_peek(
                 |t| matches!(t, Token::Operator(Operator::Assign)),
@@ -215,16 +710,22 @@ impl Parser {
             )?;
             self.next_token()?;
 
-            let member = self.parse_expression(Precedence::Lowest)?;
+            let name = Ident { name };
             if members
-                .insert(Ident { name: name.clone() }, member)
-                .is_some()
+                .iter()
+                .any(|member| matches!(member, Member::Named(member, _) if *member == name))
             {
                 return Err(Error::Collection(format!(
-                    "Cannot define `{name}` more than once as member.",
+                    "Cannot define `{}` more than once as member.",
+                    name.name
                 )));
             }
 
+            members.push(Member::Named(
+                name,
+                self.parse_expression(Precedence::Lowest)?,
+            ));
+
             if matches!(self.peek, Token::Comma) {
                 self.next_token()?;
             }
@@ -237,20 +738,126 @@ impl Parser {
             ));
         }
 
-        Ok(Expression::Literal(Literal::Collection { members }))
+        Ok(Expression::Literal(Literal::Collection { members, parent }))
+    }
+
+    /// Parses the `for` and `if` clauses of a comprehension, starting at the
+    /// `for` following its element.
+    fn parse_clauses(&mut self) -> Result<Vec<Clause>> {
+        self.scopes.push(HashMap::new());
+        let clauses = self.parse_clauses_in_scope();
+        self.scopes.pop();
+
+        clauses
+    }
+
+    fn parse_clauses_in_scope(&mut self) -> Result<Vec<Clause>> {
+        let mut clauses = vec![];
+
+        loop {
+            match self.peek {
+                Token::Keyword(Keyword::For) => {
+                    self.next_token()?;
+
+                    let Token::Ident(name) = &self.peek else {
+                        return Err(Error::Comprehension("Expected name after `for`.".into()));
+                    };
+                    let binding = Ident { name: name.clone() };
+                    self.next_token()?;
+
+                    self.expect_peek(
+                        |t| matches!(t, Token::Keyword(Keyword::In)),
+                        Error::Comprehension("Expected `in` after name of `for`.".into()),
+                    )?;
+                    self.next_token()?;
+
+                    let iterable = self.parse_expression(Precedence::Lowest)?;
+                    self.declare(&binding, false)?;
+
+                    clauses.push(Clause::For { binding, iterable });
+                }
+                Token::Keyword(Keyword::If) => {
+                    self.next_token()?;
+                    self.next_token()?;
+
+                    clauses.push(Clause::If(self.parse_expression(Precedence::Lowest)?));
+                }
+                _ => return Ok(clauses),
+            }
+        }
+    }
+
+    /// Parses an element of a vector literal or an argument, which can be
+    /// spread with `..`.
+    fn parse_spreadable(&mut self) -> Result<Expression> {
+        if !matches!(self.cur, Token::Operator(Operator::Spread)) {
+            return self.parse_expression(Precedence::Lowest);
+        }
+
+        self.next_token()?;
+
+        Ok(Expression::Spread(Box::new(
+            self.parse_expression(Precedence::Lowest)?,
+        )))
     }
 
     fn parse_index(&mut self, lhs: Expression) -> Result<Expression> {
         self.next_token()?;
-        let index = Box::new(self.parse_expression(Precedence::Lowest)?);
-        let indexee = Box::new(lhs);
+
+        let start = match self.cur {
+            Token::Colon => None,
+            _ => {
+                let index = Box::new(self.parse_expression(Precedence::Lowest)?);
+
+                if !matches!(self.peek, Token::Colon) {
+                    self.expect_peek(
+                        |t| matches!(t, Token::RBracket),
+                        Error::FunctionError("Expected closing bracket after index".into()),
+                    )?;
+
+                    return Ok(Expression::Indexed {
+                        indexee: Box::new(lhs),
+                        index,
+                    });
+                }
+
+                self.next_token()?;
+                Some(index)
+            }
+        };
+
+        let end = self.parse_slice_bound()?;
+
+        let step = match self.peek {
+            Token::Colon => {
+                self.next_token()?;
+                self.parse_slice_bound()?
+            }
+            _ => None,
+        };
 
         self.expect_peek(
             |t| matches!(t, Token::RBracket),
-            Error::FunctionError("Expected closing parentheses at function invocation".into()),
+            Error::FunctionError("Expected closing bracket after slice".into()),
         )?;
 
-        Ok(Expression::Indexed { indexee, index })
+        Ok(Expression::Sliced {
+            sliced: Box::new(lhs),
+            start,
+            end,
+            step,
+        })
+    }
+
+    /// Parses the bound following the current `:` of a slice, if there is one.
+    fn parse_slice_bound(&mut self) -> Result<Option<Box<Expression>>> {
+        if matches!(self.peek, Token::Colon | Token::RBracket) {
+            return Ok(None);
+        }
+
+        self.next_token()?;
+
+        Ok(Some(Box::new(self.parse_expression(Precedence::Lowest)?)))
     }
 
     fn parse_prefix(&mut self) -> Result<Expression> {
@@ -266,7 +873,27 @@ impl Parser {
 
         while !matches!(self.peek, Token::RParen) && !matches!(self.cur, Token::EOF) {
             self.next_token()?;
-            args.push(self.parse_expression(Precedence::Lowest)?);
+
+            let arg = match (&self.cur, &self.peek) {
+                (Token::Ident(name), Token::Colon) => {
+                    let name = Ident { name: name.clone() };
+                    self.next_token()?;
+                    self.next_token()?;
+
+                    Expression::Named {
+                        name,
+                        value: Box::new(self.parse_expression(Precedence::Lowest)?),
+                    }
+                }
+                _ if matches!(args.last(), Some(Expression::Named { .. })) => {
+                    return Err(Error::FunctionError(
+                        "Positional arguments cannot follow named arguments".into(),
+                    ));
+                }
+                _ => self.parse_spreadable()?,
+            };
+
+            args.push(arg);
             if matches!(self.peek, Token::Comma) {
                 self.next_token()?;
             }
@@ -303,18 +930,108 @@ impl Parser {
             )?;
         }
 
+        // `fn name(...)` binds the function to `name` in its clauses, which
+        // can be followed by more clauses `| name(...) { ... }`.
+        let name = match &self.peek {
+            Token::Ident(name) => {
+                let name = Ident { name: name.clone() };
+                self.next_token()?;
+                Some(name)
+            }
+            _ => None,
+        };
+
         self.expect_peek(
             |t| matches!(t, Token::LParen),
             Error::FunctionError("Expected parentheses after `fn` keyword".into()),
         )?;
 
-        let mut parameters = vec![];
+        let mut clauses = vec![self.parse_function_clause(name.as_ref(), &capture)?];
 
-        self.next_token()?;
-        while let Token::Ident(name) = &self.cur {
-            parameters.push(Ident { name: name.clone() });
-            if matches!(self.peek, Token::Comma) {
+        if let Some(name) = &name {
+            while matches!(self.peek, Token::Operator(Operator::Pipe)) {
                 self.next_token()?;
+                self.expect_peek(
+                    |t| matches!(t, Token::Ident(next) if *next == name.name),
+                    Error::FunctionError(format!(
+                        "Expected `{}` after `|` to start the next clause",
+                        name.name
+                    )),
+                )?;
+                self.expect_peek(
+                    |t| matches!(t, Token::LParen),
+                    Error::FunctionError("Expected parentheses after clause name".into()),
+                )?;
+
+                clauses.push(self.parse_function_clause(Some(name), &capture)?);
+            }
+        }
+
+        Ok(Expression::Literal(Literal::Function {
+            name,
+            clauses,
+            capture,
+        }))
+    }
+
+    /// Parses a clause of a function, from its `(` to the end of its body.
+    fn parse_function_clause(
+        &mut self,
+        name: Option<&Ident>,
+        capture: &[Ident],
+    ) -> Result<FunctionClause> {
+        let mut parameters: Vec<Parameter> = vec![];
+        let mut rest = None;
+
+        self.next_token()?;
+        loop {
+            match &self.cur {
+                // `..rest` collects the remaining positional arguments.
+                Token::Operator(Operator::Spread) if rest.is_none() => {
+                    let Token::Ident(name) = &self.peek else {
+                        return Err(Error::FunctionError(
+                            "Expected name of rest parameter".into(),
+                        ));
+                    };
+
+                    rest = Some(Ident { name: name.clone() });
+                    self.next_token()?;
+                }
+                Token::RParen => break,
+                _ if rest.is_none() => {
+                    let pattern = self.parse_pattern()?;
+                    let annotation = self.parse_annotation()?;
+
+                    let default = if matches!(self.peek, Token::Operator(Operator::Assign)) {
+                        self.next_token()?;
+                        self.next_token()?;
+                        Some(self.parse_expression(Precedence::Lowest)?)
+                    } else if parameters.iter().any(|p| p.default.is_some()) {
+                        return Err(Error::FunctionError(
+                            "Parameters without default cannot follow parameters with default"
+                                .into(),
+                        ));
+                    } else {
+                        None
+                    };
+
+                    parameters.push(Parameter {
+                        pattern,
+                        annotation,
+                        default,
+                    });
+                }
+                _ => break,
+            }
+
+            match self.peek {
+                Token::Comma => self.next_token()?,
+                Token::RParen => {}
+                _ => {
+                    return Err(Error::FunctionError(
+                        "Expected `,` or `)` after parameter".into(),
+                    ))
+                }
             }
             self.next_token()?;
         }
@@ -325,18 +1042,232 @@ impl Parser {
             ));
         }
 
+        let returns = self.parse_returns()?;
+
+        // Function bodies only see their parameters, captures and name. Only
+        // captures of constants are constant, unless shadowed by a parameter.
+        let captured = capture
+            .iter()
+            .map(|ident| (ident.name.clone(), self.is_const(ident)))
+            .collect::<Vec<_>>();
+        let scopes = std::mem::replace(
+            &mut self.scopes,
+            vec![captured
+                .into_iter()
+                .chain(
+                    parameters
+                        .iter()
+                        .flat_map(|parameter| parameter.pattern.bindings())
+                        .chain(rest.iter())
+                        .chain(name)
+                        .map(|ident| (ident.name.clone(), false)),
+                )
+                .collect()],
+        );
+        let generator = self.generator.replace(false);
+
+        let clause = self.parse_guard().and_then(|guard| {
+            self.expect_peek(
+                |t| matches!(t, Token::LBrace),
+                Error::FunctionError("Expected function body".into()),
+            )?;
+
+            Ok((guard, self.parse_block()?))
+        });
+
+        self.scopes = scopes;
+        let generator = std::mem::replace(&mut self.generator, generator) == Some(true);
+        let (guard, body) = clause?;
+
+        Ok(FunctionClause {
+            parameters,
+            rest,
+            returns,
+            guard,
+            generator,
+            r#async: false,
+            body: Box::new(body),
+        })
+    }
+
+    /// Parses the `if condition` following the current token, if any.
+    fn parse_guard(&mut self) -> Result<Option<Expression>> {
+        if !matches!(self.peek, Token::Keyword(Keyword::If)) {
+            return Ok(None);
+        }
+
+        self.next_token()?;
+        self.next_token()?;
+        Ok(Some(self.parse_expression(Precedence::Lowest)?))
+    }
+
+    /// Parses `macro(parameters) { body }`.
+    fn parse_macro(&mut self) -> Result<Expression> {
+        self.expect_peek(
+            |t| matches!(t, Token::LParen),
+            Error::FunctionError("Expected parentheses after `macro` keyword".into()),
+        )?;
+
+        let mut parameters = vec![];
+        while let Token::Ident(name) = &self.peek {
+            parameters.push(Ident { name: name.clone() });
+            self.next_token()?;
+            match self.peek {
+                Token::Comma => self.next_token()?,
+                Token::RParen => {}
+                _ => {
+                    return Err(Error::FunctionError(
+                        "Expected `,` or `)` after macro parameter".into(),
+                    ))
+                }
+            }
+        }
+
+        self.expect_peek(
+            |t| matches!(t, Token::RParen),
+            Error::FunctionError("Expected closing parentheses at macro declaration".into()),
+        )?;
         self.expect_peek(
             |t| matches!(t, Token::LBrace),
-            Error::FunctionError("Expected function body".into()),
+            Error::FunctionError("Expected macro body".into()),
         )?;
 
+        let scopes = std::mem::replace(
+            &mut self.scopes,
+            vec![parameters
+                .iter()
+                .map(|ident| (ident.name.clone(), false))
+                .collect()],
+        );
+        let body = self.parse_block();
+        self.scopes = scopes;
+
+        Ok(Expression::Literal(Literal::Macro {
+            parameters,
+            body: Box::new(body?),
+        }))
+    }
+
+    fn parse_try(&mut self) -> Result<Expression> {
+        self.expect_peek(
+            |t| matches!(t, Token::LBrace),
+            Error::Try("Expected block after `try`".into()),
+        )?;
         let body = Box::new(self.parse_block()?);
 
-        Ok(Expression::Literal(Literal::Function {
-            parameters,
+        let catch = if matches!(self.peek, Token::Keyword(Keyword::Catch)) {
+            self.next_token()?;
+
+            let binding = match &self.peek {
+                Token::Ident(name) => {
+                    let binding = Ident { name: name.clone() };
+                    self.next_token()?;
+                    Some(binding)
+                }
+                _ => None,
+            };
+
+            self.expect_peek(
+                |t| matches!(t, Token::LBrace),
+                Error::Try("Expected block after `catch`".into()),
+            )?;
+
+            self.scopes.push(HashMap::new());
+            let body = match &binding {
+                Some(binding) => self.declare(binding, false),
+                None => Ok(()),
+            }
+            .and_then(|_| self.parse_block());
+            self.scopes.pop();
+
+            Some(Catch {
+                binding,
+                body: Box::new(body?),
+            })
+        } else {
+            None
+        };
+
+        let finally = if matches!(self.peek, Token::Keyword(Keyword::Finally)) {
+            self.next_token()?;
+            self.expect_peek(
+                |t| matches!(t, Token::LBrace),
+                Error::Try("Expected block after `finally`".into()),
+            )?;
+            Some(Box::new(self.parse_block()?))
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(Error::Try(
+                "Expected `catch` or `finally` after `try` block".into(),
+            ));
+        }
+
+        Ok(Expression::Try {
             body,
-            capture,
-        }))
+            catch,
+            finally,
+        })
+    }
+
+    fn parse_yield(&mut self) -> Result<Expression> {
+        let Some(generator) = self.generator.as_mut() else {
+            return Err(Error::FunctionError(
+                "`yield` can only be used inside of functions".into(),
+            ));
+        };
+        *generator = true;
+
+        self.next_token()?;
+
+        Ok(Expression::Yield(Box::new(
+            self.parse_expression(Precedence::Lowest)?,
+        )))
+    }
+
+    /// Parses `spawn function`.
+    fn parse_spawn(&mut self) -> Result<Expression> {
+        self.next_token()?;
+
+        Ok(Expression::Spawn(Box::new(
+            self.parse_expression(Precedence::Lowest)?,
+        )))
+    }
+
+    /// Parses `async fn ...`.
+    fn parse_async(&mut self) -> Result<Expression> {
+        self.expect_peek(
+            |t| matches!(t, Token::Keyword(Keyword::Function)),
+            Error::FunctionError("Expected `fn` after `async`".into()),
+        )?;
+
+        let mut function = self.parse_function()?;
+        let Expression::Literal(Literal::Function { clauses, .. }) = &mut function else {
+            unreachable!()
+        };
+
+        for clause in clauses {
+            if clause.generator {
+                return Err(Error::FunctionError(
+                    "Async functions cannot `yield`".into(),
+                ));
+            }
+
+            clause.r#async = true;
+        }
+
+        Ok(function)
+    }
+
+    /// Parses `await future`.
+    fn parse_await(&mut self) -> Result<Expression> {
+        self.next_token()?;
+
+        Ok(Expression::Await(Box::new(
+            self.parse_expression(Precedence::Prefix)?,
+        )))
     }
 
     fn parse_if(&mut self) -> Result<Expression> {
@@ -373,10 +1304,205 @@ impl Parser {
         })
     }
 
+    fn parse_match(&mut self) -> Result<Expression> {
+        self.next_token()?;
+        let subject = Box::new(self.parse_expression(Precedence::Lowest)?);
+
+        self.expect_peek(
+            |t| matches!(t, Token::LBrace),
+            Error::Match("Expected match arms after subject".into()),
+        )?;
+
+        let mut arms = vec![];
+
+        while !matches!(self.peek, Token::RBrace | Token::EOF) {
+            self.next_token()?;
+            let pattern = self.parse_pattern()?;
+
+            self.expect_peek(
+                |t| matches!(t, Token::Operator(Operator::RightArrow)),
+                Error::Match("Expected `->` after pattern".into()),
+            )?;
+            self.next_token()?;
+
+            self.scopes.push(HashMap::new());
+            let body = self
+                .declare_pattern(&pattern)
+                .and_then(|_| self.parse_expression(Precedence::Lowest));
+            self.scopes.pop();
+
+            arms.push(MatchArm {
+                pattern,
+                body: body?,
+            });
+
+            match self.peek {
+                Token::Comma => self.next_token()?,
+                Token::RBrace => {}
+                _ => {
+                    return Err(Error::Match(
+                        "Expected `,` or `}` after match arm.".into(),
+                    ))
+                }
+            }
+        }
+
+        self.expect_peek(
+            |t| matches!(t, Token::RBrace),
+            Error::Match("Expected closing braces after match arms".into()),
+        )?;
+
+        Ok(Expression::Match { subject, arms })
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        let pattern = match &self.cur {
+            Token::Ident(name) if name == "_" => Pattern::Wildcard,
+            Token::Ident(name) => {
+                let name = Ident { name: name.clone() };
+
+                match self.peek {
+                    Token::Operator(Operator::Dot) => {
+                        self.next_token()?;
+                        self.expect_peek(
+                            |t| matches!(t, Token::Ident(_)),
+                            Error::Match("Expected variant name after `.`".into()),
+                        )?;
+
+                        let Token::Ident(tag) = &self.cur else {
+                            unreachable!()
+                        };
+                        let tag = Ident { name: tag.clone() };
+
+                        Pattern::Variant {
+                            enumeration: Some(name),
+                            tag,
+                            fields: self.parse_pattern_fields()?,
+                        }
+                    }
+                    Token::LParen => Pattern::Variant {
+                        enumeration: None,
+                        tag: name,
+                        fields: self.parse_pattern_fields()?,
+                    },
+                    _ if self.variants.contains(&name.name) => Pattern::Variant {
+                        enumeration: None,
+                        tag: name,
+                        fields: vec![],
+                    },
+                    _ => Pattern::Binding(name),
+                }
+            }
+            Token::Int(i) => Pattern::Literal(Literal::Int(*i)),
+            Token::Operator(Operator::Minus) => {
+                self.expect_peek(
+                    |t| matches!(t, Token::Int(_)),
+                    Error::Match("Expected integer after `-` in pattern".into()),
+                )?;
+
+                let Token::Int(i) = self.cur else {
+                    unreachable!()
+                };
+
+                Pattern::Literal(Literal::Int(-i))
+            }
+            Token::Str(str) => Pattern::Literal(Literal::String(str.clone())),
+            Token::Keyword(Keyword::True) => Pattern::Literal(Literal::Bool(Bool::True)),
+            Token::Keyword(Keyword::False) => Pattern::Literal(Literal::Bool(Bool::False)),
+            Token::LBracket => {
+                let mut elements = vec![];
+                let mut rest = None;
+
+                while !matches!(self.peek, Token::RBracket | Token::EOF) {
+                    self.next_token()?;
+
+                    if rest.is_some() {
+                        return Err(Error::Match(
+                            "Expected the rest pattern to come last".into(),
+                        ));
+                    }
+
+                    if matches!(self.cur, Token::Operator(Operator::Spread)) {
+                        self.expect_peek(
+                            |t| matches!(t, Token::Ident(_)),
+                            Error::Match("Expected name of rest pattern after `..`".into()),
+                        )?;
+
+                        let Token::Ident(name) = &self.cur else {
+                            unreachable!()
+                        };
+                        rest = Some(Ident { name: name.clone() });
+                    } else {
+                        elements.push(self.parse_pattern()?);
+                    }
+
+                    match self.peek {
+                        Token::Comma => self.next_token()?,
+                        Token::RBracket => {}
+                        _ => {
+                            return Err(Error::Match(
+                                "Expected `,` or `]` after element pattern".into(),
+                            ))
+                        }
+                    }
+                }
+
+                self.expect_peek(
+                    |t| matches!(t, Token::RBracket),
+                    Error::Match("Expected closing bracket after vector pattern".into()),
+                )?;
+
+                Pattern::Vector { elements, rest }
+            }
+            _ => {
+                return Err(Error::Match(format!(
+                    "Unexpected token {:?} in pattern",
+                    self.cur
+                )))
+            }
+        };
+
+        Ok(pattern)
+    }
+
+    /// Parses the optional `(a, b)` field patterns of a variant pattern.
+    fn parse_pattern_fields(&mut self) -> Result<Vec<Pattern>> {
+        let mut fields = vec![];
+
+        if !matches!(self.peek, Token::LParen) {
+            return Ok(fields);
+        }
+
+        self.next_token()?;
+
+        while !matches!(self.peek, Token::RParen | Token::EOF) {
+            self.next_token()?;
+            fields.push(self.parse_pattern()?);
+            match self.peek {
+                Token::Comma => self.next_token()?,
+                Token::RParen => {}
+                _ => {
+                    return Err(Error::Match(
+                        "Expected `,` or `)` after field pattern".into(),
+                    ))
+                }
+            }
+        }
+
+        self.expect_peek(
+            |t| matches!(t, Token::RParen),
+            Error::Match("Expected closing parentheses after variant pattern".into()),
+        )?;
+
+        Ok(fields)
+    }
+
     fn parse_block(&mut self) -> Result<Expression> {
         self.next_token()?;
         let mut statements = vec![];
 
+        self.scopes.push(HashMap::new());
+
         while self.cur != Token::RBrace && self.cur != Token::EOF {
             match self.parse_statement() {
                 Ok(statement) => statements.push(statement),
@@ -386,6 +1512,8 @@ impl Parser {
             self.next_token()?;
         }
 
+        self.scopes.pop();
+
         Ok(Expression::Block { statements })
     }
 
@@ -402,6 +1530,20 @@ impl Parser {
     fn parse_infix_operator(&mut self, lhs: Expression) -> Result<Expression> {
         let precedence = self.cur_precedence();
         let operator = self.cur.clone();
+
+        if let (
+            Token::Operator(Operator::Assign | Operator::PlusEqual | Operator::MinusEqual),
+            Expression::Ident(name),
+        ) = (&operator, &lhs)
+        {
+            if self.is_const(name) {
+                return Err(Error::Const(format!(
+                    "Cannot assign to constant `{}`.",
+                    name.name
+                )));
+            }
+        }
+
         self.next_token()?;
         let lhs = Box::new(lhs);
         let rhs = Box::new(self.parse_expression(precedence)?);
@@ -466,7 +1608,9 @@ impl Parser {
 
     fn precendence(t: &Token) -> Precedence {
         match t {
-            Token::Operator(Operator::Dot) | Token::LBracket => Precedence::Access,
+            Token::Operator(Operator::Dot) | Token::Operator(Operator::Hook) | Token::LBracket => {
+                Precedence::Access
+            }
             Token::LParen => Precedence::Invoke,
             Token::Operator(Operator::Divide) | Token::Operator(Operator::Multiply) => {
                 Precedence::Product
@@ -477,7 +1621,8 @@ impl Parser {
             | Token::Operator(Operator::Pipe) => Precedence::Sum,
             Token::Operator(Operator::Assign)
             | Token::Operator(Operator::PlusEqual)
-            | Token::Operator(Operator::MinusEqual) => Precedence::Assign,
+            | Token::Operator(Operator::MinusEqual)
+            | Token::Operator(Operator::LeftArrow) => Precedence::Assign,
             Token::Operator(Operator::Equal) | Token::Operator(Operator::NotEqual) => {
                 Precedence::Equals
             }
@@ -493,6 +1638,7 @@ impl Parser {
             | Token::Int(_)
             | Token::Str(_)
             | Token::Comma
+            | Token::Colon
             | Token::LBrace
             | Token::RParen
             | Token::RBrace
@@ -587,10 +1733,12 @@ mod test {
             Statement::Expression(Expression::Literal(Literal::Int(50))),
             Statement::Let(LetStatement {
                 name: Ident { name: "x".into() },
+                annotation: None,
                 value: Expression::Literal(Literal::Int(0)),
             }),
             Statement::Let(LetStatement {
                 name: Ident { name: "y".into() },
+                annotation: None,
                 value: Expression::Infix {
                     operator: Token::Operator(Operator::Plus),
                     lhs: Box::new(Expression::Literal(Literal::Int(1))),
@@ -599,6 +1747,7 @@ mod test {
             }),
             Statement::Let(LetStatement {
                 name: Ident { name: "yes".into() },
+                annotation: None,
                 value: Expression::Prefix {
                     operator: Token::Operator(Operator::Bang),
                     operand: Box::new(Expression::Literal(Literal::Bool(Bool::False))),
@@ -611,6 +1760,7 @@ mod test {
             }),
             Statement::Let(LetStatement {
                 name: Ident { name: "a".into() },
+                annotation: None,
                 value: Expression::Infix {
                     operator: Token::Operator(Operator::Assign),
                     lhs: Box::new(Expression::Infix {
@@ -641,6 +1791,7 @@ mod test {
                     statements: vec![
                         Statement::Let(LetStatement {
                             name: Ident { name: "y".into() },
+                            annotation: None,
                             value: Expression::Infix {
                                 operator: Token::Operator(Operator::Plus),
                                 lhs: Box::new(Expression::Literal(Literal::Int(1))),
@@ -664,6 +1815,7 @@ mod test {
                         statements: vec![
                             Statement::Let(LetStatement {
                                 name: Ident { name: "z".into() },
+                                annotation: None,
                                 value: Expression::Infix {
                                     operator: Token::Operator(Operator::Plus),
                                     lhs: Box::new(Expression::Literal(Literal::Int(1))),
@@ -682,49 +1834,109 @@ mod test {
             }),
             Statement::Let(LetStatement {
                 name: Ident { name: "add".into() },
+                annotation: None,
                 value: Expression::Literal(Literal::Function {
-                    parameters: vec![Ident { name: "a".into() }, Ident { name: "b".into() }],
-                    body: Box::new(Expression::Block {
-                        statements: vec![Statement::Expression(Expression::Infix {
-                            operator: Token::Operator(Operator::Plus),
-                            lhs: Box::new(Expression::Ident(Ident { name: "a".into() })),
-                            rhs: Box::new(Expression::Ident(Ident { name: "b".into() })),
-                        })],
-                    }),
+                    name: None,
+                    clauses: vec![FunctionClause {
+                        parameters: vec![
+                            Parameter {
+                                pattern: Pattern::Binding(Ident { name: "a".into() }),
+                                annotation: None,
+                                default: None,
+                            },
+                            Parameter {
+                                pattern: Pattern::Binding(Ident { name: "b".into() }),
+                                annotation: None,
+                                default: None,
+                            },
+                        ],
+                        rest: None,
+                        returns: None,
+                        guard: None,
+                        generator: false,
+                        r#async: false,
+                        body: Box::new(Expression::Block {
+                            statements: vec![Statement::Expression(Expression::Infix {
+                                operator: Token::Operator(Operator::Plus),
+                                lhs: Box::new(Expression::Ident(Ident { name: "a".into() })),
+                                rhs: Box::new(Expression::Ident(Ident { name: "b".into() })),
+                            })],
+                        }),
+                    }],
+                    capture: vec![],
                 }),
             }),
             Statement::Let(LetStatement {
                 name: Ident {
                     name: "hello".into(),
                 },
+                annotation: None,
                 value: Expression::Literal(Literal::Function {
-                    parameters: vec![],
-                    body: Box::new(Expression::Block { statements: vec![] }),
+                    name: None,
+                    clauses: vec![FunctionClause {
+                        parameters: vec![],
+                        rest: None,
+                        returns: None,
+                        guard: None,
+                        generator: false,
+                        r#async: false,
+                        body: Box::new(Expression::Block { statements: vec![] }),
+                    }],
+                    capture: vec![],
                 }),
             }),
             Statement::Let(LetStatement {
                 name: Ident { name: "boo".into() },
+                annotation: None,
                 value: Expression::Literal(Literal::Function {
-                    parameters: vec![Ident { name: "a".into() }],
-                    body: Box::new(Expression::Block { statements: vec![] }),
+                    name: None,
+                    clauses: vec![FunctionClause {
+                        parameters: vec![Parameter {
+                            pattern: Pattern::Binding(Ident { name: "a".into() }),
+                            annotation: None,
+                            default: None,
+                        }],
+                        rest: None,
+                        returns: None,
+                        guard: None,
+                        generator: false,
+                        r#async: false,
+                        body: Box::new(Expression::Block { statements: vec![] }),
+                    }],
+                    capture: vec![],
                 }),
             }),
             Statement::Let(LetStatement {
                 name: Ident { name: "moo".into() },
+                annotation: None,
                 value: Expression::Invoked {
                     invoked: Box::new(Expression::Literal(Literal::Function {
-                        parameters: vec![Ident { name: "a".into() }],
-                        body: Box::new(Expression::Block {
-                            statements: vec![Statement::Expression(Expression::Ident(Ident {
-                                name: "a".into(),
-                            }))],
-                        }),
+                        name: None,
+                        clauses: vec![FunctionClause {
+                            parameters: vec![Parameter {
+                                pattern: Pattern::Binding(Ident { name: "a".into() }),
+                                annotation: None,
+                                default: None,
+                            }],
+                            rest: None,
+                            returns: None,
+                            guard: None,
+                            generator: false,
+                            r#async: false,
+                            body: Box::new(Expression::Block {
+                                statements: vec![Statement::Expression(Expression::Ident(Ident {
+                                    name: "a".into(),
+                                }))],
+                            }),
+                        }],
+                        capture: vec![],
                     })),
                     args: vec![Expression::Literal(Literal::Int(1))],
                 },
             }),
             Statement::Let(LetStatement {
                 name: Ident { name: "moo".into() },
+                annotation: None,
                 value: Expression::Invoked {
                     invoked: Box::new(Expression::Ident(Ident { name: "boo".into() })),
                     args: vec![Expression::Infix {
@@ -749,4 +1961,185 @@ mod test {
         assert_eq!(program.statements, expected);
         assert_eq!(program.errors, errors);
     }
+
+    #[test]
+    fn test_enum_and_match() {
+        let input = r#"
+        enum Shape { Circle(r), Empty }
+        match s { Shape.Circle(r) -> r, Empty() -> 0, _ -> x }
+        "#;
+
+        let expected = vec![
+            Statement::Enum(EnumStatement {
+                name: Ident {
+                    name: "Shape".into(),
+                },
+                variants: vec![
+                    EnumVariant {
+                        name: Ident {
+                            name: "Circle".into(),
+                        },
+                        fields: vec![Ident { name: "r".into() }],
+                    },
+                    EnumVariant {
+                        name: Ident {
+                            name: "Empty".into(),
+                        },
+                        fields: vec![],
+                    },
+                ],
+            }),
+            Statement::Expression(Expression::Match {
+                subject: Box::new(Expression::Ident(Ident { name: "s".into() })),
+                arms: vec![
+                    MatchArm {
+                        pattern: Pattern::Variant {
+                            enumeration: Some(Ident {
+                                name: "Shape".into(),
+                            }),
+                            tag: Ident {
+                                name: "Circle".into(),
+                            },
+                            fields: vec![Pattern::Binding(Ident { name: "r".into() })],
+                        },
+                        body: Expression::Ident(Ident { name: "r".into() }),
+                    },
+                    MatchArm {
+                        pattern: Pattern::Variant {
+                            enumeration: None,
+                            tag: Ident {
+                                name: "Empty".into(),
+                            },
+                            fields: vec![],
+                        },
+                        body: Expression::Literal(Literal::Int(0)),
+                    },
+                    MatchArm {
+                        pattern: Pattern::Wildcard,
+                        body: Expression::Ident(Ident { name: "x".into() }),
+                    },
+                ],
+            }),
+        ];
+
+        let program = Parser::new(Lexer::new(input))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        assert_eq!(program.statements, expected);
+        assert_eq!(program.errors, vec![]);
+    }
+
+    #[test]
+    fn test_enum_errors() {
+        for input in ["enum Shape { Circle(a b) }", "enum Shape { Circle Empty }"] {
+            let mut parser = Parser::new(Lexer::new(input)).unwrap();
+
+            assert!(matches!(parser.parse_statement(), Err(Error::Enum(_))));
+        }
+
+        for input in [
+            "match s { Pair(a b) -> a }",
+            "match v { [a b] -> a }",
+            "match n { 0 -> a 1 -> b }",
+        ] {
+            let mut parser = Parser::new(Lexer::new(input)).unwrap();
+
+            assert!(matches!(parser.parse_statement(), Err(Error::Match(_))));
+        }
+    }
+
+    #[test]
+    fn test_parameter_errors() {
+        for input in [
+            "fn(a = 1, b) { a };",
+            "fn(..a, b) { a };",
+            "fn(..) { 1 };",
+            "fn(a b) { a };",
+            "macro(a b) { a };",
+            "f(a: 1, 2);",
+        ] {
+            let mut parser = Parser::new(Lexer::new(input)).unwrap();
+
+            assert!(matches!(
+                parser.parse_statement(),
+                Err(Error::FunctionError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_import_errors() {
+        for input in [
+            "import a as b;",
+            r#"import "a.mk";"#,
+            r#"import { a, a } from "a.mk";"#,
+            r#"import { a } "a.mk";"#,
+            "export a;",
+        ] {
+            let mut parser = Parser::new(Lexer::new(input)).unwrap();
+
+            assert!(matches!(parser.parse_statement(), Err(Error::Import(_))));
+        }
+
+        let program = Parser::new(Lexer::new("fn() { export let a = 1; };"))
+            .unwrap()
+            .parse_program()
+            .unwrap();
+
+        assert!(matches!(program.errors.as_slice(), [Error::Import(_)]));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let input = "let f: fn([int], { n: str }) -> bool = fn(a: [int], b: { n: str } = c) -> bool { true };";
+
+        let mut parser = Parser::new(Lexer::new(input)).unwrap();
+        let Ok(Statement::Let(LetStatement {
+            annotation: Some(annotation),
+            value: Expression::Literal(Literal::Function { mut clauses, .. }),
+            ..
+        })) = parser.parse_statement()
+        else {
+            panic!("Expected annotated function");
+        };
+        let FunctionClause {
+            parameters,
+            returns,
+            ..
+        } = clauses.remove(0);
+
+        let vector = Type::Vector(Box::new(Type::Int));
+        let record = Type::Record(vec![(Ident { name: "n".into() }, Type::Str)]);
+
+        assert_eq!(
+            annotation,
+            Type::Function {
+                parameters: vec![vector.clone(), record.clone()],
+                returns: Box::new(Type::Bool),
+            }
+        );
+        assert_eq!(
+            parameters
+                .iter()
+                .map(|parameter| parameter.annotation.clone())
+                .collect::<Vec<_>>(),
+            vec![Some(vector), Some(record)]
+        );
+        assert!(parameters[1].default.is_some());
+        assert_eq!(returns, Some(Type::Bool));
+
+        for input in [
+            "let a: float = 1;",
+            "let a: [int = 1;",
+            "let a: { n: int, n: str } = 1;",
+            "let a: { n } = 1;",
+            "fn(a: 1) { a };",
+        ] {
+            let mut parser = Parser::new(Lexer::new(input)).unwrap();
+
+            assert!(matches!(parser.parse_statement(), Err(Error::Type(_))));
+        }
+    }
 }
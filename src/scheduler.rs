@@ -0,0 +1,310 @@
+//! Green threads started with `spawn` or by calling async functions, which
+//! run on coroutines and take turns whenever the running thread blocks on a
+//! channel or future.
+//!
+//! Threads are cooperative: a thread runs until it blocks, which suspends its
+//! coroutine back to whichever evaluator runs the queued threads. That is the
+//! evaluator which blocked first, usually the one running the program.
+//!
+//! Once every thread is blocked, the event loop settles the futures of the
+//! next timer or operation of the host to complete.
+//!
+//! As every thread is a coroutine, it is also an OS thread until it finishes,
+//! see the `coroutine` module for what that costs.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    coroutine::Coroutine,
+    eval::error::Error,
+    object::{Future, Reference, Shareable, Unit},
+};
+
+/// What happens when a timer fires.
+pub enum Timer {
+    /// Resolves the future with unit, see `sleep`.
+    Resolve(Reference),
+    /// Fails the future unless it is settled already, see `timeout`.
+    Expire(Reference),
+}
+
+/// Measures the milliseconds passed since the event loop was created.
+enum Clock {
+    Real(Instant),
+    /// Only advances when every thread waits for a timer, by jumping to the
+    /// next one, so that timers fire in order without any real waiting.
+    Virtual(u64),
+}
+
+impl Clock {
+    fn now(&self) -> u64 {
+        match self {
+            Clock::Real(start) => start.elapsed().as_millis() as u64,
+            Clock::Virtual(now) => *now,
+        }
+    }
+}
+
+/// The outcome of an operation of the host, sent by its `Completer` along
+/// with the number of the operation.
+type Completion = (usize, Result<Shareable, String>);
+
+struct State {
+    /// The threads waiting for their turn, which are suspended while blocked
+    /// or not started yet.
+    threads: VecDeque<Coroutine>,
+    /// Counts the operations that could unblock a thread, e.g. sending or
+    /// receiving a value, so that rounds without any can be detected.
+    progress: usize,
+    clock: Clock,
+    /// The pending timers by deadline, in the order they were started.
+    timers: BTreeMap<(u64, usize), Timer>,
+    started: usize,
+    completed: Sender<Completion>,
+    completions: Receiver<Completion>,
+    /// The number of operations of the host started so far.
+    operations: usize,
+    /// The futures of the operations of the host that haven't completed yet,
+    /// by the numbers of the operations.
+    outstanding: HashMap<usize, Reference>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        let (completed, completions) = mpsc::channel();
+
+        Self {
+            threads: VecDeque::new(),
+            progress: 0,
+            clock: Clock::Real(Instant::now()),
+            timers: BTreeMap::new(),
+            started: 0,
+            completed,
+            completions,
+            operations: 0,
+            outstanding: HashMap::new(),
+        }
+    }
+}
+
+/// Settles the future of an operation of the host, from any thread.
+///
+/// A completer dropped without completing fails its future, so that nothing
+/// waits for it forever.
+pub struct Completer {
+    operation: Option<usize>,
+    completed: Sender<Completion>,
+}
+
+impl Completer {
+    /// Resolves the future with a value, or fails it with an error carrying
+    /// the message.
+    ///
+    /// Only [shareable](Reference::share) values can be sent from the thread
+    /// the operation completes on.
+    pub fn complete(mut self, outcome: Result<Shareable, String>) {
+        if let Some(operation) = self.operation.take() {
+            let _ = self.completed.send((operation, outcome));
+        }
+    }
+}
+
+impl Drop for Completer {
+    fn drop(&mut self) {
+        if let Some(operation) = self.operation.take() {
+            let _ = self.completed.send((
+                operation,
+                Err("The host dropped the operation without completing it.".into()),
+            ));
+        }
+    }
+}
+
+/// The queue of green threads, shared by the evaluators of a program.
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    state: Arc<Mutex<State>>,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("threads", &self.state().threads.len())
+            .finish()
+    }
+}
+
+impl Scheduler {
+    fn state(&self) -> MutexGuard<'_, State> {
+        // Threads never run while the state is locked, so it can't be
+        // poisoned by them.
+        self.state.lock().unwrap()
+    }
+
+    /// Queues `thread` to run once the running thread blocks.
+    pub fn spawn(&self, thread: Coroutine) {
+        let mut state = self.state();
+        state.threads.push_back(thread);
+        state.progress += 1;
+    }
+
+    /// Records an operation that could unblock a thread.
+    pub fn progressed(&self) {
+        self.state().progress += 1;
+    }
+
+    pub fn progress(&self) -> usize {
+        self.state().progress
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().threads.is_empty()
+    }
+
+    /// Runs every queued thread until it blocks or finishes, giving whether
+    /// any thread was queued.
+    ///
+    /// Fails with the error of the first thread failing, which is dropped.
+    pub fn run_round(&self) -> Result<bool, Error> {
+        let queued = self.state().threads.len();
+
+        for _ in 0..queued {
+            let Some(mut thread) = self.state().threads.pop_front() else {
+                break;
+            };
+
+            match thread.resume() {
+                Ok(Some(_)) => self.state().threads.push_back(thread),
+                Ok(None) => self.progressed(),
+                Err(e) => {
+                    self.progressed();
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(queued > 0)
+    }
+
+    /// Drops every queued thread, unwinding those which are suspended.
+    pub fn clear(&self) {
+        let threads = std::mem::take(&mut self.state().threads);
+        drop(threads);
+        self.state().timers.clear();
+    }
+
+    /// Lets the clock only advance to the next timer once every thread waits.
+    pub fn use_virtual_clock(&self) {
+        let mut state = self.state();
+        state.clock = Clock::Virtual(state.clock.now());
+    }
+
+    /// The milliseconds passed on the clock since the scheduler was created.
+    pub fn now(&self) -> u64 {
+        self.state().clock.now()
+    }
+
+    /// Starts a timer firing `ms` milliseconds from now.
+    pub fn start_timer(&self, ms: u64, timer: Timer) {
+        let mut state = self.state();
+        let deadline = state.clock.now().saturating_add(ms);
+        state.started += 1;
+        let started = state.started;
+        state.timers.insert((deadline, started), timer);
+    }
+
+    /// Creates a future settled by the returned completer once the host's
+    /// operation completes.
+    pub fn pending(&self) -> (Reference, Completer) {
+        let mut state = self.state();
+        state.operations += 1;
+        let operation = state.operations;
+
+        let future = Future::erased();
+        state.outstanding.insert(operation, future.clone());
+        let completer = Completer {
+            operation: Some(operation),
+            completed: state.completed.clone(),
+        };
+
+        (future, completer)
+    }
+
+    /// Settles the futures of the next timers or operation of the host to
+    /// complete, waiting for them on the real clock.
+    ///
+    /// Gives `false` if there is nothing left to wait for.
+    pub fn advance(&self) -> bool {
+        let mut state = self.state();
+
+        // Operations that completed already come first, as do those which
+        // complete before the next timer.
+        let completion = match (state.timers.first_key_value(), state.outstanding.len()) {
+            (_, 0) => None,
+            (None, _) => state.completions.recv().ok(),
+            (Some(_), _) if matches!(state.clock, Clock::Virtual(_)) => {
+                state.completions.try_recv().ok()
+            }
+            (Some(((deadline, _), _)), _) => {
+                let wait = deadline.saturating_sub(state.clock.now());
+                state
+                    .completions
+                    .recv_timeout(Duration::from_millis(wait))
+                    .ok()
+            }
+        };
+
+        if let Some((operation, outcome)) = completion {
+            // Operations are only ever completed once.
+            let future = state.outstanding.remove(&operation).unwrap();
+            state.progress += 1;
+            Future::settle(
+                &future,
+                outcome.map(|value| value.reference()).map_err(Error::Eval),
+            );
+            return true;
+        }
+
+        let Some((&(deadline, _), _)) = state.timers.first_key_value() else {
+            return false;
+        };
+
+        match &mut state.clock {
+            Clock::Virtual(now) => *now = (*now).max(deadline),
+            Clock::Real(start) => {
+                let wait = deadline.saturating_sub(start.elapsed().as_millis() as u64);
+                std::thread::sleep(Duration::from_millis(wait));
+            }
+        }
+
+        let now = state.clock.now();
+        while let Some(entry) = state.timers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            match entry.remove() {
+                Timer::Resolve(future) => {
+                    Future::settle(&future, Ok(Unit::erased()));
+                }
+                Timer::Expire(future) => {
+                    Future::settle(
+                        &future,
+                        Err(Error::Timeout("The operation timed out.".into())),
+                    );
+                }
+            }
+        }
+
+        state.progress += 1;
+        true
+    }
+}
@@ -1,46 +1,239 @@
 use std::{
     borrow::Borrow,
+    cell::Cell,
     collections::{HashMap, HashSet},
+    io,
+    rc::Rc,
+    thread::{self, JoinHandle, Scope, ScopedJoinHandle},
 };
 
-use crate::{builtin::builtins, object::Reference};
+use crate::{builtin::builtins, coroutine::Handoff, object::Reference};
+
+/// The number of nested calls allowed unless configured otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// The size of the native stacks evaluation continues on once the one it runs
+/// on is about to overflow, which is also the size of the threads it starts.
+pub const NATIVE_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+/// The size of the native stacks coroutines start on. It is kept small as
+/// every generator and green thread has its own, and evaluation continues on
+/// stacks of [`NATIVE_STACK_SIZE`] once a coroutine recurses deeper.
+pub const COROUTINE_STACK_SIZE: usize = 512 * 1024;
+
+/// How much room has to be left on a native stack to evaluate another node on
+/// it. It has to fit everything done between two nested evaluations, even in
+/// debug builds where that takes up a lot more room.
+const RED_ZONE: usize = 256 * 1024;
+
+thread_local! {
+    /// The address below which the native stack of the current thread is about
+    /// to overflow, or zero until it is known.
+    static LIMIT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The address the native stack currently ends at, as it grows downwards.
+#[inline(always)]
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Whether the native stack of the current thread is about to overflow.
+///
+/// The stacks of threads which weren't started by [`spawn`], e.g. those of
+/// the host, are looked up once. Where that isn't supported they are treated
+/// as exhausted.
+pub fn exhausted() -> bool {
+    let mut limit = LIMIT.get();
+
+    if limit == 0 {
+        limit = stack_bottom().map_or(usize::MAX, |bottom| bottom + RED_ZONE);
+        LIMIT.set(limit);
+    }
+
+    stack_pointer() < limit
+}
+
+/// The lowest address of the native stack of the current thread.
+#[cfg(target_os = "linux")]
+fn stack_bottom() -> Option<usize> {
+    use std::ffi::{c_int, c_void};
+
+    /// Large and aligned enough for `pthread_attr_t` on every target, whose
+    /// contents are only accessed by the functions below.
+    #[repr(C, align(16))]
+    struct Attributes([u8; 128]);
+
+    extern "C" {
+        fn pthread_self() -> usize;
+        fn pthread_getattr_np(thread: usize, attr: *mut Attributes) -> c_int;
+        fn pthread_attr_getstack(
+            attr: *const Attributes,
+            addr: *mut *mut c_void,
+            size: *mut usize,
+        ) -> c_int;
+        fn pthread_attr_destroy(attr: *mut Attributes) -> c_int;
+    }
+
+    let mut attr = Attributes([0; 128]);
+    let mut addr = std::ptr::null_mut();
+    let mut size = 0;
+
+    unsafe {
+        if pthread_getattr_np(pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+
+        let ret = pthread_attr_getstack(&attr, &mut addr, &mut size);
+        pthread_attr_destroy(&mut attr);
+
+        (ret == 0 && !addr.is_null()).then_some(addr as usize)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn stack_bottom() -> Option<usize> {
+    None
+}
+
+/// Runs `f` on a new native stack of [`NATIVE_STACK_SIZE`], blocking until it
+/// returns, so that deep evaluations don't overflow the current one.
+///
+/// The current thread doesn't run meanwhile, so `f` may use what isn't `Send`
+/// as long as it doesn't depend on the thread it is used on.
+pub(crate) fn grow<R>(f: impl FnOnce() -> R) -> io::Result<R> {
+    let f = Handoff(f);
+
+    thread::scope(|scope| {
+        let segment = spawn_scoped(scope, NATIVE_STACK_SIZE, move || {
+            let f = f;
+            Handoff((f.0)())
+        })?;
+
+        match segment.join() {
+            Ok(Handoff(ret)) => Ok(ret),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    })
+}
+
+/// Starts a thread with a native stack of `size`, whose room [`exhausted`]
+/// keeps track of.
+pub fn spawn<T: Send + 'static>(
+    size: usize,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> io::Result<JoinHandle<T>> {
+    thread::Builder::new().stack_size(size).spawn(move || {
+        limit(size);
+        f()
+    })
+}
+
+/// Like [`spawn`], for a thread of `scope`.
+pub fn spawn_scoped<'scope, T: Send + 'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    size: usize,
+    f: impl FnOnce() -> T + Send + 'scope,
+) -> io::Result<ScopedJoinHandle<'scope, T>> {
+    thread::Builder::new()
+        .stack_size(size)
+        .spawn_scoped(scope, move || {
+            limit(size);
+            f()
+        })
+}
+
+/// Records that the native stack of the current thread, which was just
+/// started, is `size` bytes large.
+fn limit(size: usize) {
+    let limit = stack_pointer().saturating_sub(size.saturating_sub(RED_ZONE));
+    LIMIT.set(limit);
+}
 
 #[derive(Debug)]
-struct Frame {
+pub struct Frame {
     scope: Vec<HashSet<String>>,
-    vars: HashMap<String, Vec<(Reference, u32)>>,
+    /// Every binding of a name, innermost last, with the id of its scope and
+    /// whether it is constant.
+    vars: HashMap<String, Vec<(Reference, u32, bool)>>,
+}
+
+impl Frame {
+    fn new() -> Self {
+        Self {
+            scope: vec![HashSet::new()],
+            vars: HashMap::new(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Stack {
     frames: Vec<Frame>,
+    /// Visible in every frame unless shadowed, and shared by all of them.
+    builtins: Rc<HashMap<String, Reference>>,
+    /// The number of frames above the global one at which `push_frame` fails.
+    max_depth: usize,
 }
 
 impl Stack {
     pub fn new() -> Self {
         Self {
-            frames: vec![Frame {
-                scope: vec![HashSet::new()],
-                vars: builtins()
-                    .into_iter()
-                    .map(|(k, v)| (k, vec![(v, 0)]))
-                    .collect(),
-            }],
+            frames: vec![Frame::new()],
+            builtins: Rc::new(builtins()),
+            max_depth: DEFAULT_MAX_DEPTH,
         }
     }
 
-    pub fn push_frame(&mut self) {
-        self.frames.push(Frame {
-            scope: vec![HashSet::new()],
-            vars: builtins()
-                .into_iter()
-                .map(|(k, v)| (k, vec![(v, 0)]))
-                .collect(),
-        })
+    /// Adds a builtin, or replaces the one named `name`.
+    pub fn define(&mut self, name: String, value: Reference) {
+        Rc::make_mut(&mut self.builtins).insert(name, value);
+    }
+
+    /// The builtins, including those defined with `define`.
+    pub fn builtins(&self) -> impl Iterator<Item = (&String, &Reference)> {
+        self.builtins.iter()
+    }
+
+    /// Uses the builtins of `other`, including those it defined.
+    pub fn share_builtins(&mut self, other: &Stack) {
+        self.builtins = other.builtins.clone();
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
     }
 
-    pub fn pop_frame(&mut self) {
-        self.frames.pop();
+    /// The number of frames above the global one, i.e. of nested calls.
+    pub fn depth(&self) -> usize {
+        self.frames.len() - 1
+    }
+
+    pub fn push_frame(&mut self) -> Result<(), String> {
+        if self.depth() >= self.max_depth {
+            return Err(format!(
+                "Maximum recursion depth of {} exceeded.",
+                self.max_depth
+            ));
+        }
+
+        self.frames.push(Frame::new());
+        Ok(())
+    }
+
+    pub fn pop_frame(&mut self) -> Option<Frame> {
+        self.frames.pop()
+    }
+
+    /// Pushes a frame previously popped with `pop_frame`, e.g. to continue the
+    /// call it belongs to on another stack.
+    pub fn resume_frame(&mut self, frame: Frame) {
+        self.frames.push(frame);
     }
 
     fn scope(&self) -> &Vec<HashSet<String>> {
@@ -51,7 +244,7 @@ impl Stack {
         &mut self.frames.last_mut().unwrap().scope
     }
 
-    fn vars_mut(&mut self) -> &mut HashMap<String, Vec<(Reference, u32)>> {
+    fn vars_mut(&mut self) -> &mut HashMap<String, Vec<(Reference, u32, bool)>> {
         &mut self.frames.last_mut().unwrap().vars
     }
 
@@ -67,7 +260,7 @@ impl Stack {
         self.vars_mut()
             .entry(ident.clone())
             .or_insert(vec![])
-            .push((val, cur_id));
+            .push((val, cur_id, false));
     }
 
     pub fn push(&mut self) {
@@ -82,9 +275,9 @@ impl Stack {
 
         for ident in out {
             if let Some(mut scope) = self.vars_mut().remove(&ident) {
-                while let Some((val, id)) = scope.pop() {
+                while let Some((val, id, constant)) = scope.pop() {
                     if id < prev_id {
-                        scope.push((val, id));
+                        scope.push((val, id, constant));
                         break;
                     }
                     drop(val)
@@ -97,21 +290,95 @@ impl Stack {
         }
     }
 
+    /// The bindings declared in the outermost scope of the current frame.
+    pub fn globals(&self) -> Vec<(String, Reference)> {
+        let frame = self.frames.last().unwrap();
+
+        frame.scope[0]
+            .iter()
+            .filter_map(|ident| {
+                let (val, _, _) = frame.vars.get(ident)?.last()?;
+                Some((ident.clone(), val.clone()))
+            })
+            .collect()
+    }
+
+    /// The innermost binding of `ident`, or the builtin named `ident`.
+    ///
+    /// Macros expand the names they use without binding them to `#name`,
+    /// which refers to the binding of `name` in the outermost scope of the
+    /// frame instead, so that the code they are expanded into can't shadow it.
     pub fn get(&mut self, ident: impl Borrow<String>) -> Option<Reference> {
-        self.vars_mut()
-            .get(ident.borrow())
-            .and_then(|var| var.last())
-            .map(|(obj, _)| obj.clone())
+        let ident = ident.borrow();
+
+        if let Some((obj, _, _)) = self.vars_mut().get(ident).and_then(|var| var.last()) {
+            return Some(obj.clone());
+        }
+
+        let ident = match ident.strip_prefix('#') {
+            Some(name) => {
+                let frame = self.frames.last().unwrap();
+                if frame.scope[0].contains(name) {
+                    return frame.vars.get(name)?.first().map(|(obj, _, _)| obj.clone());
+                }
+
+                name
+            }
+            None => ident,
+        };
+
+        self.builtins.get(ident).cloned()
+    }
+
+    /// Whether the innermost binding of `ident` is constant.
+    pub fn is_constant(&self, ident: &str) -> bool {
+        let frame = self.frames.last().unwrap();
+
+        matches!(
+            frame.vars.get(ident).and_then(|var| var.last()),
+            Some((_, _, true))
+        )
     }
 
     pub fn take(&mut self, ident: impl Borrow<String>) -> Option<Reference> {
         self.vars_mut()
             .get_mut(ident.borrow())
             .and_then(|var| var.pop())
-            .map(|(obj, _)| obj)
+            .map(|(obj, _, _)| obj)
+    }
+
+    /// Rebinds `ident` to `val`.
+    ///
+    /// Fails if the binding of `ident` that is currently visible is constant.
+    pub fn assign(&mut self, ident: String, val: Reference) -> Result<(), String> {
+        if let Some((_, _, true)) = self.vars_mut().get(&ident).and_then(|var| var.last()) {
+            return Err(format!("Cannot assign to constant `{ident}`."));
+        }
+
+        self.bind(ident, val, false);
+
+        Ok(())
+    }
+
+    /// Declares `ident` as `val` in the current scope, shadowing bindings of
+    /// outer scopes.
+    ///
+    /// Fails if `ident` is already declared as a constant in the current scope.
+    pub fn declare(&mut self, ident: String, val: Reference, constant: bool) -> Result<(), String> {
+        let cur_id = self.scope().len() as u32 - 1;
+
+        if let Some((_, id, true)) = self.vars_mut().get(&ident).and_then(|var| var.last()) {
+            if *id >= cur_id {
+                return Err(format!("Cannot redeclare constant `{ident}`."));
+            }
+        }
+
+        self.bind(ident, val, constant);
+
+        Ok(())
     }
 
-    pub fn assign(&mut self, ident: String, val: Reference) {
+    fn bind(&mut self, ident: String, val: Reference, constant: bool) {
         let cur_id = self.scope().len() as u32 - 1;
 
         self.scope_mut().last_mut().unwrap().insert(ident.clone());
@@ -122,14 +389,14 @@ impl Stack {
 
         let scope = self.vars_mut().entry(ident).or_insert(vec![]);
 
-        while let Some((val, id)) = scope.pop() {
+        while let Some((val, id, constant)) = scope.pop() {
             if id < cur_id {
-                scope.push((val, id));
+                scope.push((val, id, constant));
                 break;
             }
             drop(val)
         }
 
-        scope.push((val, cur_id));
+        scope.push((val, cur_id, constant));
     }
 }
@@ -9,6 +9,7 @@ pub enum Token {
     Str(String),
     Operator(Operator),
     Comma,
+    Colon,
     Semicolon,
     LParen,
     RParen,
@@ -43,12 +44,44 @@ pub enum Operator {
     Pipe,
     RightArrow,
     LeftArrow,
+    Spread,
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Operator::Assign => "=",
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Divide => "/",
+            Operator::Multiply => "*",
+            Operator::PlusEqual => "+=",
+            Operator::MinusEqual => "-=",
+            Operator::Dot => ".",
+            Operator::Bang => "!",
+            Operator::Hook => "?",
+            Operator::Less => "<",
+            Operator::Greater => ">",
+            Operator::LessOrEqual => "<=",
+            Operator::GreaterOrEqual => ">=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Ampersand => "&",
+            Operator::Pipe => "|",
+            Operator::RightArrow => "->",
+            Operator::LeftArrow => "<-",
+            Operator::Spread => "..",
+        })
+    }
 }
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Keyword {
     Let,
+    Const,
     Function,
     Def,
     If,
@@ -56,12 +89,29 @@ pub enum Keyword {
     True,
     False,
     Return,
+    Enum,
+    Match,
+    For,
+    In,
+    Yield,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    Import,
+    As,
+    Export,
+    Macro,
+    Spawn,
+    Async,
+    Await,
 }
 
 impl Keyword {
     fn match_ident(ident: impl AsRef<str>) -> Result<Self, ()> {
         match ident.as_ref() {
             "let" => Ok(Self::Let),
+            "const" => Ok(Self::Const),
             "fn" => Ok(Self::Function),
             "def" => Ok(Self::Def),
             "true" => Ok(Self::True),
@@ -69,6 +119,22 @@ impl Keyword {
             "if" => Ok(Self::If),
             "else" => Ok(Self::Else),
             "return" => Ok(Self::Return),
+            "enum" => Ok(Self::Enum),
+            "match" => Ok(Self::Match),
+            "for" => Ok(Self::For),
+            "in" => Ok(Self::In),
+            "yield" => Ok(Self::Yield),
+            "throw" => Ok(Self::Throw),
+            "try" => Ok(Self::Try),
+            "catch" => Ok(Self::Catch),
+            "finally" => Ok(Self::Finally),
+            "import" => Ok(Self::Import),
+            "as" => Ok(Self::As),
+            "export" => Ok(Self::Export),
+            "macro" => Ok(Self::Macro),
+            "spawn" => Ok(Self::Spawn),
+            "async" => Ok(Self::Async),
+            "await" => Ok(Self::Await),
             _ => Err(()),
         }
     }